@@ -41,6 +41,7 @@ impl CharBuffer {
     /// let mut cb = CharBuffer::from_str("rust");
     /// assert_eq!(cb.length(), 4)
     /// ```
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Self {
         let cb = s.chars().collect();
         Self {
@@ -50,7 +51,7 @@ impl CharBuffer {
     }
 
     pub fn is_remain(&self) -> bool {
-        return self.pos < self.char_buffer.len();
+        self.pos < self.char_buffer.len()
     }
 
     /// Convert `char_buffer` to `String`.
@@ -65,6 +66,7 @@ impl CharBuffer {
     /// let cb = CharBuffer::from_str("Hello");
     /// assert_eq!("Hello".to_string(), cb.to_string());
     /// ```
+    #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
         let s: String = self.char_buffer.iter().collect();
         s
@@ -169,7 +171,7 @@ impl CharBuffer {
         if let Some(c) = self.next() {
             return self.is_digit(c);
         };
-        return false;
+        false
     }
 
     /// Access next character in `char_buffer` and convert it to digit.If there are no characters in `char_buffer`, return `None`.
@@ -187,10 +189,7 @@ impl CharBuffer {
     /// assert_eq!(Some(43), cb_alpha.next_with_digit());
     /// ```
     pub fn next_with_digit(&self) -> Option<usize> {
-        match self.next() {
-            None => None,
-            Some(c) => Some(self.to_digit(c)),
-        }
+        self.next().map(|c| self.to_digit(c))
     }
 
     /// Access next character in `char_buffer` and convert it to digit and progress position.
@@ -209,10 +208,7 @@ impl CharBuffer {
     /// assert_eq!(cb.position(), 1);
     /// ```
     pub fn next_with_digit_and_progress(&mut self) -> Option<usize> {
-        match self.next_with_progress() {
-            None => None,
-            Some(c) => Some(self.to_digit(c)),
-        }
+        self.next_with_progress().map(|c| self.to_digit(c))
     }
 
     #[inline]
@@ -1,3 +1,5 @@
+use crate::graph::VertexIdx;
+use std::fmt;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, RuatomError>;
@@ -5,17 +7,17 @@ pub type Result<T> = std::result::Result<T, RuatomError>;
 #[derive(Error, Debug, PartialEq, Eq)]
 pub enum RuatomError {
     #[error("no such vertex `{0}`")]
-    NoSuchVertex(u8),
+    NoSuchVertex(VertexIdx),
     #[error("already existed vertex `{0}`")]
-    ExistedVertex(u8),
+    ExistedVertex(VertexIdx),
     #[error("edge existed: : `{0}` `{1}`")]
-    ExistedEdge(u8, u8),
+    ExistedEdge(VertexIdx, VertexIdx),
     #[error("no such edge: `{0}` `{1}`")]
-    NoSuchEdge(u8, u8),
+    NoSuchEdge(VertexIdx, VertexIdx),
     #[error("invalid edge: `{0}` `{1}`")]
-    InvalidEdge(u8, u8),
+    InvalidEdge(VertexIdx, VertexIdx),
     #[error("not any edge include this vertex: `{0}`")]
-    NoEdgeInclude(u8),
+    NoEdgeInclude(VertexIdx),
     #[error("unknown error")]
     Unknown,
 
@@ -28,6 +30,9 @@ pub enum RuatomError {
     #[error("invalid molecule: `{0}`")]
     IllegalMolecule(&'static str),
 
+    #[error("no Kekulé structure, atoms {0:?} get no double bond")]
+    NoKekuleStructure(Vec<VertexIdx>),
+
     #[error("cann't transform to another atom")]
     TransformError,
 
@@ -40,6 +45,87 @@ pub enum RuatomError {
     #[error("invalid SMILES: `{0}`")]
     IllegalSMILES(&'static str),
 
+    #[error("invalid SMILES: {0}")]
+    SmilesSyntax(SmilesError),
+
+    #[error("invalid SMARTS at {1}: `{0}`")]
+    IllegalSMARTS(&'static str, usize),
+
+    #[error("invalid molecule key: `{0}`")]
+    IllegalMolKey(String),
+
+    #[error("invalid molfile at line {0}: `{1}`")]
+    IllegalMolfile(usize, &'static str),
+
+    #[error("invalid SDF record {0}: {1}")]
+    IllegalSdfRecord(usize, Box<RuatomError>),
+
+    #[error("invalid SMILES record at line {0} `{1}`: {2}")]
+    IllegalSmilesRecord(usize, String, Box<RuatomError>),
+
     #[error("std error: `{0}`")]
     StdError(String),
 }
+
+/// A SMILES parse failure located in the input.
+///
+/// `position` is the character offset where the parser failed and `token` the
+/// text at fault there. For unclosed ring bonds and branches `token` is the
+/// opening ring number or `(` and `opened` its offset.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SmilesError {
+    pub smiles: String,
+    pub position: usize,
+    pub token: String,
+    pub opened: Option<usize>,
+    pub reason: Box<RuatomError>,
+}
+
+impl SmilesError {
+    /// The SMILES with carets under the problem and the reason behind them.
+    ///
+    /// ```
+    /// use ruatom::{error::RuatomError, Parser};
+    ///
+    /// let Err(RuatomError::SmilesSyntax(e)) = Parser::new("CCC(C").parse() else {
+    ///     panic!("expected a SMILES syntax error");
+    /// };
+    /// assert_eq!(e.render(), "CCC(C\n   ^ unclosed branch");
+    /// ```
+    pub fn render(&self) -> String {
+        let at = self.opened.unwrap_or(self.position);
+        let width = self.token.chars().count().max(1);
+        format!(
+            "{}\n{}{} {}",
+            self.smiles,
+            " ".repeat(at),
+            "^".repeat(width),
+            Reason(&self.reason)
+        )
+    }
+}
+
+/// Fixed messages without the `invalid SMILES` prefix.
+struct Reason<'a>(&'a RuatomError);
+
+impl fmt::Display for Reason<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            RuatomError::IllegalSMILES(msg) => write!(f, "{}", msg),
+            e => write!(f, "{}", e),
+        }
+    }
+}
+
+impl fmt::Display for SmilesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", Reason(&self.reason), self.position)?;
+        if !self.token.is_empty() {
+            write!(f, " `{}`", self.token)?;
+        }
+        if let Some(at) = self.opened {
+            write!(f, ", opened at {}", at)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,303 @@
+use super::BitVector;
+use crate::error::Result;
+use crate::molecule::Molecule;
+use crate::smarts::{QueryMol, SmartsParser};
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+/// Number of bits of [`maccs_keys`], bit 0 being unused.
+pub const MACCS_LENGTH: usize = 167;
+
+/// The 166 public MACCS keys as SMARTS and the number of unique matches to
+/// exceed, `?` for the keys computed by hand.
+const KEYS: [(&str, usize); 166] = [
+    ("?", 0), // 1 isotope
+    ("[#104]", 0),
+    ("[#32,#33,#34,#50,#51,#52,#82,#83,#84]", 0),
+    ("[Ac,Th,Pa,U,Np,Pu,Am,Cm,Bk,Cf,Es,Fm,Md,No,Lr]", 0),
+    ("[Sc,Ti,Y,Zr,Hf]", 0),
+    ("[La,Ce,Pr,Nd,Pm,Sm,Eu,Gd,Tb,Dy,Ho,Er,Tm,Yb,Lu]", 0),
+    ("[V,Cr,Mn,Nb,Mo,Tc,Ta,W,Re]", 0),
+    ("[!#6;!#1]1~*~*~*~1", 0),
+    ("[Fe,Co,Ni,Ru,Rh,Pd,Os,Ir,Pt]", 0),
+    ("[Be,Mg,Ca,Sr,Ba,Ra]", 0), // 10
+    ("*1~*~*~*~1", 0),
+    ("[Cu,Zn,Ag,Cd,Au,Hg]", 0),
+    ("[#8]~[#7](~[#6])~[#6]", 0),
+    ("[#16]-[#16]", 0),
+    ("[#8]~[#6](~[#8])~[#8]", 0),
+    ("[!#6;!#1]1~*~*~1", 0),
+    ("[#6]#[#6]", 0),
+    ("[#5,#13,#31,#49,#81]", 0),
+    ("*1~*~*~*~*~*~*~1", 0),
+    ("[#14]", 0), // 20
+    ("[#6]=[#6](~[!#6;!#1])~[!#6;!#1]", 0),
+    ("*1~*~*~1", 0),
+    ("[#7]~[#6](~[#8])~[#8]", 0),
+    ("[#7]-[#8]", 0),
+    ("[#7]~[#6](~[#7])~[#7]", 0),
+    ("[#6]=;@[#6](@*)@*", 0),
+    ("[I]", 0),
+    ("[!#6;!#1]~[CH2]~[!#6;!#1]", 0),
+    ("[#15]", 0),
+    ("[#6]~[!#6;!#1](~[#6])(~[#6])~*", 0), // 30
+    ("[!#6;!#1]~[F,Cl,Br,I]", 0),
+    ("[#6]~[#16]~[#7]", 0),
+    ("[#7]~[#16]", 0),
+    ("[CH2]=*", 0),
+    ("[Li,Na,K,Rb,Cs,Fr]", 0),
+    ("[#16R]", 0),
+    ("[#7]~[#6](~[#8])~[#7]", 0),
+    ("[#7]~[#6](~[#6])~[#7]", 0),
+    ("[#8]~[#16](~[#8])~[#8]", 0),
+    ("[#16]-[#8]", 0), // 40
+    ("[#6]#[#7]", 0),
+    ("F", 0),
+    ("[!#6;!#1;!H0]~*~[!#6;!#1;!H0]", 0),
+    ("?", 0), // 44 other elements
+    ("[#6]=[#6]~[#7]", 0),
+    ("Br", 0),
+    ("[#16]~*~[#7]", 0),
+    ("[#8]~[!#6;!#1](~[#8])(~[#8])", 0),
+    ("[!+0]", 0),
+    ("[#6]=[#6](~[#6])~[#6]", 0), // 50
+    ("[#6]~[#16]~[#8]", 0),
+    ("[#7]~[#7]", 0),
+    ("[!#6;!#1;!H0]~*~*~*~[!#6;!#1;!H0]", 0),
+    ("[!#6;!#1;!H0]~*~*~[!#6;!#1;!H0]", 0),
+    ("[#8]~[#16]~[#8]", 0),
+    ("[#8]~[#7](~[#8])~[#6]", 0),
+    ("[#8R]", 0),
+    ("[!#6;!#1]~[#16]~[!#6;!#1]", 0),
+    ("[#16]!:*:*", 0),
+    ("[#16]=[#8]", 0), // 60
+    ("*~[#16](~*)~*", 0),
+    ("*@*!@*@*", 0),
+    ("[#7]=[#8]", 0),
+    ("*@*!@[#16]", 0),
+    ("c:n", 0),
+    ("[#6]~[#6](~[#6])(~[#6])~*", 0),
+    ("[!#6;!#1]~[#16]", 0),
+    ("[!#6;!#1;!H0]~[!#6;!#1;!H0]", 0),
+    ("[!#6;!#1]~[!#6;!#1;!H0]", 0),
+    ("[!#6;!#1]~[#7]~[!#6;!#1]", 0), // 70
+    ("[#7]~[#8]", 0),
+    ("[#8]~*~*~[#8]", 0),
+    ("[#16]=*", 0),
+    ("[CH3]~*~[CH3]", 0),
+    ("*!@[#7]@*", 0),
+    ("[#6]=[#6](~*)~*", 0),
+    ("[#7]~*~[#7]", 0),
+    ("[#6]=[#7]", 0),
+    ("[#7]~*~*~[#7]", 0),
+    ("[#7]~*~*~*~[#7]", 0), // 80
+    ("[#16]~*(~*)~*", 0),
+    ("*~[CH2]~[!#6;!#1;!H0]", 0),
+    ("[!#6;!#1]1~*~*~*~*~1", 0),
+    ("[NH2]", 0),
+    ("[#6]~[#7](~[#6])~[#6]", 0),
+    ("[C;H2,H3][!#6;!#1][C;H2,H3]", 0),
+    ("[F,Cl,Br,I]!@*@*", 0),
+    ("[#16]", 0),
+    ("[#8]~*~*~*~[#8]", 0),
+    (
+        "[$([!#6;!#1;!H0]~*~*~[CH2]~*),$([!#6;!#1;!H0;R]1@[R]@[R]@[CH2;R]1),\
+         $([!#6;!#1;!H0]~[R]1@[R]@[CH2;R]1)]",
+        0,
+    ), // 90
+    (
+        "[$([!#6;!#1;!H0]~*~*~*~[CH2]~*),$([!#6;!#1;!H0;R]1@[R]@[R]@[R]@[CH2;R]1),\
+         $([!#6;!#1;!H0]~[R]1@[R]@[R]@[CH2;R]1),$([!#6;!#1;!H0]~*~[R]1@[R]@[CH2;R]1)]",
+        0,
+    ),
+    ("[#8]~[#6](~[#7])~[#6]", 0),
+    ("[!#6;!#1]~[CH3]", 0),
+    ("[!#6;!#1]~[#7]", 0),
+    ("[#7]~*~*~[#8]", 0),
+    ("*1~*~*~*~*~1", 0),
+    ("[#7]~*~*~*~[#8]", 0),
+    ("[!#6;!#1]1~*~*~*~*~*~1", 0),
+    ("[#6]=[#6]", 0),
+    ("*~[CH2]~[#7]", 0), // 100
+    (
+        "[$([R]@1@[R]@[R]@[R]@[R]@[R]@[R]@[R]1),$([R]@1@[R]@[R]@[R]@[R]@[R]@[R]@[R]@[R]1),\
+         $([R]@1@[R]@[R]@[R]@[R]@[R]@[R]@[R]@[R]@[R]1),\
+         $([R]@1@[R]@[R]@[R]@[R]@[R]@[R]@[R]@[R]@[R]@[R]1),\
+         $([R]@1@[R]@[R]@[R]@[R]@[R]@[R]@[R]@[R]@[R]@[R]@[R]1),\
+         $([R]@1@[R]@[R]@[R]@[R]@[R]@[R]@[R]@[R]@[R]@[R]@[R]@[R]1),\
+         $([R]@1@[R]@[R]@[R]@[R]@[R]@[R]@[R]@[R]@[R]@[R]@[R]@[R]@[R]1)]",
+        0,
+    ),
+    ("[!#6;!#1]~[#8]", 0),
+    ("Cl", 0),
+    ("[!#6;!#1;!H0]~*~[CH2]~*", 0),
+    ("*@*(@*)@*", 0),
+    ("[!#6;!#1]~*(~[!#6;!#1])~[!#6;!#1]", 0),
+    ("[F,Cl,Br,I]~*(~*)~*", 0),
+    ("[CH3]~*~*~*~[CH2]~*", 0),
+    ("*~[CH2]~[#8]", 0),
+    ("[#7]~[#6]~[#8]", 0), // 110
+    ("[#7]~*~[CH2]~*", 0),
+    ("*~*(~*)(~*)~*", 0),
+    ("[#8]!:*:*", 0),
+    ("[CH3]~[CH2]~*", 0),
+    ("[CH3]~*~[CH2]~*", 0),
+    ("[$([CH3]~*~*~[CH2]~*),$([CH3]~*1~*~[CH2]1)]", 0),
+    ("[#7]~*~[#8]", 0),
+    ("[$(*~[CH2]~[CH2]~*),$(*1~[CH2]~[CH2]1)]", 1),
+    ("[#7]=*", 0),
+    ("[!#6;R]", 1), // 120
+    ("[#7;R]", 0),
+    ("*~[#7](~*)~*", 0),
+    ("[#8]~[#6]~[#8]", 0),
+    ("[!#6;!#1]~[!#6;!#1]", 0),
+    ("?", 0), // 125 more than one aromatic ring
+    ("*!@[#8]!@*", 0),
+    ("*@*!@[#8]", 1),
+    (
+        "[$(*~[CH2]~*~*~*~[CH2]~*),$([R]1@[CH2;R]@[R]@[R]@[R]@[CH2;R]1),\
+         $(*~[CH2]~[R]1@[R]@[R]@[CH2;R]1),$(*~[CH2]~*~[R]1@[R]@[CH2;R]1)]",
+        0,
+    ),
+    (
+        "[$(*~[CH2]~*~*~[CH2]~*),$([R]1@[CH2]@[R]@[R]@[CH2;R]1),\
+         $(*~[CH2]~[R]1@[R]@[CH2;R]1)]",
+        0,
+    ),
+    ("[!#6;!#1]~[!#6;!#1]", 1), // 130
+    ("[!#6;!#1;!H0]", 1),
+    ("[#8]~*~[CH2]~*", 0),
+    ("*@*!@[#7]", 0),
+    ("[F,Cl,Br,I]", 0),
+    ("[#7]!:*:*", 0),
+    ("[#8]=*", 1),
+    ("[!C;!c;R]", 0),
+    ("[!#6;!#1]~[CH2]~*", 1),
+    ("[O;!H0]", 0),
+    ("[#8]", 3), // 140
+    ("[CH3]", 2),
+    ("[#7]", 1),
+    ("*@*!@[#8]", 0),
+    ("*!:*:*!:*", 0),
+    ("*1~*~*~*~*~*~1", 1),
+    ("[#8]", 2),
+    ("[$(*~[CH2]~[CH2]~*),$([R]1@[CH2;R]@[CH2;R]1)]", 0),
+    ("*~[!#6;!#1](~*)~*", 0),
+    ("[C;H3,H4]", 1),
+    ("*!@*@*!@*", 0), // 150
+    ("[#7;!H0]", 0),
+    ("[#8]~[#6](~[#6])~[#6]", 0),
+    ("[!#6;!#1]~[CH2]~*", 0),
+    ("[#6]=[#8]", 0),
+    ("*!@[CH2]!@*", 0),
+    ("[#7]~*(~*)~*", 0),
+    ("[#6]-[#8]", 0),
+    ("[#6]-[#7]", 0),
+    ("[#8]", 1),
+    ("[C;H3,H4]", 0), // 160
+    ("[#7]", 0),
+    ("a", 0),
+    ("*1~*~*~*~*~*~1", 0),
+    ("[#8]", 0),
+    ("[R]", 0),
+    ("?", 0), // 166 more than one fragment
+];
+
+fn queries() -> &'static [Option<(QueryMol, usize)>] {
+    static QUERIES: OnceLock<Vec<Option<(QueryMol, usize)>>> = OnceLock::new();
+    QUERIES.get_or_init(|| {
+        KEYS.iter()
+            .map(|(smarts, count)| match *smarts {
+                "?" => None,
+                s => Some((SmartsParser::new(s).parse().unwrap(), *count)),
+            })
+            .collect()
+    })
+}
+
+/// The 166 public MACCS structural keys of `mol` as a fixed bit vector,
+/// indexed by key number from 1.
+///
+/// Keys are the common SMARTS definitions, a key with a count set when
+/// more than that many atom sets match. Key 1 is any atom with an isotope,
+/// 44 any element other than H, C, N, O, Si, P, S, F, Cl, Br and I, 125
+/// more than one aromatic ring and 166 more than one fragment. `*` atoms
+/// never set key 44.
+///
+/// ```
+/// use ruatom::fingerprint::maccs_keys;
+/// use ruatom::Parser;
+///
+/// let m = Parser::new("c1ccccc1").parse().unwrap();
+/// assert_eq!(maccs_keys(&m).unwrap().bits(), vec![162, 163, 165]);
+/// ```
+pub fn maccs_keys(mol: &Molecule) -> Result<BitVector> {
+    let mut keys = BitVector::new(MACCS_LENGTH);
+    for (ix, query) in queries().iter().enumerate() {
+        let key = ix + 1;
+        let on = match query {
+            Some((q, 0)) => q.is_match(mol)?,
+            Some((q, count)) => q.unique_matches(mol)?.len() > *count,
+            None => special(mol, key)?,
+        };
+        keys.set(key, on);
+    }
+    Ok(keys)
+}
+
+fn special(mol: &Molecule, key: usize) -> Result<bool> {
+    match key {
+        1 => {
+            for at in mol.atoms().iter() {
+                if mol.atom_at(at)?.isotope() >= 0 {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        }
+        44 => {
+            for at in mol.atoms().iter() {
+                let atom = mol.atom_at(at)?;
+                if atom.ele_is_any() {
+                    continue;
+                }
+                let number = atom.element().atomic_number();
+                if !matches!(number, 1 | 6 | 7 | 8 | 9 | 14 | 15 | 16 | 17 | 35 | 53) {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        }
+        125 => {
+            let mut aromatic = 0;
+            for ring in mol.rings().iter() {
+                let mut all = true;
+                for at in ring.atoms().iter() {
+                    all &= mol.atom_at(at)?.is_aromatic();
+                }
+                aromatic += all as usize;
+            }
+            Ok(aromatic > 1)
+        }
+        166 => {
+            let mut seen = HashSet::new();
+            let mut fragments = 0;
+            for at in mol.atoms().iter() {
+                if !seen.insert(*at) {
+                    continue;
+                }
+                fragments += 1;
+                let mut stack = vec![*at];
+                while let Some(u) = stack.pop() {
+                    for n in mol.graph().neighbors(&u)? {
+                        if seen.insert(*n) {
+                            stack.push(*n);
+                        }
+                    }
+                }
+            }
+            Ok(fragments > 1)
+        }
+        _ => Ok(false),
+    }
+}
@@ -0,0 +1,56 @@
+//! Molecular fingerprints.
+//!
+//! [`Morgan`] hashes the circular environment of each atom, ECFP style, or
+//! with pharmacophore features in place of atom invariants, FCFP style.
+//! [`Paths`] hashes the linear paths, Daylight style, and serves as a
+//! substructure screen. [`maccs_keys`] computes the 166 public MACCS keys,
+//! [`AtomPairs`] and [`Torsions`] count atom types at a distance and along
+//! four atom paths.
+//!
+//! Folded bits go in a [`BitVector`] and sparse counts in a [`CountVector`],
+//! both compared through [`Fingerprint`], and [`SimilaritySearch`] ranks a
+//! database of either against a query.
+
+mod maccs;
+mod morgan;
+mod pairs;
+mod paths;
+mod similarity;
+
+pub use maccs::{maccs_keys, MACCS_LENGTH};
+pub use morgan::{Environment, Morgan};
+pub use pairs::{AtomPairs, Torsions};
+pub use paths::Paths;
+pub use similarity::{BitVector, CountVector, Fingerprint, Metric, SimilaritySearch};
+
+use crate::error::Result;
+use crate::molecule::{AtomIdx, Molecule};
+
+/// Bond order, 5 for aromatic bonds: flagged ones and the unmarked ring
+/// bonds between aromatic atoms.
+pub(crate) fn bond_code(mol: &Molecule, u: AtomIdx, v: AtomIdx) -> Result<i64> {
+    let bond = mol.edge_at(u, v)?;
+    let aromatic = bond.is_aromatic()
+        || (bond.ring_membership() > 0
+            && bond.electron() == 1
+            && !bond.is("-")
+            && mol.atom_at(&u)?.is_aromatic()
+            && mol.atom_at(&v)?.is_aromatic());
+    Ok(match aromatic {
+        true => 5,
+        false => bond.electron() as i64,
+    })
+}
+
+/// Heavy neighbours and hydrogens, implicit or as atoms.
+pub(crate) fn degree(mol: &Molecule, at: AtomIdx) -> Result<(i64, i64)> {
+    let mut heavy = 0;
+    let mut hydrogens = mol.hydrogen_count(&at)? as i64;
+    for n in mol.graph().neighbors(&at)? {
+        match mol.atom_at(n)?.element().atomic_number() {
+            1 => hydrogens += 1,
+            _ => heavy += 1,
+        }
+    }
+    Ok((heavy, hydrogens))
+}
@@ -0,0 +1,261 @@
+use super::{bond_code, degree, BitVector};
+use crate::error::Result;
+use crate::molecule::{canon::tetrahedral_parity, key::hash, AtomIdx, Molecule};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+/// An atom environment: the atoms within `radius` bonds of `centre`,
+/// hashed to `id`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Environment {
+    pub id: u32,
+    pub centre: AtomIdx,
+    pub radius: u32,
+}
+
+/// Extended-connectivity fingerprints, ECFP4 by default.
+///
+/// Each atom starts from a hash of its invariants, or of its pharmacophore
+/// features with [`Morgan::features`], and each iteration hashes in the
+/// identifiers of its neighbours and the bonds to them. An environment
+/// covering the same bonds as one found before is left out.
+///
+/// ```
+/// use ruatom::fingerprint::Morgan;
+/// use ruatom::Parser;
+///
+/// let m = Parser::new("CCO").parse().unwrap();
+/// let morgan = Morgan::new(1).length(1024);
+/// let bits = morgan.bits(&m).unwrap();
+/// assert_eq!(bits.len(), 6);
+/// assert!(bits.iter().all(|b| *b < 1024));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Morgan {
+    radius: u32,
+    length: usize,
+    features: bool,
+    chirality: bool,
+}
+
+impl Default for Morgan {
+    fn default() -> Self {
+        Self {
+            radius: 2,
+            length: 2048,
+            features: false,
+            chirality: false,
+        }
+    }
+}
+
+impl Morgan {
+    pub fn new(radius: u32) -> Self {
+        Self {
+            radius,
+            ..Self::default()
+        }
+    }
+
+    /// Number of bits [`Morgan::bits`] folds the identifiers into.
+    pub fn length(mut self, length: usize) -> Self {
+        self.length = length.max(1);
+        self
+    }
+
+    /// Pharmacophore features instead of atom invariants, FCFP style.
+    pub fn features(mut self, on: bool) -> Self {
+        self.features = on;
+        self
+    }
+
+    /// Tetrahedral configurations, once the neighbours of a centre tell
+    /// apart.
+    pub fn chirality(mut self, on: bool) -> Self {
+        self.chirality = on;
+        self
+    }
+
+    /// Environments of every radius up to the configured one, by radius
+    /// then by identifier.
+    pub fn environments(&self, mol: &Molecule) -> Result<Vec<Environment>> {
+        let atoms = mol.atoms();
+        let mut ids = Vec::with_capacity(atoms.len());
+        for at in atoms.iter() {
+            let invariants = match self.features {
+                true => features(mol, *at)?.to_vec(),
+                false => invariants(mol, *at)?.to_vec(),
+            };
+            ids.push(hash(&invariants) as u32);
+        }
+        let mut envs: Vec<Environment> = atoms
+            .iter()
+            .map(|at| Environment {
+                id: ids[*at as usize - 1],
+                centre: *at,
+                radius: 0,
+            })
+            .collect();
+        envs.sort_by_key(|e| (e.id, e.centre));
+
+        let mut index = BTreeMap::new();
+        for (ix, [u, v]) in mol.bonds().iter().enumerate() {
+            index.insert([*u.min(v), *u.max(v)], ix);
+        }
+        let mut covered: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); atoms.len()];
+        let mut seen: HashSet<BTreeSet<usize>> = HashSet::new();
+        for radius in 1..=self.radius {
+            let mut next = Vec::with_capacity(atoms.len());
+            let mut next_covered = Vec::with_capacity(atoms.len());
+            for at in atoms.iter() {
+                let ix = *at as usize - 1;
+                let mut bonds = covered[ix].clone();
+                let mut nbors = vec![];
+                for n in mol.graph().neighbors(at)? {
+                    nbors.push((bond_code(mol, *at, *n)?, ids[*n as usize - 1] as i64));
+                    bonds.insert(index[&[*at.min(n), *at.max(n)]]);
+                    bonds.extend(covered[*n as usize - 1].iter());
+                }
+                nbors.sort_unstable();
+                let mut values = vec![radius as i64, ids[ix] as i64];
+                values.extend(nbors.into_iter().flat_map(|(b, id)| [b, id]));
+                if self.chirality {
+                    if let Some(parity) = self.parity(mol, *at, &ids)? {
+                        values.push(parity as i64);
+                    }
+                }
+                next.push(hash(&values) as u32);
+                next_covered.push(bonds);
+            }
+            let mut found: Vec<(&BTreeSet<usize>, u32, AtomIdx)> = atoms
+                .iter()
+                .map(|at| {
+                    let ix = *at as usize - 1;
+                    (&next_covered[ix], next[ix], *at)
+                })
+                .collect();
+            found.sort();
+            let mut layer = vec![];
+            for (bonds, id, centre) in found {
+                if !seen.contains(bonds) {
+                    seen.insert(bonds.clone());
+                    layer.push(Environment { id, centre, radius });
+                }
+            }
+            layer.sort_by_key(|e| (e.id, e.centre));
+            envs.extend(layer);
+            ids = next;
+            covered = next_covered;
+        }
+        Ok(envs)
+    }
+
+    /// Unfolded identifiers and how often each occurs.
+    pub fn counts(&self, mol: &Molecule) -> Result<BTreeMap<u32, u32>> {
+        let mut counts = BTreeMap::new();
+        for env in self.environments(mol)? {
+            *counts.entry(env.id).or_default() += 1;
+        }
+        Ok(counts)
+    }
+
+    /// Bits set once the identifiers are folded into the length, ascending.
+    pub fn bits(&self, mol: &Molecule) -> Result<Vec<usize>> {
+        Ok(self.bit_info(mol)?.into_keys().collect())
+    }
+
+    /// The folded bits as a vector of the fingerprint length.
+    pub fn bit_vector(&self, mol: &Molecule) -> Result<BitVector> {
+        Ok(BitVector::from_bits(self.length, &self.bits(mol)?))
+    }
+
+    /// Centre atoms and radii of the environments behind each folded bit.
+    pub fn bit_info(&self, mol: &Molecule) -> Result<BTreeMap<usize, Vec<(AtomIdx, u32)>>> {
+        let mut info: BTreeMap<usize, Vec<(AtomIdx, u32)>> = BTreeMap::new();
+        for env in self.environments(mol)? {
+            info.entry(env.id as usize % self.length)
+                .or_default()
+                .push((env.centre, env.radius));
+        }
+        Ok(info)
+    }
+
+    /// Parity of the configuration at `at` with its carriers in identifier
+    /// order, none when identifiers tie.
+    fn parity(&self, mol: &Molecule, at: AtomIdx, ids: &[u32]) -> Result<Option<i8>> {
+        let top = match mol.topology_at(&at) {
+            Some(t) => t,
+            None => return Ok(None),
+        };
+        let mut carried: Vec<u32> = top
+            .carriers()
+            .iter()
+            .filter(|c| **c > 0 && **c != at as i64)
+            .map(|c| ids[*c as usize - 1])
+            .collect();
+        let count = carried.len();
+        carried.sort_unstable();
+        carried.dedup();
+        if carried.len() != count {
+            return Ok(None);
+        }
+        let ranks: Vec<usize> = ids.iter().map(|id| *id as usize + 1).collect();
+        tetrahedral_parity(mol, at, &ranks)
+    }
+}
+
+/// Daylight atom invariants: atomic number, heavy degree, hydrogens,
+/// charge, isotope and ring membership.
+fn invariants(mol: &Molecule, at: AtomIdx) -> Result<[i64; 6]> {
+    let atom = mol.atom_at(&at)?;
+    let (heavy, hydrogens) = degree(mol, at)?;
+    Ok([
+        atom.element().atomic_number() as i64,
+        heavy,
+        hydrogens,
+        atom.charge() as i64,
+        atom.isotope().max(0) as i64,
+        (atom.ring_membership() > 0) as i64,
+    ])
+}
+
+/// Donor, acceptor, aromatic, halogen, basic and acidic, by simple rules
+/// on the atom and its neighbours.
+fn features(mol: &Molecule, at: AtomIdx) -> Result<[i64; 6]> {
+    let atom = mol.atom_at(&at)?;
+    let number = atom.element().atomic_number();
+    let charge = atom.charge();
+    let (heavy, hydrogens) = degree(mol, at)?;
+    let donor = matches!(number, 7 | 8) && hydrogens > 0;
+    let acceptor = match number {
+        8 => charge <= 0,
+        7 => charge <= 0 && hydrogens == 0 && heavy <= 2,
+        _ => false,
+    };
+    let halogen = matches!(number, 9 | 17 | 35 | 53);
+    let mut single = true;
+    let mut conjugated = false;
+    let mut acid_centre = false;
+    for n in mol.graph().neighbors(&at)? {
+        single &= bond_code(mol, at, *n)? == 1;
+        let nb = mol.atom_at(n)?;
+        conjugated |= nb.is_aromatic();
+        for m in mol.graph().neighbors(n)? {
+            if *m != at && bond_code(mol, *n, *m)? == 2 {
+                let other = mol.atom_at(m)?.element().atomic_number();
+                conjugated |= matches!(other, 7 | 8 | 16);
+                acid_centre |= other == 8 && matches!(nb.element().atomic_number(), 6 | 15 | 16);
+            }
+        }
+    }
+    let basic = number == 7
+        && (charge > 0 || (charge == 0 && !atom.is_aromatic() && single && !conjugated));
+    let acidic = matches!(number, 8 | 16) && (charge < 0 || (hydrogens > 0 && acid_centre));
+    Ok([
+        donor as i64,
+        acceptor as i64,
+        atom.is_aromatic() as i64,
+        halogen as i64,
+        basic as i64,
+        acidic as i64,
+    ])
+}
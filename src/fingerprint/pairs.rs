@@ -0,0 +1,155 @@
+use super::{bond_code, degree};
+use crate::error::Result;
+use crate::molecule::{key::hash, AtomIdx, Molecule};
+use std::collections::BTreeMap;
+
+/// Atom type of pairs and torsions: atomic number, heavy neighbours less
+/// `used` of them, and π electrons, at most 3, 1 for aromatic atoms.
+fn atom_type(mol: &Molecule, at: AtomIdx, used: i64) -> Result<[i64; 3]> {
+    let atom = mol.atom_at(&at)?;
+    let pi = match atom.is_aromatic() {
+        true => 1,
+        false => {
+            let mut pi = 0;
+            for n in mol.graph().neighbors(&at)? {
+                pi += match bond_code(mol, at, *n)? {
+                    5 => 1,
+                    code => code - 1,
+                };
+            }
+            pi.min(3)
+        }
+    };
+    Ok([
+        atom.element().atomic_number() as i64,
+        degree(mol, at)?.0 - used,
+        pi,
+    ])
+}
+
+fn heavy_atoms(mol: &Molecule) -> Result<Vec<AtomIdx>> {
+    let mut atoms = vec![];
+    for at in mol.atoms().iter() {
+        if mol.atom_at(at)?.element().atomic_number() != 1 {
+            atoms.push(*at);
+        }
+    }
+    Ok(atoms)
+}
+
+/// Atom pair fingerprints: the types of every two heavy atoms and their
+/// topological distance, as sparse counts.
+///
+/// ```
+/// use ruatom::fingerprint::AtomPairs;
+/// use ruatom::Parser;
+///
+/// let m = Parser::new("CCO").parse().unwrap();
+/// let counts = AtomPairs::default().counts(&m).unwrap();
+/// assert_eq!(counts.values().sum::<u32>(), 3);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AtomPairs {
+    min_distance: u32,
+    max_distance: u32,
+}
+
+impl Default for AtomPairs {
+    fn default() -> Self {
+        Self {
+            min_distance: 1,
+            max_distance: 30,
+        }
+    }
+}
+
+impl AtomPairs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn min_distance(mut self, distance: u32) -> Self {
+        self.min_distance = distance;
+        self
+    }
+
+    pub fn max_distance(mut self, distance: u32) -> Self {
+        self.max_distance = distance;
+        self
+    }
+
+    /// Pair hashes and how often each occurs.
+    pub fn counts(&self, mol: &Molecule) -> Result<BTreeMap<u32, u32>> {
+        let atoms = heavy_atoms(mol)?;
+        let mut types = Vec::with_capacity(atoms.len());
+        for at in atoms.iter() {
+            types.push(atom_type(mol, *at, 0)?);
+        }
+        let distances = mol.distance_matrix()?;
+        let mut counts = BTreeMap::new();
+        for (i, u) in atoms.iter().enumerate() {
+            for (j, v) in atoms.iter().enumerate().skip(i + 1) {
+                let d = match distances[*u as usize - 1][*v as usize - 1] {
+                    Some(d) if d >= self.min_distance && d <= self.max_distance => d,
+                    _ => continue,
+                };
+                let (a, b) = (types[i].min(types[j]), types[i].max(types[j]));
+                let mut values = a.to_vec();
+                values.extend(b);
+                values.push(d as i64);
+                *counts.entry(hash(&values) as u32).or_default() += 1;
+            }
+        }
+        Ok(counts)
+    }
+}
+
+/// Topological torsion fingerprints: the types along every linear path of
+/// four heavy atoms, read in the direction that gives the smaller sequence,
+/// as sparse counts. Atoms count their heavy neighbours off the path.
+///
+/// ```
+/// use ruatom::fingerprint::Torsions;
+/// use ruatom::Parser;
+///
+/// let m = Parser::new("CCCC").parse().unwrap();
+/// assert_eq!(Torsions::counts(&m).unwrap().values().sum::<u32>(), 1);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Torsions;
+
+impl Torsions {
+    /// Torsion hashes and how often each occurs.
+    pub fn counts(mol: &Molecule) -> Result<BTreeMap<u32, u32>> {
+        let heavy =
+            |at: &AtomIdx| -> Result<bool> { Ok(mol.atom_at(at)?.element().atomic_number() != 1) };
+        let mut counts = BTreeMap::new();
+        for [b, c] in mol.bonds().iter().copied() {
+            if !heavy(&b)? || !heavy(&c)? {
+                continue;
+            }
+            for a in mol.graph().neighbors(&b)? {
+                if *a == c || !heavy(a)? {
+                    continue;
+                }
+                for d in mol.graph().neighbors(&c)? {
+                    if *d == b || *d == *a || !heavy(d)? {
+                        continue;
+                    }
+                    let forward = [
+                        atom_type(mol, *a, 1)?,
+                        atom_type(mol, b, 2)?,
+                        atom_type(mol, c, 2)?,
+                        atom_type(mol, *d, 1)?,
+                    ];
+                    let mut backward = forward;
+                    backward.reverse();
+                    let path = forward.min(backward);
+                    let values: Vec<i64> = path.into_iter().flatten().collect();
+                    *counts.entry(hash(&values) as u32).or_default() += 1;
+                }
+            }
+        }
+        Ok(counts)
+    }
+}
@@ -0,0 +1,135 @@
+use super::BitVector;
+use crate::error::Result;
+use crate::molecule::{key::hash, substructure::bond_order, AtomIdx, Molecule};
+use std::collections::BTreeMap;
+
+/// Daylight style path fingerprints: every simple path of up to
+/// `max_length` bonds, single atoms included, hashed from its atoms and
+/// bonds read in the direction that gives the smaller sequence.
+///
+/// Atoms are labelled by element and aromaticity and bonds by what
+/// substructure search compares them on, so each path of a substructure is
+/// a path of any molecule containing it and its bits are a subset of that
+/// molecule's bits. That makes them a screen ahead of
+/// [`SubstructureMatcher`](crate::molecule::SubstructureMatcher).
+///
+/// ```
+/// use ruatom::fingerprint::Paths;
+/// use ruatom::Parser;
+///
+/// let phenol = Parser::new("Oc1ccccc1").parse().unwrap();
+/// let tyrosine = Parser::new("N[C@@H](Cc1ccc(O)cc1)C(=O)O").parse().unwrap();
+/// let paths = Paths::default();
+/// let (q, t) = (paths.bits(&phenol).unwrap(), paths.bits(&tyrosine).unwrap());
+/// assert!(q.iter().all(|b| t.binary_search(b).is_ok()));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Paths {
+    max_length: usize,
+    length: usize,
+    bits_per_path: usize,
+}
+
+impl Default for Paths {
+    fn default() -> Self {
+        Self {
+            max_length: 7,
+            length: 2048,
+            bits_per_path: 2,
+        }
+    }
+}
+
+impl Paths {
+    pub fn new(max_length: usize) -> Self {
+        Self {
+            max_length,
+            ..Self::default()
+        }
+    }
+
+    /// Number of bits the paths are folded into.
+    pub fn length(mut self, length: usize) -> Self {
+        self.length = length.max(1);
+        self
+    }
+
+    /// Bits each path sets, fewer collisions between paths for more bits.
+    pub fn bits_per_path(mut self, bits: usize) -> Self {
+        self.bits_per_path = bits.max(1);
+        self
+    }
+
+    /// Unfolded path hashes and how often each occurs.
+    pub fn counts(&self, mol: &Molecule) -> Result<BTreeMap<u32, u32>> {
+        let mut labels = Vec::with_capacity(mol.atoms().len());
+        for at in mol.atoms().iter() {
+            let atom = mol.atom_at(at)?;
+            labels.push(atom.element().atomic_number() as i64 * 2 + atom.is_aromatic() as i64);
+        }
+        let mut counts = BTreeMap::new();
+        let mut path = vec![];
+        for at in mol.atoms().iter() {
+            path.push(*at);
+            self.extend(mol, &labels, &mut path, &mut counts)?;
+            path.pop();
+        }
+        Ok(counts)
+    }
+
+    /// Bits set by the paths, ascending.
+    pub fn bits(&self, mol: &Molecule) -> Result<Vec<usize>> {
+        let mut bits = vec![];
+        for id in self.counts(mol)?.into_keys() {
+            for k in 0..self.bits_per_path {
+                let bit = match k {
+                    0 => id as u64,
+                    _ => hash(&[id as i64, k as i64]),
+                };
+                bits.push(bit as usize % self.length);
+            }
+        }
+        bits.sort_unstable();
+        bits.dedup();
+        Ok(bits)
+    }
+
+    /// The path bits as a vector of the fingerprint length.
+    pub fn bit_vector(&self, mol: &Molecule) -> Result<BitVector> {
+        Ok(BitVector::from_bits(self.length, &self.bits(mol)?))
+    }
+
+    /// Counts `path` once, from its lower numbered end, and every simple
+    /// path it extends to.
+    fn extend(
+        &self,
+        mol: &Molecule,
+        labels: &[i64],
+        path: &mut Vec<AtomIdx>,
+        counts: &mut BTreeMap<u32, u32>,
+    ) -> Result<()> {
+        let last = *path.last().unwrap();
+        if path[0] <= last {
+            let mut forward = vec![labels[path[0] as usize - 1]];
+            for w in path.windows(2) {
+                let bond = bond_order(mol, [w[0], w[1]])?.unwrap_or_default();
+                forward.extend([bond as i64, labels[w[1] as usize - 1]]);
+            }
+            let backward: Vec<i64> = forward.iter().rev().copied().collect();
+            *counts
+                .entry(hash(&forward.min(backward)) as u32)
+                .or_default() += 1;
+        }
+        if path.len() > self.max_length {
+            return Ok(());
+        }
+        for n in mol.graph().neighbors(&last)? {
+            if !path.contains(n) {
+                path.push(*n);
+                self.extend(mol, labels, path, counts)?;
+                path.pop();
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,318 @@
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+
+/// What the similarity metrics need of a fingerprint: its size and how much
+/// of it another one shares.
+pub trait Fingerprint: Sync {
+    /// Bits set, or the sum of the counts.
+    fn popcount(&self) -> u64;
+
+    /// Bits set in both, or the sum of the smaller counts.
+    fn common(&self, other: &Self) -> u64;
+
+    /// Inner product, the common bits for bit vectors.
+    fn dot(&self, other: &Self) -> f64 {
+        self.common(other) as f64
+    }
+
+    /// Inner product with itself.
+    fn norm(&self) -> f64 {
+        self.popcount() as f64
+    }
+
+    fn tanimoto(&self, other: &Self) -> f64 {
+        self.tversky(other, 1.0, 1.0)
+    }
+
+    fn dice(&self, other: &Self) -> f64 {
+        self.tversky(other, 0.5, 0.5)
+    }
+
+    /// `alpha` weighs what only `self` has and `beta` what only `other`
+    /// has; 1 and 1 is Tanimoto, 0.5 and 0.5 is Dice.
+    fn tversky(&self, other: &Self, alpha: f64, beta: f64) -> f64 {
+        let c = self.common(other) as f64;
+        let a = self.popcount() as f64 - c;
+        let b = other.popcount() as f64 - c;
+        let denominator = c + alpha * a + beta * b;
+        match denominator > 0.0 {
+            true => c / denominator,
+            false => 0.0,
+        }
+    }
+
+    fn cosine(&self, other: &Self) -> f64 {
+        let denominator = (self.norm() * other.norm()).sqrt();
+        match denominator > 0.0 {
+            true => self.dot(other) / denominator,
+            false => 0.0,
+        }
+    }
+
+    /// Bits set in one only, or the sum of count differences; a distance,
+    /// zero for equal fingerprints.
+    fn hamming(&self, other: &Self) -> u64 {
+        self.popcount() + other.popcount() - 2 * self.common(other)
+    }
+}
+
+/// Fixed-width fingerprint, as folded by [`Morgan`](super::Morgan),
+/// [`Paths`](super::Paths) or [`maccs_keys`](super::maccs_keys).
+///
+/// ```
+/// use ruatom::fingerprint::{BitVector, Fingerprint};
+///
+/// let a = BitVector::from_bits(16, &[1, 2, 3]);
+/// let b = BitVector::from_bits(16, &[2, 3, 4, 5]);
+/// assert_eq!(a.popcount(), 3);
+/// assert_eq!(a.tanimoto(&b), 0.4);
+/// assert_eq!(a.hamming(&b), 3);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BitVector {
+    length: usize,
+    words: Vec<u64>,
+}
+
+impl BitVector {
+    pub fn new(length: usize) -> Self {
+        Self {
+            length,
+            words: vec![0; length.div_ceil(64)],
+        }
+    }
+
+    /// Bits past the length are left out.
+    pub fn from_bits(length: usize, bits: &[usize]) -> Self {
+        let mut v = Self::new(length);
+        for bit in bits {
+            v.set(*bit, true);
+        }
+        v
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn get(&self, bit: usize) -> bool {
+        bit < self.length && self.words[bit / 64] >> (bit % 64) & 1 == 1
+    }
+
+    /// Does nothing past the length.
+    pub fn set(&mut self, bit: usize, on: bool) {
+        if bit >= self.length {
+            return;
+        }
+        match on {
+            true => self.words[bit / 64] |= 1 << (bit % 64),
+            false => self.words[bit / 64] &= !(1 << (bit % 64)),
+        }
+    }
+
+    /// Bits set, ascending.
+    pub fn bits(&self) -> Vec<usize> {
+        (0..self.length).filter(|b| self.get(*b)).collect()
+    }
+}
+
+impl Fingerprint for BitVector {
+    fn popcount(&self) -> u64 {
+        self.words.iter().map(|w| w.count_ones() as u64).sum()
+    }
+
+    /// # Panics
+    ///
+    /// When the vectors differ in length, and so every metric on them.
+    fn common(&self, other: &Self) -> u64 {
+        assert_eq!(self.length, other.length, "bit vectors differ in length");
+        self.words
+            .iter()
+            .zip(other.words.iter())
+            .map(|(a, b)| (a & b).count_ones() as u64)
+            .sum()
+    }
+}
+
+/// Sparse fingerprint of identifiers and how often each occurs, as counted
+/// by [`Morgan`](super::Morgan), [`AtomPairs`](super::AtomPairs) or
+/// [`Torsions`](super::Torsions).
+///
+/// ```
+/// use ruatom::fingerprint::{CountVector, Fingerprint};
+///
+/// let a = CountVector::from_iter([(1, 2), (7, 1)]);
+/// let b = CountVector::from_iter([(1, 1), (9, 1)]);
+/// assert_eq!(a.popcount(), 3);
+/// assert_eq!(a.common(&b), 1);
+/// assert_eq!(a.tanimoto(&b), 0.25);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct CountVector {
+    counts: BTreeMap<u32, u32>,
+}
+
+impl CountVector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Distinct identifiers.
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    pub fn get(&self, id: u32) -> u32 {
+        self.counts.get(&id).copied().unwrap_or_default()
+    }
+
+    /// Zero removes `id`.
+    pub fn set(&mut self, id: u32, count: u32) {
+        match count {
+            0 => self.counts.remove(&id),
+            _ => self.counts.insert(id, count),
+        };
+    }
+
+    pub fn counts(&self) -> &BTreeMap<u32, u32> {
+        &self.counts
+    }
+}
+
+impl From<BTreeMap<u32, u32>> for CountVector {
+    fn from(mut counts: BTreeMap<u32, u32>) -> Self {
+        counts.retain(|_, c| *c > 0);
+        Self { counts }
+    }
+}
+
+impl FromIterator<(u32, u32)> for CountVector {
+    fn from_iter<I: IntoIterator<Item = (u32, u32)>>(iter: I) -> Self {
+        let mut counts = BTreeMap::new();
+        for (id, count) in iter {
+            *counts.entry(id).or_default() += count;
+        }
+        Self::from(counts)
+    }
+}
+
+impl Fingerprint for CountVector {
+    fn popcount(&self) -> u64 {
+        self.counts.values().map(|c| *c as u64).sum()
+    }
+
+    fn common(&self, other: &Self) -> u64 {
+        self.counts
+            .iter()
+            .map(|(id, c)| (*c).min(other.get(*id)) as u64)
+            .sum()
+    }
+
+    fn dot(&self, other: &Self) -> f64 {
+        self.counts
+            .iter()
+            .map(|(id, c)| *c as f64 * other.get(*id) as f64)
+            .sum()
+    }
+
+    fn norm(&self) -> f64 {
+        self.counts.values().map(|c| *c as f64 * *c as f64).sum()
+    }
+}
+
+/// Similarity a [`SimilaritySearch`] ranks by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+    Tanimoto,
+    Dice,
+    /// Weights of what only the query and only the candidate have.
+    Tversky(f64, f64),
+    Cosine,
+}
+
+impl Metric {
+    pub fn similarity<F: Fingerprint>(&self, a: &F, b: &F) -> f64 {
+        match self {
+            Metric::Tanimoto => a.tanimoto(b),
+            Metric::Dice => a.dice(b),
+            Metric::Tversky(alpha, beta) => a.tversky(b, *alpha, *beta),
+            Metric::Cosine => a.cosine(b),
+        }
+    }
+}
+
+/// Similarity search of a query against a database of fingerprints, in
+/// parallel. Hits are every candidate at or above the threshold, the best
+/// `top` of them if set, most similar first and by index among ties.
+///
+/// ```
+/// use ruatom::fingerprint::{BitVector, Metric, SimilaritySearch};
+///
+/// let query = BitVector::from_bits(8, &[0, 1, 2]);
+/// let database = vec![
+///     BitVector::from_bits(8, &[5]),
+///     BitVector::from_bits(8, &[0, 1, 2]),
+///     BitVector::from_bits(8, &[0, 1, 3]),
+/// ];
+/// let search = SimilaritySearch::new(Metric::Tanimoto).threshold(0.3);
+/// assert_eq!(search.run(&query, &database), vec![(1, 1.0), (2, 0.5)]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimilaritySearch {
+    metric: Metric,
+    threshold: f64,
+    top: Option<usize>,
+}
+
+impl Default for SimilaritySearch {
+    fn default() -> Self {
+        Self {
+            metric: Metric::Tanimoto,
+            threshold: 0.0,
+            top: None,
+        }
+    }
+}
+
+impl SimilaritySearch {
+    pub fn new(metric: Metric) -> Self {
+        Self {
+            metric,
+            ..Self::default()
+        }
+    }
+
+    pub fn threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Keeps the `k` most similar hits.
+    pub fn top(mut self, k: usize) -> Self {
+        self.top = Some(k);
+        self
+    }
+
+    /// Indices into `database` and similarities of the hits.
+    pub fn run<F: Fingerprint>(&self, query: &F, database: &[F]) -> Vec<(usize, f64)> {
+        let mut hits: Vec<(usize, f64)> = database
+            .par_iter()
+            .enumerate()
+            .map(|(ix, fp)| (ix, self.metric.similarity(query, fp)))
+            .filter(|(_, s)| *s >= self.threshold)
+            .collect();
+        hits.par_sort_unstable_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        if let Some(k) = self.top {
+            hits.truncate(k);
+        }
+        hits
+    }
+}
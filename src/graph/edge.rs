@@ -1,10 +1,11 @@
+use super::VertexIdx;
 use std::hash::Hash;
 use std::hash::Hasher;
 
 #[derive(Clone, Debug)]
 pub struct Edge {
-    inbound: u8,
-    outbound: u8,
+    inbound: VertexIdx,
+    outbound: VertexIdx,
 }
 
 impl PartialEq for Edge {
@@ -23,16 +24,16 @@ impl Hash for Edge {
 }
 
 impl Edge {
-    pub fn new(outbound: u8, inbound: u8) -> Edge {
+    pub fn new(outbound: VertexIdx, inbound: VertexIdx) -> Edge {
         Edge { inbound, outbound }
     }
 
-    pub fn inbound(&self) -> &u8 {
+    pub fn inbound(&self) -> &VertexIdx {
         &self.inbound
     }
 
-    /// Returns the inbound u8
-    pub fn outbound(&self) -> &u8 {
+    /// Returns the outbound vertex
+    pub fn outbound(&self) -> &VertexIdx {
         &self.outbound
     }
 }
@@ -1,70 +1,92 @@
-use super::{edge::Edge, vertices::VertexIter};
+use super::{edge::Edge, vertices::VertexIter, VertexIdx};
 use crate::error::RuatomError;
-use std::collections::HashMap;
 use std::slice::Iter;
 
-#[derive(Clone, Debug, Default)]
+/// Both directed attributes of an undirected edge, `attrs[0]` being the one
+/// seen from `ends[0]`.
+#[derive(Clone, Debug)]
+struct EdgeEntry<F> {
+    ends: [VertexIdx; 2],
+    attrs: [F; 2],
+}
+
+/// Index addressed graph.
+///
+/// Vertices and edges live in contiguous arrays, every vertex keeps its sorted
+/// neighbour list together with the ids of the matching edges, so finding an
+/// edge is a scan over the degree of one of its ends.
+#[derive(Clone, Debug)]
 pub struct Graph<T, F> {
-    vertices: HashMap<u8, T>,
-    edges: HashMap<Edge, F>,
-    bound_table: HashMap<u8, Vec<u8>>,
+    vertices: Vec<Option<T>>,
+    vertex_count: usize,
+    edges: Vec<EdgeEntry<F>>,
+    neighbors: Vec<Vec<VertexIdx>>,
+    edge_ids: Vec<Vec<usize>>,
+}
+
+impl<T, F: Clone> Default for Graph<T, F> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<T, F: Clone> Graph<T, F> {
     pub fn new() -> Self {
         Self {
-            vertices: HashMap::new(),
-            edges: HashMap::new(),
-            bound_table: HashMap::new(),
+            vertices: Vec::new(),
+            vertex_count: 0,
+            edges: Vec::new(),
+            neighbors: Vec::new(),
+            edge_ids: Vec::new(),
         }
     }
 
     pub fn with_capacity(capacity: usize) -> Self {
-        let edges_capacity = if capacity < 100 {
-            usize::pow(capacity, 2)
-        } else {
-            capacity
-        };
-
         Self {
-            vertices: HashMap::with_capacity(capacity),
-            edges: HashMap::with_capacity(edges_capacity),
-            bound_table: HashMap::with_capacity(capacity),
+            vertices: Vec::with_capacity(capacity + 1),
+            vertex_count: 0,
+            edges: Vec::with_capacity(capacity),
+            neighbors: Vec::with_capacity(capacity + 1),
+            edge_ids: Vec::with_capacity(capacity + 1),
         }
     }
 
     #[inline]
-    fn has_vertex(&self, v: &u8) -> bool {
-        self.vertices.get(&v).is_some()
+    fn has_vertex(&self, v: &VertexIdx) -> bool {
+        matches!(self.vertices.get(*v as usize), Some(Some(_)))
     }
 
     #[inline]
-    pub fn add_vertex(&mut self, k: u8, v: T) -> Result<(), RuatomError> {
+    pub fn add_vertex(&mut self, k: VertexIdx, v: T) -> Result<(), RuatomError> {
         if self.has_vertex(&k) {
             return Err(RuatomError::ExistedVertex(k));
         }
-        self.bound_table.insert(k, Vec::new());
-        self.vertices.insert(k, v);
+        let slot = k as usize;
+        if slot >= self.vertices.len() {
+            self.vertices.resize_with(slot + 1, || None);
+            self.neighbors.resize_with(slot + 1, Vec::new);
+            self.edge_ids.resize_with(slot + 1, Vec::new);
+        }
+        self.vertices[slot] = Some(v);
+        self.vertex_count += 1;
         Ok(())
     }
 
-    pub fn add_edge(&mut self, a: u8, b: u8, attr: F) -> Result<bool, RuatomError> {
+    pub fn add_edge(&mut self, a: VertexIdx, b: VertexIdx, attr: F) -> Result<bool, RuatomError> {
         if self.has_edge(&a, &b) {
             return Err(RuatomError::ExistedEdge(a, b));
         }
         if a == b {
             return Err(RuatomError::InvalidEdge(a, b));
         }
-        if let Err(e) = self.do_add_edge(a, b, attr, None) {
-            return Err(e);
-        };
-        return Ok(true);
+        self.do_add_edge(a, b, attr.clone(), attr)?;
+        Ok(true)
     }
 
     pub fn add_direction_edge(
         &mut self,
-        a: u8,
-        b: u8,
+        a: VertexIdx,
+        b: VertexIdx,
         attr_ab: F,
         attr_ba: F,
     ) -> Result<bool, RuatomError> {
@@ -74,115 +96,172 @@ impl<T, F: Clone> Graph<T, F> {
         if a == b {
             return Err(RuatomError::InvalidEdge(a, b));
         }
-        if let Err(e) = self.do_add_edge(a, b, attr_ab, Some(attr_ba)) {
-            return Err(e);
-        };
-        return Ok(true);
+        self.do_add_edge(a, b, attr_ab, attr_ba)?;
+        Ok(true)
     }
 
     #[inline]
-    fn has_edge(&self, a: &u8, b: &u8) -> bool {
-        match self.bound_table.get(a) {
-            None => false,
-            Some(bound) => bound.contains(b),
-        }
+    fn has_edge(&self, a: &VertexIdx, b: &VertexIdx) -> bool {
+        self.edge_id(*a, *b).is_some()
+    }
+
+    /// Id of the edge between `a` and `b` in the edge array.
+    #[inline]
+    fn edge_id(&self, a: VertexIdx, b: VertexIdx) -> Option<usize> {
+        let bound = self.neighbors.get(a as usize)?;
+        bound
+            .iter()
+            .position(|v| *v == b)
+            .map(|ix| self.edge_ids[a as usize][ix])
+    }
+
+    /// Locates the attribute of the directed edge `a -> b`.
+    #[inline]
+    fn attr_slot(&self, a: VertexIdx, b: VertexIdx) -> Option<(usize, usize)> {
+        self.edge_id(a, b)
+            .map(|id| (id, if self.edges[id].ends[0] == a { 0 } else { 1 }))
     }
 
     fn do_add_edge(
         &mut self,
-        a: u8,
-        b: u8,
-        attr: F,
-        direction_attr: Option<F>,
+        a: VertexIdx,
+        b: VertexIdx,
+        attr_ab: F,
+        attr_ba: F,
     ) -> Result<(), RuatomError> {
-        if !self.vertices.contains_key(&a) {
+        if !self.has_vertex(&a) {
             return Err(RuatomError::NoSuchVertex(a));
         }
-        if !self.vertices.contains_key(&b) {
+        if !self.has_vertex(&b) {
             return Err(RuatomError::NoSuchVertex(b));
         }
-        let edge_ab = Edge::new(a, b);
-        self.edges.insert(edge_ab, attr.clone());
-        match direction_attr {
-            Some(attr_inner) => {
-                let edge_ba = Edge::new(b, a);
-                self.edges.insert(edge_ba, attr_inner);
-            }
-            None => {
-                let edge_ba = Edge::new(b, a);
-                self.edges.insert(edge_ba, attr);
+        let id = self.edges.len();
+        self.edges.push(EdgeEntry {
+            ends: [a, b],
+            attrs: [attr_ab, attr_ba],
+        });
+        self.link(a, b, id);
+        self.link(b, a, id);
+        Ok(())
+    }
+
+    /// Inserts `b` into the sorted neighbour list of `a`.
+    #[inline]
+    fn link(&mut self, a: VertexIdx, b: VertexIdx, id: usize) {
+        let bound = &mut self.neighbors[a as usize];
+        let pos = bound.partition_point(|v| *v < b);
+        bound.insert(pos, b);
+        self.edge_ids[a as usize].insert(pos, id);
+    }
+
+    /// Removes the edge between `a` and `b`, returning the attribute of `a -> b`.
+    pub fn remove_edge(&mut self, a: VertexIdx, b: VertexIdx) -> Result<F, RuatomError> {
+        let (id, side) = self.attr_slot(a, b).ok_or(RuatomError::NoSuchEdge(b, a))?;
+        self.unlink(a, b);
+        self.unlink(b, a);
+        let entry = self.edges.swap_remove(id);
+        if id < self.edges.len() {
+            let moved = self.edges.len();
+            for end in self.edges[id].ends {
+                let ids = &mut self.edge_ids[end as usize];
+                if let Some(e) = ids.iter_mut().find(|e| **e == moved) {
+                    *e = id;
+                }
             }
         }
+        let [ab, ba] = entry.attrs;
+        Ok(if side == 0 { ab } else { ba })
+    }
 
-        match self.bound_table.get_mut(&b) {
-            None => {
-                self.bound_table.insert(b, vec![a]);
-            }
-            Some(bound) => {
-                bound.push(a);
-                bound.sort();
-            }
+    /// Removes `b` from the neighbour list of `a`.
+    #[inline]
+    fn unlink(&mut self, a: VertexIdx, b: VertexIdx) {
+        let bound = &mut self.neighbors[a as usize];
+        if let Some(pos) = bound.iter().position(|v| *v == b) {
+            bound.remove(pos);
+            self.edge_ids[a as usize].remove(pos);
         }
-        match self.bound_table.get_mut(&a) {
-            None => {
-                self.bound_table.insert(a, vec![b]);
-            }
-            Some(bound) => {
-                bound.push(b);
-                bound.sort();
-            }
+    }
+
+    /// Removes vertex `k` and its edges. Vertices above `k` move down by one
+    /// so that the indices stay dense.
+    pub fn remove_vertex(&mut self, k: VertexIdx) -> Result<T, RuatomError> {
+        if !self.has_vertex(&k) {
+            return Err(RuatomError::NoSuchVertex(k));
         }
-        Ok(())
+        let bound = self.neighbors[k as usize].clone();
+        for v in bound {
+            self.remove_edge(k, v)?;
+        }
+        let slot = k as usize;
+        self.neighbors.remove(slot);
+        self.edge_ids.remove(slot);
+        let removed = self.vertices.remove(slot);
+        self.vertex_count -= 1;
+        let shift = |v: &mut VertexIdx| {
+            if *v > k {
+                *v -= 1;
+            }
+        };
+        self.neighbors.iter_mut().flatten().for_each(shift);
+        self.edges
+            .iter_mut()
+            .flat_map(|e| e.ends.iter_mut())
+            .for_each(shift);
+        removed.ok_or(RuatomError::NoSuchVertex(k))
     }
 
     #[inline]
-    pub fn bound_count(&self, v: &u8) -> Result<usize, RuatomError> {
+    pub fn bound_count(&self, v: &VertexIdx) -> Result<usize, RuatomError> {
         if !self.has_vertex(v) {
             return Err(RuatomError::NoSuchVertex(*v));
         }
-        self.bound_table.get(v).map_or(Ok(0), |l| Ok(l.len()))
+        Ok(self.neighbors[*v as usize].len())
     }
 
     #[inline]
-    pub fn adjancent(&self, a: u8, b: u8) -> bool {
-        let e1 = Edge::new(a, b);
-        let e2 = Edge::new(b, a);
-        self.edges.contains_key(&e1) || self.edges.contains_key(&e2)
+    pub fn adjancent(&self, a: VertexIdx, b: VertexIdx) -> bool {
+        self.has_edge(&a, &b)
     }
 
     #[inline]
-    pub fn neighbors(&self, v: &u8) -> Result<VertexIter<'_, Iter<'_, u8>>, RuatomError> {
-        self.bound_table
-            .get(v)
-            .map_or(Err(RuatomError::NoSuchVertex(*v)), |l| {
-                Ok(VertexIter::new(l.iter()))
-            })
+    pub fn neighbors(
+        &self,
+        v: &VertexIdx,
+    ) -> Result<VertexIter<'_, Iter<'_, VertexIdx>>, RuatomError> {
+        if !self.has_vertex(v) {
+            return Err(RuatomError::NoSuchVertex(*v));
+        }
+        Ok(VertexIter::new(self.neighbors[*v as usize].iter()))
     }
 
-    pub fn map_edge<Func>(&self, loc: &u8, mut f: Func) -> Result<(), RuatomError>
+    pub fn map_edge<Func>(&self, loc: &VertexIdx, mut f: Func) -> Result<(), RuatomError>
     where
-        Func: FnMut(&F, &u8),
+        Func: FnMut(&F, &VertexIdx),
     {
         if !self.has_vertex(loc) {
             return Err(RuatomError::NoSuchVertex(*loc));
         }
-        let _ = self.neighbors(loc).and_then(|vs| {
-            for v in vs {
-                let fd = self.edge_with_vertex(*v, *loc).unwrap();
-                f(fd, v);
-            }
-            Ok(())
-        });
+        let slot = *loc as usize;
+        for (v, id) in self.neighbors[slot].iter().zip(self.edge_ids[slot].iter()) {
+            let entry = &self.edges[*id];
+            let attr = if entry.ends[0] == *v {
+                &entry.attrs[0]
+            } else {
+                &entry.attrs[1]
+            };
+            f(attr, v);
+        }
         Ok(())
     }
 
-    pub fn map_vertex<Func>(&self, loc: &u8, mut f: Func) -> Result<(), RuatomError>
+    pub fn map_vertex<Func>(&self, loc: &VertexIdx, mut f: Func) -> Result<(), RuatomError>
     where
         Func: FnMut(&T),
     {
         self.neighbors(loc).and_then(|vs| {
             for v in vs {
-                let fd = self.vertex(&v)?;
+                let fd = self.vertex(v)?;
                 f(fd);
             }
             Ok(())
@@ -191,57 +270,74 @@ impl<T, F: Clone> Graph<T, F> {
     }
 
     #[inline]
-    pub fn vertex(&self, v: &u8) -> Result<&T, RuatomError> {
-        self.vertices.get(v).ok_or(RuatomError::NoSuchVertex(*v))
+    pub fn vertex(&self, v: &VertexIdx) -> Result<&T, RuatomError> {
+        self.vertices
+            .get(*v as usize)
+            .and_then(|t| t.as_ref())
+            .ok_or(RuatomError::NoSuchVertex(*v))
     }
 
-    pub fn update_vertex(&mut self, k: u8, v: T) {
-        self.vertices.insert(k, v);
+    pub fn update_vertex(&mut self, k: VertexIdx, v: T) {
+        match self.vertices.get_mut(k as usize) {
+            Some(Some(t)) => *t = v,
+            _ => {
+                let _ = self.add_vertex(k, v);
+            }
+        }
     }
 
-    pub fn vertex_mut(&mut self, v: &u8) -> Result<&mut T, RuatomError> {
+    pub fn vertex_mut(&mut self, v: &VertexIdx) -> Result<&mut T, RuatomError> {
         self.vertices
-            .get_mut(&v)
+            .get_mut(*v as usize)
+            .and_then(|t| t.as_mut())
             .ok_or(RuatomError::NoSuchVertex(*v))
     }
 
     pub fn edge(&self, e: &Edge) -> Result<&F, RuatomError> {
-        self.edges
-            .get(&e)
+        self.attr_slot(*e.outbound(), *e.inbound())
+            .map(|(id, side)| &self.edges[id].attrs[side])
             .ok_or(RuatomError::NoSuchEdge(*e.inbound(), *e.outbound()))
     }
 
     pub fn edge_mut(&mut self, e: &Edge) -> Result<&mut F, RuatomError> {
-        self.edges
-            .get_mut(&e)
-            .ok_or(RuatomError::NoSuchEdge(*e.inbound(), *e.outbound()))
+        match self.attr_slot(*e.outbound(), *e.inbound()) {
+            Some((id, side)) => Ok(&mut self.edges[id].attrs[side]),
+            None => Err(RuatomError::NoSuchEdge(*e.inbound(), *e.outbound())),
+        }
     }
 
-    pub fn edge_with_vertex(&self, a: u8, b: u8) -> Result<&F, RuatomError> {
+    pub fn edge_with_vertex(&self, a: VertexIdx, b: VertexIdx) -> Result<&F, RuatomError> {
         let edge = Edge::new(a, b);
-        return self.edge(&edge).and_then(|f| Ok(f));
+        self.edge(&edge)
     }
 
+    /// Replaces the attribute of an existing directed edge, unknown edges are
+    /// ignored.
     pub fn replace(&mut self, e: Edge, desc: F) {
-        self.edges.insert(e, desc);
+        if let Ok(attr) = self.edge_mut(&e) {
+            *attr = desc;
+        }
     }
 
     #[inline]
     pub fn order(&self) -> usize {
-        self.vertices.len()
+        self.vertex_count
     }
 
     #[inline]
     pub fn size(&self) -> usize {
-        self.edges.len() / 2
+        self.edges.len()
     }
 
+    /// Visits every edge in both directions, in insertion order.
     pub fn map_edges<Func>(&self, mut f: Func) -> Result<(), RuatomError>
     where
         Func: FnMut(&Edge, &F),
     {
-        for (k, v) in self.edges.iter() {
-            f(k, v)
+        for entry in self.edges.iter() {
+            let [a, b] = entry.ends;
+            f(&Edge::new(a, b), &entry.attrs[0]);
+            f(&Edge::new(b, a), &entry.attrs[1]);
         }
         Ok(())
     }
@@ -1,6 +1,10 @@
 mod edge;
+#[allow(clippy::module_inception)]
 pub mod graph;
 mod vertices;
 
 pub use edge::Edge;
 pub use graph::Graph;
+
+/// Index used to address a vertex of a [`Graph`].
+pub type VertexIdx = u32;
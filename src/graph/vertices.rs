@@ -1,16 +1,17 @@
+use super::VertexIdx;
 use std::fmt::Debug;
 
 #[derive(Debug)]
-pub struct VertexIter<'a, T: Iterator<Item = &'a u8> + Debug>(T);
+pub struct VertexIter<'a, T: Iterator<Item = &'a VertexIdx> + Debug>(T);
 
-impl<'a, T: Iterator<Item = &'a u8> + Debug> VertexIter<'a, T> {
+impl<'a, T: Iterator<Item = &'a VertexIdx> + Debug> VertexIter<'a, T> {
     pub fn new(v: T) -> Self {
         Self(v)
     }
 }
 
-impl<'a, T: Iterator<Item = &'a u8> + Debug> Iterator for VertexIter<'a, T> {
-    type Item = &'a u8;
+impl<'a, T: Iterator<Item = &'a VertexIdx> + Debug> Iterator for VertexIter<'a, T> {
+    type Item = &'a VertexIdx;
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
@@ -1,9 +1,15 @@
 pub mod char_buff;
 pub mod error;
+pub mod fingerprint;
 pub mod graph;
 
 pub mod molecule;
+pub mod molfile;
 pub mod parser;
+pub mod sdf;
+pub mod smarts;
+pub mod smi;
 
 pub use crate::char_buff::CharBuffer;
 pub use crate::parser::Parser;
+pub use crate::smarts::SmartsParser;
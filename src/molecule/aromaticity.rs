@@ -0,0 +1,397 @@
+//! Aromaticity models.
+//!
+//! A model only decides which atoms and bonds are aromatic, the molecule then
+//! marks them and gives any atoms leaving aromaticity a Kekulé form. Pick a
+//! model at parse time with [`Parser::aromaticity`](crate::Parser::aromaticity)
+//! or re-perceive with [`Molecule::perceive_aromaticity`].
+
+use super::{
+    element::Specification,
+    kekule::{kekule_bonds, sorted_pair},
+    Atom, AtomIdx, Molecule,
+};
+use crate::error::Result;
+use phf::phf_set;
+use std::collections::{HashMap, HashSet};
+
+static RING_SIZE: phf::Set<u32> = phf_set! {
+    5u32,
+    6u32,
+    7u32
+};
+
+/// Most rings a fused system may have for all its ring combinations to be
+/// counted, larger systems only count single rings and fused pairs.
+const MAX_FUSED: usize = 12;
+
+/// Aromatic atoms and bonds found by an [`AromaticityModel`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Aromatic {
+    pub atoms: HashSet<AtomIdx>,
+    /// Bonds as atom pairs, lower index first.
+    pub bonds: HashSet<[AtomIdx; 2]>,
+}
+
+pub trait AromaticityModel: Send + Sync {
+    /// Which atoms and bonds of `mol` are aromatic. Rings are perceived,
+    /// input aromaticity is still marked.
+    fn perceive(&self, mol: &Molecule) -> Result<Aromatic>;
+}
+
+/// The default: sp2 ring atoms of 5 to 7 membered rings with a conjugated
+/// neighbour on either side, no electron counting.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Basic;
+
+impl AromaticityModel for Basic {
+    fn perceive(&self, mol: &Molecule) -> Result<Aromatic> {
+        let mut sp2atoms: HashMap<AtomIdx, u8> = HashMap::new();
+        for atom in mol.atoms().iter() {
+            sp2atoms.insert(*atom, 0);
+            let at = mol.atom_at(atom)?;
+            let hc = mol.hydrogen_count(atom)?;
+            if at.is_aromatic() {
+                sp2atoms.entry(*atom).and_modify(|e| *e = 1);
+            }
+            if !at.ele_is_any()
+                && at.ring_membership() > 0
+                && (RING_SIZE.contains(&at.ring_size())
+                    || RING_SIZE.contains(&at.max_bonds_ringsize()))
+                && maybe_aromaticity(at, hc)
+            {
+                let mut count = 0;
+                for j in mol.graph().neighbors(atom)? {
+                    let elec = mol.edge_at(*atom, *j)?.electron();
+                    if mol.atom_at(j)?.ring_membership() > 0 && elec == 2 {
+                        count += 1;
+                    } else if elec == 2 {
+                        sp2atoms.entry(*atom).and_modify(|e| *e = 0);
+                    }
+                }
+                if count == 1 {
+                    sp2atoms.entry(*atom).and_modify(|e| *e = 1);
+                }
+            }
+        }
+        for atom in mol.atoms().iter() {
+            let at = mol.atom_at(atom)?;
+            let exist = sp2atoms.get(atom).unwrap();
+            if exist == &0
+                && !at.ele_is_any()
+                && at.ring_membership() > 0
+                && (RING_SIZE.contains(&at.ring_size())
+                    || RING_SIZE.contains(&at.max_bonds_ringsize()))
+                && maybe_aromaticity_nonsp2(at)
+            {
+                let mut count = 0;
+                for j in mol.graph().neighbors(atom)? {
+                    if mol.edge_at(*atom, *j)?.electron() == 1 {
+                        count += sp2atoms.get(j).unwrap();
+                    }
+                }
+                if count >= 2 {
+                    sp2atoms.entry(*atom).and_modify(|e| *e = 1);
+                } else if at.is_aromatic() {
+                    sp2atoms.entry(*atom).and_modify(|e| *e = 0);
+                }
+            }
+        }
+        loop {
+            let mut flag = 0;
+            for atom in mol.atoms().iter() {
+                if sp2atoms.get(atom).unwrap() == &1 {
+                    let mut count = 0;
+                    for j in mol.graph().neighbors(atom)? {
+                        if mol.edge_at(*atom, *j)?.electron() == 1 {
+                            count += sp2atoms.get(j).unwrap();
+                        }
+                    }
+                    if count < 2 {
+                        sp2atoms.entry(*atom).and_modify(|e| *e = 0);
+                        flag = 1;
+                    }
+                }
+            }
+            if flag == 0 {
+                break;
+            }
+        }
+        let mut aromatic = Aromatic::default();
+        for atom in mol.atoms().iter() {
+            if sp2atoms.get(atom).unwrap() == &1 {
+                aromatic.atoms.insert(*atom);
+                for j in mol.graph().neighbors(atom)? {
+                    if sp2atoms.get(j).unwrap() == &1 {
+                        aromatic.bonds.insert(sorted_pair(*atom, *j));
+                    }
+                }
+            }
+        }
+        Ok(aromatic)
+    }
+}
+
+#[inline]
+fn maybe_aromaticity(atom: &Atom, hc: u8) -> bool {
+    match atom.element().atomic_number() {
+        5 => atom.is_organogen(),
+        6 => atom.is_organogen() || hc == 0 && (atom.charge() == 1 || atom.charge() == -1),
+        7 | 15 => atom.is_organogen() || atom.charge() == 1,
+        8 | 16 | 34 | 52 => atom.charge() == 1,
+        33 => hc == 0 && atom.charge() == 0,
+        _ => false,
+    }
+}
+
+#[inline]
+fn maybe_aromaticity_nonsp2(atom: &Atom) -> bool {
+    match atom.element().atomic_number() {
+        6 => atom.charge() == -1,
+        7 | 15 => atom.is_organogen() || atom.charge() == -1,
+        8 | 16 => atom.is_organogen(),
+        33 | 34 | 52 => atom.charge() == 0,
+        _ => false,
+    }
+}
+
+/// Hückel's rule over the SSSR: a ring, or a fused combination of rings,
+/// is aromatic when its atoms share 4n+2 π electrons. Handles azulene,
+/// porphyrins and the like, elements outside the [`Specification`] never
+/// take part.
+#[derive(Debug, Clone, Copy)]
+pub struct Huckel {
+    spec: Specification,
+}
+
+impl Huckel {
+    pub fn new(spec: Specification) -> Self {
+        Self { spec }
+    }
+}
+
+impl Default for Huckel {
+    fn default() -> Self {
+        Self::new(Specification::OpenSMILES)
+    }
+}
+
+impl AromaticityModel for Huckel {
+    fn perceive(&self, mol: &Molecule) -> Result<Aromatic> {
+        let pi = PiBonds::new(mol)?;
+        let mut electrons = HashMap::new();
+        for at in mol.atoms().iter() {
+            if let Some(e) = pi.huckel_electrons(mol, at, self.spec)? {
+                electrons.insert(*at, e);
+            }
+        }
+        let rings: Vec<Vec<AtomIdx>> = mol
+            .rings()
+            .iter()
+            .filter(|r| r.atoms().iter().all(|a| electrons.contains_key(a)))
+            .map(|r| r.atoms().to_vec())
+            .collect();
+
+        let mut aromatic = Aromatic::default();
+        for system in fused_systems(&rings) {
+            for subset in ring_combinations(&rings, &system) {
+                let atoms: HashSet<AtomIdx> = subset
+                    .iter()
+                    .flat_map(|r| rings[*r].iter().copied())
+                    .collect();
+                let sum: u32 = atoms.iter().map(|a| electrons[a] as u32).sum();
+                if sum % 4 == 2 {
+                    for r in subset.iter() {
+                        aromatic.mark(&rings[*r]);
+                    }
+                }
+            }
+        }
+        Ok(aromatic)
+    }
+}
+
+/// MDL style: six membered rings where every atom has a double bond inside
+/// the ring system, so only alternating rings like benzene, pyridine and
+/// their fused systems count. Lone pairs never contribute.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Mdl;
+
+impl AromaticityModel for Mdl {
+    fn perceive(&self, mol: &Molecule) -> Result<Aromatic> {
+        let pi = PiBonds::new(mol)?;
+        let mut aromatic = Aromatic::default();
+        for ring in mol.rings().iter().filter(|r| r.len() == 6) {
+            let mut alternating = true;
+            for at in ring.atoms().iter() {
+                let (endo, exo) = pi.doubles(mol, at)?;
+                alternating &= endo.len() == 1 && exo.is_empty() && !pi.triple(mol, at)?;
+            }
+            if alternating {
+                aromatic.mark(ring.atoms());
+            }
+        }
+        Ok(aromatic)
+    }
+}
+
+impl Aromatic {
+    fn mark(&mut self, ring: &[AtomIdx]) {
+        for (i, a) in ring.iter().enumerate() {
+            self.atoms.insert(*a);
+            self.bonds
+                .insert(sorted_pair(*a, ring[(i + 1) % ring.len()]));
+        }
+    }
+}
+
+/// Double bonds of a Kekulé form of the molecule, the written ones plus
+/// those placed in input aromatic systems.
+struct PiBonds {
+    doubles: HashSet<[AtomIdx; 2]>,
+}
+
+impl PiBonds {
+    fn new(mol: &Molecule) -> Result<Self> {
+        let mut doubles = kekule_bonds(mol)?;
+        for [u, v] in mol.bonds().iter() {
+            if mol.edge_at(*u, *v)?.electron() == 2 {
+                doubles.insert(sorted_pair(*u, *v));
+            }
+        }
+        Ok(Self { doubles })
+    }
+
+    /// Neighbours double bonded to `at` through ring and non-ring bonds.
+    fn doubles(&self, mol: &Molecule, at: &AtomIdx) -> Result<(Vec<AtomIdx>, Vec<AtomIdx>)> {
+        let mut endo = vec![];
+        let mut exo = vec![];
+        for n in mol.graph().neighbors(at)? {
+            if self.doubles.contains(&sorted_pair(*at, *n)) {
+                match mol.edge_at(*at, *n)?.ring_membership() > 0 {
+                    true => endo.push(*n),
+                    false => exo.push(*n),
+                }
+            }
+        }
+        Ok((endo, exo))
+    }
+
+    fn triple(&self, mol: &Molecule, at: &AtomIdx) -> Result<bool> {
+        for n in mol.graph().neighbors(at)? {
+            if mol.edge_at(*at, *n)?.electron() > 2 {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// π electrons `at` gives a ring, `None` when it cannot be aromatic.
+    fn huckel_electrons(
+        &self,
+        mol: &Molecule,
+        at: &AtomIdx,
+        spec: Specification,
+    ) -> Result<Option<u8>> {
+        let atom = mol.atom_at(at)?;
+        let e = atom.element();
+        if atom.ele_is_any() || !e.is_aromatic(spec) || atom.ring_membership() == 0 {
+            return Ok(None);
+        }
+        if self.triple(mol, at)? {
+            return Ok(None);
+        }
+        let (endo, exo) = self.doubles(mol, at)?;
+        match (endo.len(), exo.len()) {
+            (1, 0) => return Ok(Some(1)),
+            // a polarised exocyclic double bond, as in pyridone
+            (0, 1) if mol.atom_at(&exo[0])?.element().atomic_number() != 6 => return Ok(Some(0)),
+            (0, 0) => (),
+            _ => return Ok(None),
+        }
+        let connections = mol.graph().neighbors(at)?.count() + mol.hydrogen_count(at)? as usize;
+        Ok(match (e.atomic_number(), atom.charge(), connections) {
+            (6, -1, 3) => Some(2),
+            (6, 1, 3) => Some(0),
+            (7 | 15 | 33, 0, 3) => Some(2),
+            (7 | 15 | 33, -1, 2) => Some(2),
+            (8 | 16 | 34 | 52, 0, 2) => Some(2),
+            (5, 0, 3) => Some(0),
+            _ => None,
+        })
+    }
+}
+
+/// Groups of rings connected through shared bonds.
+fn fused_systems(rings: &[Vec<AtomIdx>]) -> Vec<Vec<usize>> {
+    let mut systems: Vec<Vec<usize>> = vec![];
+    let mut seen = vec![false; rings.len()];
+    for start in 0..rings.len() {
+        if seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        let mut system = vec![start];
+        let mut i = 0;
+        while i < system.len() {
+            let r = system[i];
+            for (o, s) in seen.iter_mut().enumerate() {
+                if !*s && fused(&rings[r], &rings[o]) {
+                    *s = true;
+                    system.push(o);
+                }
+            }
+            i += 1;
+        }
+        system.sort();
+        systems.push(system);
+    }
+    systems
+}
+
+fn fused(a: &[AtomIdx], b: &[AtomIdx]) -> bool {
+    a.iter().filter(|x| b.contains(x)).count() >= 2
+}
+
+/// Connected subsets of a fused system, every subset when it is small
+/// enough, the rings and fused pairs otherwise.
+fn ring_combinations(rings: &[Vec<AtomIdx>], system: &[usize]) -> Vec<Vec<usize>> {
+    if system.len() > MAX_FUSED {
+        let mut combos: Vec<Vec<usize>> = system.iter().map(|r| vec![*r]).collect();
+        for (i, a) in system.iter().enumerate() {
+            for b in system[i + 1..].iter() {
+                if fused(&rings[*a], &rings[*b]) {
+                    combos.push(vec![*a, *b]);
+                }
+            }
+        }
+        return combos;
+    }
+    let mut combos = vec![];
+    for mask in 1u32..(1 << system.len()) {
+        let subset: Vec<usize> = system
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| mask & (1 << i) != 0)
+            .map(|(_, r)| *r)
+            .collect();
+        if connected(rings, &subset) {
+            combos.push(subset);
+        }
+    }
+    combos
+}
+
+fn connected(rings: &[Vec<AtomIdx>], subset: &[usize]) -> bool {
+    let mut reached = vec![subset[0]];
+    let mut i = 0;
+    while i < reached.len() {
+        let r = reached[i];
+        for o in subset.iter() {
+            if !reached.contains(o) && fused(&rings[r], &rings[*o]) {
+                reached.push(*o);
+            }
+        }
+        i += 1;
+    }
+    reached.len() == subset.len()
+}
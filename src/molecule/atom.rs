@@ -18,11 +18,13 @@ pub struct Atom {
     explicit_hydrogens: u8,
     charge: i8,
     isotope: i16,
+    radical: u8,
+    atom_class: u32,
     bond_degree: u8,
-    ring_size: u8,
+    ring_size: u32,
     ring_membership: u8,
     ring_connectivity: u8,
-    max_bonds_ringsize: u8,
+    max_bonds_ringsize: u32,
     isorganogen: bool,
     rank: Option<usize>,
     symmetry_class: Option<usize>,
@@ -38,6 +40,8 @@ impl Atom {
             explicit_hydrogens: 0,
             charge: 0,
             isotope,
+            radical: 0,
+            atom_class: 0,
             bond_degree: 0,
             ring_membership: 0,
             ring_size: 0,
@@ -75,6 +79,8 @@ impl Atom {
             explicit_hydrogens: hydrogens,
             charge,
             isotope,
+            radical: 0,
+            atom_class: 0,
             bond_degree: 0,
             ring_membership: 0,
             ring_size: 0,
@@ -99,7 +105,29 @@ impl Atom {
 
     #[inline]
     pub fn isotope(&self) -> i16 {
-        return self.isotope;
+        self.isotope
+    }
+
+    /// MDL radical code: 0 none, 1 singlet, 2 doublet, 3 triplet.
+    #[inline]
+    pub fn radical(&self) -> u8 {
+        self.radical
+    }
+
+    #[inline]
+    pub(crate) fn set_radical(&mut self, radical: u8) {
+        self.radical = radical;
+    }
+
+    /// OpenSMILES atom class, the `12` of `[CH3:12]`, 0 when unset.
+    #[inline]
+    pub fn atom_class(&self) -> u32 {
+        self.atom_class
+    }
+
+    #[inline]
+    pub fn set_atom_class(&mut self, class: u32) {
+        self.atom_class = class;
     }
 
     #[inline]
@@ -124,6 +152,15 @@ impl Atom {
         };
     }
 
+    #[inline]
+    pub(crate) fn set_aliphatic(&mut self) {
+        match &mut self.kind {
+            AtomKind::Bracket(is_aromatic) => *is_aromatic = false,
+            AtomKind::Aromatic => self.kind = AtomKind::Aliphatic,
+            _ => (),
+        };
+    }
+
     #[inline]
     pub(crate) fn set_stereocenter(&mut self) {
         if !self.is_stereocenter {
@@ -148,10 +185,7 @@ impl Atom {
 
     #[inline]
     pub fn is_bracket_atom(&self) -> bool {
-        match self.kind {
-            AtomKind::Bracket(_) => true,
-            _ => false,
-        }
+        matches!(self.kind, AtomKind::Bracket(_))
     }
 
     #[inline]
@@ -166,12 +200,17 @@ impl Atom {
 
     #[inline]
     pub fn charge(&self) -> i8 {
-        return self.charge;
+        self.charge
     }
 
     #[inline]
     pub fn explicit_hydrogens(&self) -> u8 {
-        return self.explicit_hydrogens;
+        self.explicit_hydrogens
+    }
+
+    #[inline]
+    pub(crate) fn set_explicit_hydrogens(&mut self, hydrogens: u8) {
+        self.explicit_hydrogens = hydrogens;
     }
 
     #[inline]
@@ -185,12 +224,12 @@ impl Atom {
     }
 
     #[inline]
-    pub(crate) fn set_ring_size(&mut self, rs: u8) {
+    pub(crate) fn set_ring_size(&mut self, rs: u32) {
         self.ring_size = rs;
     }
 
     #[inline]
-    pub(crate) fn ring_size(&self) -> u8 {
+    pub(crate) fn ring_size(&self) -> u32 {
         self.ring_size
     }
 
@@ -200,12 +239,12 @@ impl Atom {
     }
 
     #[inline]
-    pub(crate) fn max_bonds_ringsize(&self) -> u8 {
+    pub(crate) fn max_bonds_ringsize(&self) -> u32 {
         self.max_bonds_ringsize
     }
 
     #[inline]
-    pub(crate) fn set_max_bonds_ringsize(&mut self, mbr: u8) {
+    pub(crate) fn set_max_bonds_ringsize(&mut self, mbr: u32) {
         self.max_bonds_ringsize = mbr;
     }
 
@@ -236,7 +275,7 @@ impl Atom {
 
     #[inline]
     pub(crate) fn set_symmetry_class(&mut self) {
-        self.symmetry_class = Some(self.rank.unwrap() as usize);
+        self.symmetry_class = Some(self.rank.unwrap());
     }
 
     pub(crate) fn to_aromatic(&self, spec: Specification) -> Option<Self> {
@@ -249,6 +288,8 @@ impl Atom {
             explicit_hydrogens: self.explicit_hydrogens,
             charge: self.charge,
             isotope: self.isotope,
+            radical: self.radical,
+            atom_class: self.atom_class,
             bond_degree: self.bond_degree,
             ring_membership: self.ring_membership,
             ring_size: self.ring_size,
@@ -271,6 +312,8 @@ impl Atom {
             explicit_hydrogens: self.explicit_hydrogens,
             charge: self.charge,
             isotope: self.isotope,
+            radical: self.radical,
+            atom_class: self.atom_class,
             bond_degree: self.bond_degree,
             ring_membership: self.ring_membership,
             ring_size: self.ring_size,
@@ -283,25 +326,26 @@ impl Atom {
         })
     }
 
-    pub(crate) fn to_aliphatic(&self) -> Option<Self> {
-        if self.is_aliphatic() {
+    /// The organic subset form of a bracket atom, `None` when the brackets
+    /// say more than the element, its aromaticity and hydrogens.
+    pub(crate) fn to_subset(&self) -> Option<Self> {
+        if !self.is_bracket_atom()
+            || self.isotope >= 0
+            || self.charge != 0
+            || self.radical != 0
+            || self.atom_class > 0
+            || !self.element.is_subset_symbol()
+        {
             return None;
         }
+        let kind = match self.is_aromatic() {
+            true => AtomKind::Aromatic,
+            false => AtomKind::Aliphatic,
+        };
         Some(Self {
-            element: self.element.clone(),
-            kind: AtomKind::Aliphatic,
-            explicit_hydrogens: self.explicit_hydrogens,
-            charge: self.charge,
-            isotope: self.isotope,
-            bond_degree: self.bond_degree,
-            ring_membership: self.ring_membership,
-            ring_size: self.ring_size,
-            max_bonds_ringsize: self.max_bonds_ringsize,
-            ring_connectivity: self.ring_connectivity,
-            isorganogen: self.isorganogen,
-            rank: self.rank,
-            symmetry_class: self.symmetry_class,
-            is_stereocenter: self.is_stereocenter,
+            kind,
+            explicit_hydrogens: 0,
+            ..self.clone()
         })
     }
 
@@ -331,4 +375,19 @@ impl Atom {
             self.ring_membership = 1;
         }
     }
+
+    #[inline]
+    pub(crate) fn decr_degree(&mut self, var: u8) {
+        self.bond_degree -= var;
+    }
+
+    /// Clears the ring and stereocenter flags set by the perception passes.
+    #[inline]
+    pub(crate) fn reset_perception(&mut self) {
+        self.ring_membership = 0;
+        self.ring_size = 0;
+        self.ring_connectivity = 0;
+        self.max_bonds_ringsize = 0;
+        self.is_stereocenter = false;
+    }
 }
@@ -1,3 +1,5 @@
+use super::AtomIdx;
+
 #[macro_export]
 macro_rules! to_bond {
     ($variable:ident, $t:expr, $ele:expr, $direct:expr, $kind: expr) => {
@@ -26,7 +28,7 @@ pub struct Bond {
     electron: u8,
     directional: bool,
     kind: BondKind,
-    ring_size: u8,
+    ring_size: u32,
     ring_membership: u8,
 }
 
@@ -44,12 +46,12 @@ impl Bond {
 
     #[inline]
     pub fn token(&self) -> &str {
-        return self.token;
+        self.token
     }
 
     #[inline]
     pub(crate) fn electron(&self) -> u8 {
-        return self.electron;
+        self.electron
     }
 
     #[inline]
@@ -91,12 +93,12 @@ impl Bond {
     }
 
     #[inline]
-    pub(crate) fn set_ring_size(&mut self, rs: u8) {
+    pub(crate) fn set_ring_size(&mut self, rs: u32) {
         self.ring_size = rs;
     }
 
     #[inline]
-    pub(crate) fn ring_size(&self) -> u8 {
+    pub(crate) fn ring_size(&self) -> u32 {
         self.ring_size
     }
 
@@ -121,18 +123,18 @@ to_bond!(DOWN, "\\", 1, true, BondKind::Down);
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct RingBond {
     bond: Bond,
-    vertex: u8,
-    pos: Option<u8>,
+    vertex: AtomIdx,
+    pos: Option<usize>,
 }
 
 impl RingBond {
     #[inline]
-    pub fn new(bond: Bond, vertex: u8, pos: Option<u8>) -> Self {
+    pub fn new(bond: Bond, vertex: AtomIdx, pos: Option<usize>) -> Self {
         Self { bond, vertex, pos }
     }
 
     #[inline]
-    pub fn vertex(&self) -> u8 {
+    pub fn vertex(&self) -> AtomIdx {
         self.vertex
     }
 
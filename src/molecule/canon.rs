@@ -3,8 +3,16 @@ use std::{
     hash::Hash,
 };
 
+use super::{
+    aromaticity::{Aromatic, AromaticityModel, Huckel},
+    kekule::sorted_pair,
+    topology::{permutation_parity, TopologySeq},
+    AtomIdx, Molecule,
+};
+use crate::error::Result;
 use primitive_types::U256;
-use std::collections::HashMap;
+use std::collections::{hash_map::Entry, BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::RwLock;
 
 pub(crate) const PRIMES: [usize; 300] = [
     2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
@@ -26,44 +34,73 @@ pub(crate) const PRIMES: [usize; 300] = [
     1877, 1879, 1889, 1901, 1907, 1913, 1931, 1933, 1949, 1951, 1973, 1979, 1987,
 ];
 
+// primes beyond `PRIMES`, grown on demand for molecules with more than 300 distinct ranks
+static EXTENDED_PRIMES: RwLock<Vec<usize>> = RwLock::new(Vec::new());
+
 pub(crate) fn prime(n: usize) -> usize {
-    if n > 0 {
-        PRIMES[n]
-    } else {
-        1
+    if n == 0 {
+        return 1;
+    }
+    if n < PRIMES.len() {
+        return PRIMES[n];
+    }
+    let ix = n - PRIMES.len();
+    if let Some(p) = EXTENDED_PRIMES.read().unwrap().get(ix) {
+        return *p;
+    }
+    let mut extended = EXTENDED_PRIMES.write().unwrap();
+    let mut candidate = *extended.last().unwrap_or(&PRIMES[PRIMES.len() - 1]);
+    while extended.len() <= ix {
+        candidate += 2;
+        if is_prime(candidate) {
+            extended.push(candidate);
+        }
+    }
+    extended[ix]
+}
+
+#[inline]
+fn is_prime(n: usize) -> bool {
+    let mut d = 3;
+    while d * d <= n {
+        if n.is_multiple_of(d) {
+            return false;
+        }
+        d += 2;
     }
+    !n.is_multiple_of(2)
 }
 
-pub(crate) fn rank(x: &mut Vec<U256>, dist: &mut usize) {
+pub(crate) fn rank(x: &mut [U256], dist: &mut usize) {
     if x.len() < 2 {
         return;
     }
-    let mut cp = x.clone();
+    let mut cp = x.to_vec();
     cp.sort_unstable();
     let mut hm = HashMap::new();
     let mut ix = 1;
     for i in cp.into_iter() {
-        if !hm.contains_key(&i) {
-            hm.insert(i, U256::from(ix));
+        if let Entry::Vacant(e) = hm.entry(i) {
+            e.insert(U256::from(ix));
             ix += 1;
         }
     }
     *dist = hm.len();
-    for i in 0..x.len() {
-        x[i] = *hm.get(&x[i]).unwrap();
+    for v in x.iter_mut() {
+        *v = *hm.get(v).unwrap();
     }
 }
 
 pub(crate) fn rank_matrix<T: Hash + Clone + PartialOrd + Eq + Default>(
-    matrix: &mut Vec<[T; 3]>,
+    matrix: &mut [[T; 3]],
 ) -> Vec<usize> {
-    let mut cp = matrix.clone();
+    let mut cp = matrix.to_vec();
     sort_matrix(&mut cp);
     let mut ix = 1;
     let mut hm: HashMap<[T; 3], usize> = HashMap::new();
     for v in cp.into_iter() {
-        if !hm.contains_key(&v) {
-            hm.insert(v, ix);
+        if let Entry::Vacant(e) = hm.entry(v) {
+            e.insert(ix);
             ix += 1;
         };
     }
@@ -76,14 +113,14 @@ pub(crate) fn rank_matrix<T: Hash + Clone + PartialOrd + Eq + Default>(
 
 // desc
 #[inline]
-pub(crate) fn sort_matrix<T: PartialOrd + Clone>(matrix: &mut Vec<[T; 3]>) {
-    matrix.sort_by(|x, y| lexcompare(&x.to_vec(), &y.to_vec()));
+pub(crate) fn sort_matrix<T: PartialOrd + Clone>(matrix: &mut [[T; 3]]) {
+    matrix.sort_by(|x, y| lexcompare(x, y));
 }
 
 // 1 - (x < y), 0 - (x = y), -1 - (x > y)
 #[inline]
-pub(crate) fn lexcompare<T: PartialOrd>(x: &Vec<T>, y: &Vec<T>) -> Ordering {
-    if x.len() == 0 && y.len() == 0 {
+pub(crate) fn lexcompare<T: PartialOrd>(x: &[T], y: &[T]) -> Ordering {
+    if x.is_empty() && y.is_empty() {
         return Ordering::Equal;
     }
     let m = min(x.len(), y.len());
@@ -99,11 +136,11 @@ pub(crate) fn lexcompare<T: PartialOrd>(x: &Vec<T>, y: &Vec<T>) -> Ordering {
     } else if x.len() > y.len() {
         return Ordering::Greater;
     }
-    return Ordering::Equal;
+    Ordering::Equal
 }
 
 #[inline]
-pub(crate) fn is_unique_array<T: Hash + Eq>(array: &Vec<T>) -> bool {
+pub(crate) fn is_unique_array<T: Hash + Eq>(array: &[T]) -> bool {
     let mut hm = HashMap::new();
     for i in array.iter() {
         if hm.contains_key(i) {
@@ -112,7 +149,466 @@ pub(crate) fn is_unique_array<T: Hash + Eq>(array: &Vec<T>) -> bool {
             hm.insert(i, 1);
         }
     }
-    return true;
+    true
+}
+
+/// A double bond with `/` or `\` marks on both ends, `a` on the side of
+/// `u` and `b` on the side of `v`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct DoubleBondStereo {
+    pub u: AtomIdx,
+    pub v: AtomIdx,
+    pub a: AtomIdx,
+    pub b: AtomIdx,
+    /// Whether `a` and `b` are on opposite sides.
+    pub trans: bool,
+}
+
+impl DoubleBondStereo {
+    /// Whether `x` on the side of `u` and `y` on the side of `v` are on
+    /// opposite sides, each end having at most two substituents.
+    pub fn trans_of(&self, x: AtomIdx, y: AtomIdx) -> bool {
+        self.trans ^ (x != self.a) ^ (y != self.b)
+    }
+}
+
+/// Double bonds made stereogenic by directional bonds, `a/u=v/b` being trans.
+pub(crate) fn double_bond_stereo(mol: &Molecule) -> Result<Vec<DoubleBondStereo>> {
+    let marked = |u: AtomIdx, v: AtomIdx| -> Result<Option<AtomIdx>> {
+        for a in mol.graph().neighbors(&u)? {
+            if *a != v && mol.edge_at(*a, u)?.direction() {
+                return Ok(Some(*a));
+            }
+        }
+        Ok(None)
+    };
+    let mut stereo = vec![];
+    for [u, v] in mol.bonds().iter().copied() {
+        let bond = mol.edge_at(u, v)?;
+        if bond.electron() != 2 || bond.is_aromatic() {
+            continue;
+        }
+        if let (Some(a), Some(b)) = (marked(u, v)?, marked(v, u)?) {
+            let trans = mol.edge_at(a, u)?.token() == mol.edge_at(v, b)?.token();
+            stereo.push(DoubleBondStereo { u, v, a, b, trans });
+        }
+    }
+    Ok(stereo)
+}
+
+/// What a canonical labelling tells atoms and bonds apart by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Labels {
+    pub charge: bool,
+    pub isotope: bool,
+    pub atom_class: bool,
+    /// Tetrahedral and double bond configurations.
+    pub stereo: bool,
+    /// Bonds by their written token and aromaticity as marked, rather than
+    /// by order and as Hückel's rule perceives it on any Kekulé form.
+    pub tokens: bool,
+}
+
+impl Labels {
+    /// Everything a SMILES writes, stereo configurations when `stereo`.
+    pub fn smiles(stereo: bool) -> Self {
+        Labels {
+            charge: true,
+            isotope: true,
+            atom_class: true,
+            stereo,
+            tokens: true,
+        }
+    }
+
+    fn atom(&self, mol: &Molecule, at: AtomIdx, aromatic: Option<&Aromatic>) -> Result<[i64; 6]> {
+        let atom = mol.atom_at(&at)?;
+        Ok([
+            atom.element().atomic_number() as i64,
+            match aromatic {
+                Some(aromatic) => aromatic.atoms.contains(&at) as i64,
+                None => atom.is_aromatic() as i64,
+            },
+            if self.isotope {
+                atom.isotope() as i64
+            } else {
+                -1
+            },
+            if self.charge { atom.charge() as i64 } else { 0 },
+            mol.hydrogen_count(&at)? as i64,
+            if self.atom_class {
+                atom.atom_class() as i64
+            } else {
+                0
+            },
+        ])
+    }
+
+    /// Code of the bond `u`-`v`, lower for higher orders.
+    fn bond(
+        &self,
+        mol: &Molecule,
+        u: AtomIdx,
+        v: AtomIdx,
+        aromatic: Option<&Aromatic>,
+    ) -> Result<i64> {
+        let bond = mol.edge_at(u, v)?;
+        match aromatic {
+            Some(aromatic) if aromatic.bonds.contains(&sorted_pair(u, v)) => Ok(4),
+            Some(_) => Ok(match bond.electron() {
+                2 => 1,
+                3 => 2,
+                4 => 3,
+                _ => 5,
+            }),
+            None => Ok(match bond.token() {
+                "=" => 1,
+                "#" => 2,
+                "$" => 3,
+                ":" => 4,
+                "-" => 5,
+                _ => 6,
+            }),
+        }
+    }
+}
+
+/// Ranks, indexed by atom - 1, that only depend on the molecule and not on
+/// the order its atoms were added in, stereo configurations included when
+/// `stereo`.
+pub(crate) fn canonical_ranks(mol: &Molecule, stereo: bool) -> Result<Vec<usize>> {
+    Ok(canonical_labelling(mol, Labels::smiles(stereo))?.0)
+}
+
+/// Canonical ranks under `labels` and the [`certificate`] they give, equal
+/// for two molecules exactly when they are the same under `labels`.
+///
+/// The partition by atom invariants is refined until equitable; ties left
+/// are broken by trying every atom of the first tied cell in turn, refining
+/// again and recursing. Of all labellings reached the one with the smallest
+/// certificate wins; labellings with the same certificate are automorphisms,
+/// which prune the branches they map onto each other.
+pub(crate) fn canonical_labelling(
+    mol: &Molecule,
+    labels: Labels,
+) -> Result<(Vec<usize>, Vec<i64>)> {
+    let n = mol.atoms().len();
+    // symmetry classes only refine the start when they see no more than
+    // the labels do, they carry no stereo so either stereo setting will do
+    let symmetry = labels == Labels::smiles(labels.stereo);
+    let aromatic = match labels.tokens {
+        true => None,
+        false => Some(Huckel::default().perceive(mol)?),
+    };
+    let mut atoms = Vec::with_capacity(n);
+    let mut keys = Vec::with_capacity(n);
+    for at in mol.atoms().iter() {
+        let class = match symmetry {
+            true => mol.atom_at(at)?.symmetry_class() as i64,
+            false => 0,
+        };
+        atoms.push(labels.atom(mol, *at, aromatic.as_ref())?);
+        keys.push((class, atoms[*at as usize - 1]));
+    }
+    let mut adjacency = Vec::with_capacity(n);
+    for at in mol.atoms().iter() {
+        let mut ns = vec![];
+        for b in mol.graph().neighbors(at)? {
+            ns.push((
+                *b as usize - 1,
+                labels.bond(mol, *at, *b, aromatic.as_ref())?,
+            ));
+        }
+        adjacency.push(ns);
+    }
+    let mut sorted = keys.clone();
+    sorted.sort_unstable();
+    sorted.dedup();
+    let mut partition = Partition {
+        cell: vec![0; n],
+        members: vec![vec![]; n],
+        cells: 0,
+    };
+    let mut start = 0;
+    for key in sorted.iter() {
+        let atoms: Vec<usize> = (0..n).filter(|ix| keys[*ix] == *key).collect();
+        for ix in atoms.iter() {
+            partition.cell[*ix] = start;
+        }
+        let len = atoms.len();
+        partition.members[start] = atoms;
+        partition.cells += 1;
+        start += len;
+    }
+    let mut search = Search {
+        mol,
+        atoms,
+        adjacency,
+        labels,
+        stereo: match labels.stereo {
+            true => double_bond_stereo(mol)?,
+            false => vec![],
+        },
+        first: None,
+        best: None,
+        automorphisms: vec![],
+    };
+    if n > 0 {
+        let splitters = (0..n)
+            .filter(|s| !partition.members[*s].is_empty())
+            .collect();
+        search.refine(&mut partition, splitters);
+        search.descend(partition, &mut vec![])?;
+    }
+    Ok(search.best.map(|(c, r)| (r, c)).unwrap_or_default())
+}
+
+/// Ordered partition of the atoms, 0-based, into cells named by the
+/// position they start at in rank order.
+#[derive(Clone)]
+struct Partition {
+    /// Cell of each atom.
+    cell: Vec<usize>,
+    /// Atoms of the cell starting at each position, empty elsewhere.
+    members: Vec<Vec<usize>>,
+    cells: usize,
+}
+
+struct Search<'a> {
+    mol: &'a Molecule,
+    /// Labels of each atom, 0-based.
+    atoms: Vec<[i64; 6]>,
+    /// Neighbours of each atom, 0-based, with their bond codes.
+    adjacency: Vec<Vec<(usize, i64)>>,
+    labels: Labels,
+    stereo: Vec<DoubleBondStereo>,
+    first: Option<(Vec<i64>, Vec<usize>)>,
+    best: Option<(Vec<i64>, Vec<usize>)>,
+    /// Found automorphisms as images, indexed by atom - 1.
+    automorphisms: Vec<Vec<AtomIdx>>,
+}
+
+impl Search<'_> {
+    /// Splits cells by their bonds into the `splitters` cells, queueing
+    /// every new cell as a splitter, until the partition is equitable. Atoms
+    /// with higher order bonds into a splitter go to the lower cells.
+    fn refine(&self, p: &mut Partition, splitters: VecDeque<usize>) {
+        let mut queued = vec![false; p.cell.len()];
+        for s in splitters.iter() {
+            queued[*s] = true;
+        }
+        let mut queue = splitters;
+        while let Some(w) = queue.pop_front() {
+            queued[w] = false;
+            if p.cells == p.cell.len() {
+                break;
+            }
+            let mut counts: HashMap<usize, u64> = HashMap::new();
+            for a in p.members[w].iter() {
+                for (b, code) in self.adjacency[*a].iter() {
+                    *counts.entry(*b).or_default() += 1 << (10 * code);
+                }
+            }
+            let mut touched: Vec<usize> = counts.keys().map(|b| p.cell[*b]).collect();
+            touched.sort_unstable();
+            touched.dedup();
+            for x in touched {
+                if p.members[x].len() == 1 {
+                    continue;
+                }
+                let mut parts: BTreeMap<u64, Vec<usize>> = BTreeMap::new();
+                for a in p.members[x].iter() {
+                    parts
+                        .entry(counts.get(a).copied().unwrap_or_default())
+                        .or_default()
+                        .push(*a);
+                }
+                if parts.len() == 1 {
+                    continue;
+                }
+                p.cells += parts.len() - 1;
+                let mut start = x;
+                for (_, atoms) in parts {
+                    for a in atoms.iter() {
+                        p.cell[*a] = start;
+                    }
+                    let len = atoms.len();
+                    p.members[start] = atoms;
+                    if !queued[start] {
+                        queued[start] = true;
+                        queue.push_back(start);
+                    }
+                    start += len;
+                }
+            }
+        }
+    }
+
+    fn descend(&mut self, p: Partition, fixed: &mut Vec<AtomIdx>) -> Result<()> {
+        if p.cells == p.cell.len() {
+            return self.leaf(p.cell.iter().map(|c| c + 1).collect());
+        }
+        let x = (0..p.members.len())
+            .find(|s| p.members[*s].len() > 1)
+            .unwrap();
+        let mut members = p.members[x].clone();
+        members.sort_unstable();
+        let mut tried: Vec<AtomIdx> = vec![];
+        for ix in members {
+            let at = ix as AtomIdx + 1;
+            if !tried.is_empty() && self.orbit(fixed, &tried).contains(&at) {
+                continue;
+            }
+            let mut next = p.clone();
+            let rest: Vec<usize> = next.members[x]
+                .iter()
+                .copied()
+                .filter(|a| *a != ix)
+                .collect();
+            for a in rest.iter() {
+                next.cell[*a] = x + 1;
+            }
+            next.members[x] = vec![ix];
+            next.members[x + 1] = rest;
+            next.cells += 1;
+            self.refine(&mut next, VecDeque::from([x]));
+            fixed.push(at);
+            self.descend(next, fixed)?;
+            fixed.pop();
+            tried.push(at);
+        }
+        Ok(())
+    }
+
+    fn orbit(&self, fixed: &[AtomIdx], from: &[AtomIdx]) -> HashSet<AtomIdx> {
+        let group: Vec<&Vec<AtomIdx>> = self
+            .automorphisms
+            .iter()
+            .filter(|g| fixed.iter().all(|at| g[*at as usize - 1] == *at))
+            .collect();
+        let mut orbit: HashSet<AtomIdx> = from.iter().copied().collect();
+        let mut queue: VecDeque<AtomIdx> = from.iter().copied().collect();
+        while let Some(at) = queue.pop_front() {
+            for g in group.iter() {
+                let image = g[at as usize - 1];
+                if orbit.insert(image) {
+                    queue.push_back(image);
+                }
+            }
+        }
+        orbit
+    }
+
+    fn leaf(&mut self, ranks: Vec<usize>) -> Result<()> {
+        let cert = self.certificate(&ranks)?;
+        for known in [&self.first, &self.best].into_iter().flatten() {
+            if known.0 == cert {
+                let mut at_rank = vec![0; ranks.len() + 1];
+                for (ix, r) in known.1.iter().enumerate() {
+                    at_rank[*r] = ix as AtomIdx + 1;
+                }
+                let image: Vec<AtomIdx> = ranks.iter().map(|r| at_rank[*r]).collect();
+                if image
+                    .iter()
+                    .enumerate()
+                    .any(|(ix, at)| *at as usize != ix + 1)
+                {
+                    self.automorphisms.push(image);
+                }
+                return Ok(());
+            }
+        }
+        if self.first.is_none() {
+            self.first = Some((cert.clone(), ranks.clone()));
+        }
+        if self.best.as_ref().is_none_or(|(best, _)| cert < *best) {
+            self.best = Some((cert, ranks));
+        }
+        Ok(())
+    }
+
+    /// The molecule relabelled by `ranks`: atoms, bonds and stereo
+    /// configurations in rank order.
+    fn certificate(&self, ranks: &[usize]) -> Result<Vec<i64>> {
+        let mol = self.mol;
+        let rank = |at: AtomIdx| ranks[at as usize - 1] as i64;
+        let mut atoms: Vec<AtomIdx> = mol.atoms().clone();
+        atoms.sort_by_key(|at| rank(*at));
+        let mut cert = vec![];
+        for at in atoms.iter() {
+            cert.extend(self.atoms[*at as usize - 1]);
+            let mut bonds = vec![];
+            for (n, code) in self.adjacency[*at as usize - 1].iter() {
+                bonds.push(ranks[*n] as i64 * 8 + code);
+            }
+            bonds.sort_unstable();
+            cert.push(bonds.len() as i64);
+            cert.extend(bonds);
+        }
+        if self.labels.stereo {
+            for at in atoms.iter() {
+                if let Some(parity) = tetrahedral_parity(mol, *at, ranks)? {
+                    cert.extend([rank(*at), parity as i64]);
+                }
+            }
+        }
+        cert.push(-1);
+        let mut doubles = vec![];
+        for db in self.stereo.iter() {
+            let first = |u: AtomIdx, v: AtomIdx| -> Result<AtomIdx> {
+                let mut subs: Vec<AtomIdx> = mol
+                    .graph()
+                    .neighbors(&u)?
+                    .copied()
+                    .filter(|a| *a != v)
+                    .collect();
+                subs.sort_by_key(|a| rank(*a));
+                Ok(subs[0])
+            };
+            let (x, y) = (first(db.u, db.v)?, first(db.v, db.u)?);
+            let (u, v) = (rank(db.u).min(rank(db.v)), rank(db.u).max(rank(db.v)));
+            doubles.push([u, v, db.trans_of(x, y) as i64]);
+        }
+        doubles.sort_unstable();
+        cert.extend(doubles.into_iter().flatten());
+        Ok(cert)
+    }
+}
+
+/// Parity of the tetrahedral or extended tetrahedral configuration at `at`
+/// with its carriers in rank order, an implicit hydrogen or lone pair
+/// coming first.
+pub(crate) fn tetrahedral_parity(
+    mol: &Molecule,
+    at: AtomIdx,
+    ranks: &[usize],
+) -> Result<Option<i8>> {
+    let top = match mol.topology_at(&at) {
+        Some(t)
+            if matches!(
+                t.seq(),
+                TopologySeq::Tetrahedral | TopologySeq::ExtendedTetrahedral
+            ) =>
+        {
+            t
+        }
+        _ => return Ok(None),
+    };
+    let key = |c: &i64| match *c {
+        c if c <= 0 || c == at as i64 => 0,
+        c => ranks[c as usize - 1],
+    };
+    let mut sorted = top.carriers().to_vec();
+    sorted.sort_by_key(key);
+    let parity = match permutation_parity(top.carriers(), &sorted) {
+        Some(p) => p,
+        None => return Ok(None),
+    };
+    let sign = match top.configuration()?.is_anti_clockwise() {
+        true => -1,
+        false => 1,
+    };
+    Ok(Some(sign * parity))
 }
 
 #[test]
@@ -149,6 +645,16 @@ fn test_sort_matrix() {
     assert_eq!(matrix, vec![[2, 2, 3], [2, 3, 4]]);
 }
 
+#[test]
+fn test_prime() {
+    assert_eq!(prime(0), 1);
+    assert_eq!(prime(1), 3);
+    assert_eq!(prime(299), 1987);
+    assert_eq!(prime(300), 1993);
+    assert_eq!(prime(1000), 7927);
+    assert_eq!(prime(301), 1997);
+}
+
 #[test]
 fn test_rank_matrix() {
     let mut matrix = vec![[2, 3, 4], [1, 2, 3], [1, 1, 2], [1, 1, 2]];
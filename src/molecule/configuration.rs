@@ -46,10 +46,7 @@ impl Configuration {
     }
 
     pub(crate) fn is_anti_clockwise(&self) -> bool {
-        self.order.clone().map_or(false, |o| match o {
-            ConfigOrder::AntiClockwise(_) => true,
-            _ => false,
-        })
+        matches!(self.order, Some(ConfigOrder::AntiClockwise(_)))
     }
 
     pub fn is_tetrahedral(&self) -> bool {
@@ -94,6 +91,36 @@ impl Configuration {
     pub fn is_trigonal(&self) -> bool {
         self.kind == ConfigKind::DoubleBond
     }
+
+    /// The configuration with the opposite winding, configurations without a
+    /// winding are returned unchanged.
+    pub(crate) fn inverse(&self) -> Self {
+        let anti = self.is_anti_clockwise();
+        match self.kind {
+            ConfigKind::Tetrahedral if self.order.is_some() => {
+                if anti {
+                    TH2
+                } else {
+                    TH1
+                }
+            }
+            ConfigKind::DoubleBond if self.order.is_some() => {
+                if anti {
+                    DB2
+                } else {
+                    DB1
+                }
+            }
+            ConfigKind::ExtendedTetrahedral if self.order.is_some() => {
+                if anti {
+                    AL2
+                } else {
+                    AL1
+                }
+            }
+            _ => self.clone(),
+        }
+    }
 }
 
 #[macro_export]
@@ -2,7 +2,7 @@ use phf::{phf_map, phf_set};
 
 use crate::error::RuatomError;
 
-#[derive(PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Specification {
     DayLight,
     General,
@@ -36,7 +36,12 @@ impl Element {
 
     #[inline]
     pub fn read(c: &str) -> Option<Self> {
-        ELEMENT_MAP.get(c).map_or(None, |e| Some(e.clone()))
+        ELEMENT_MAP.get(c).cloned()
+    }
+
+    /// The element with atomic number `n`, `*` for 0.
+    pub(crate) fn by_atomic_number(n: u8) -> Option<Self> {
+        ELEMENT_MAP.values().find(|e| e.atomic_number == n).cloned()
     }
 
     #[inline]
@@ -59,6 +64,26 @@ impl Element {
         ORGANIC_SUBSET.contains(&self.atomic_number)
     }
 
+    /// Elements SMILES writes without brackets, `H` and `*` included.
+    #[inline]
+    pub(crate) fn is_subset_symbol(&self) -> bool {
+        matches!(
+            self.symbol,
+            "B" | "C" | "N" | "O" | "P" | "S" | "F" | "Cl" | "Br" | "I" | "H" | "*"
+        )
+    }
+
+    #[inline]
+    pub(crate) fn default_valences(&self) -> impl Iterator<Item = u8> + '_ {
+        self.valence.iter().copied().filter(|v| *v > 0)
+    }
+
+    /// Whether `valence` is within one of the default valences.
+    #[inline]
+    pub(crate) fn has_default_valence(&self, valence: u8) -> bool {
+        self.valence.iter().any(|&v| v >= valence)
+    }
+
     #[inline]
     pub fn implicit_hydrogen_count(&self, sum: i32) -> i32 {
         if self.atomic_number == 0 {
@@ -93,11 +118,7 @@ impl Element {
 
     #[inline]
     pub(crate) fn implict_atom_hydrogen(&self, valence: u8) -> u8 {
-        if self.valence[0] > valence {
-            return self.valence[0] - valence;
-        } else {
-            return 0;
-        }
+        self.valence[0].saturating_sub(valence)
     }
 
     #[inline]
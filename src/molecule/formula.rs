@@ -0,0 +1,118 @@
+//! Molecular formulas in Hill notation: carbon, then hydrogen, then every
+//! other element alphabetically, or all of them alphabetically when there is
+//! no carbon. The net charge follows as `+`, `-2` and so on.
+
+use super::{AtomIdx, Molecule};
+use crate::error::Result;
+use std::collections::{BTreeMap, VecDeque};
+
+/// Writes formulas of whole molecules or of each fragment, the default
+/// matches [`Molecule::formula`].
+///
+/// With isotopes, labelled atoms are counted apart from the element and
+/// written after it, `[13C]`, or as `D` and `T` for hydrogen.
+///
+/// ```
+/// use ruatom::molecule::FormulaBuilder;
+/// use ruatom::Parser;
+///
+/// let m = Parser::new("[13CH3]C([2H])O").parse().unwrap();
+/// assert_eq!(m.formula().unwrap(), "C2H6O");
+/// let labelled = FormulaBuilder::new().isotopes(true);
+/// assert_eq!(labelled.build(&m).unwrap(), "C[13C]H5DO");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FormulaBuilder {
+    isotopes: bool,
+}
+
+impl FormulaBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn isotopes(mut self, on: bool) -> Self {
+        self.isotopes = on;
+        self
+    }
+
+    pub fn build(&self, mol: &Molecule) -> Result<String> {
+        self.write(mol, mol.atoms())
+    }
+
+    /// Formulas of the dot-disconnected fragments, in the order their first
+    /// atoms come.
+    pub fn fragments(&self, mol: &Molecule) -> Result<Vec<String>> {
+        let mut seen = vec![false; mol.atoms().len()];
+        let mut formulas = vec![];
+        for at in mol.atoms().iter() {
+            if seen[*at as usize - 1] {
+                continue;
+            }
+            seen[*at as usize - 1] = true;
+            let mut fragment = vec![*at];
+            let mut queue = VecDeque::from([*at]);
+            while let Some(u) = queue.pop_front() {
+                for v in mol.graph().neighbors(&u)? {
+                    if !seen[*v as usize - 1] {
+                        seen[*v as usize - 1] = true;
+                        fragment.push(*v);
+                        queue.push_back(*v);
+                    }
+                }
+            }
+            formulas.push(self.write(mol, &fragment)?);
+        }
+        Ok(formulas)
+    }
+
+    fn write(&self, mol: &Molecule, atoms: &[AtomIdx]) -> Result<String> {
+        // element, then mass number with 0 for unlabelled atoms
+        let mut counts: BTreeMap<(String, i16), u32> = BTreeMap::new();
+        let mut charge = 0;
+        for at in atoms.iter() {
+            let atom = mol.atom_at(at)?;
+            let isotope = match self.isotopes {
+                true => atom.isotope().max(0),
+                false => 0,
+            };
+            *counts
+                .entry((atom.element().symbol().to_string(), isotope))
+                .or_default() += 1;
+            let hydrogens = mol.hydrogen_count(at)? as u32;
+            if hydrogens > 0 {
+                *counts.entry(("H".to_string(), 0)).or_default() += hydrogens;
+            }
+            charge += atom.charge() as i32;
+        }
+        let carbon = counts.keys().any(|(symbol, _)| symbol == "C");
+        let hill = |symbol: &str| match (carbon, symbol) {
+            (true, "C") => 0,
+            (true, "H") => 1,
+            _ => 2,
+        };
+        let mut order: Vec<((String, i16), u32)> = counts.into_iter().collect();
+        order.sort_by(|((a, i), _), ((b, j), _)| (hill(a), a, i).cmp(&(hill(b), b, j)));
+
+        let mut formula = String::new();
+        for ((symbol, isotope), count) in order {
+            match (symbol.as_str(), isotope) {
+                ("H", 2) => formula.push('D'),
+                ("H", 3) => formula.push('T'),
+                (_, 0) => formula.push_str(&symbol),
+                _ => formula.push_str(&format!("[{}{}]", isotope, symbol)),
+            }
+            if count > 1 {
+                formula.push_str(&count.to_string());
+            }
+        }
+        match charge {
+            0 => (),
+            1 => formula.push('+'),
+            -1 => formula.push('-'),
+            c if c > 0 => formula.push_str(&format!("+{}", c)),
+            c => formula.push_str(&c.to_string()),
+        }
+        Ok(formula)
+    }
+}
@@ -0,0 +1,228 @@
+//! Kekulé structures for aromatic systems.
+//!
+//! Every aromatic atom that still needs a double bond is matched to one of
+//! its aromatic neighbours, a perfect matching over those atoms is a valid
+//! Kekulé form. Matching uses Edmonds' blossom algorithm so fused systems
+//! with odd rings are handled.
+
+use super::{AtomIdx, Molecule};
+use crate::error::{Result, RuatomError};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+const NONE: usize = usize::MAX;
+
+/// Bonds to write as double in a Kekulé form, as ordered atom pairs.
+pub(crate) fn kekule_bonds(mol: &Molecule) -> Result<HashSet<[AtomIdx; 2]>> {
+    let order: Vec<usize> = (1..=mol.atoms().len()).collect();
+    ranked_kekule_bonds(mol, &order)
+}
+
+/// [`kekule_bonds`] matching atoms and their neighbours in the order of
+/// `ranks`, indexed by atom - 1, so canonical ranks give a canonical form.
+pub(crate) fn ranked_kekule_bonds(
+    mol: &Molecule,
+    ranks: &[usize],
+) -> Result<HashSet<[AtomIdx; 2]>> {
+    let rank = |at: &AtomIdx| ranks[*at as usize - 1];
+    let mut needy = vec![];
+    for at in mol.atoms().iter() {
+        if mol.atom_at(at)?.is_aromatic() && needs_double(mol, at)? {
+            needy.push(*at);
+        }
+    }
+    needy.sort_by_key(rank);
+    let index: HashMap<AtomIdx, usize> = needy.iter().enumerate().map(|(i, a)| (*a, i)).collect();
+    let mut adj = vec![vec![]; needy.len()];
+    for (i, at) in needy.iter().enumerate() {
+        for n in mol.graph().neighbors(at)? {
+            if let Some(j) = index.get(n) {
+                if is_delocalised(mol, *at, *n)? {
+                    adj[i].push(*j);
+                }
+            }
+        }
+        adj[i].sort_unstable();
+    }
+    let mate = Blossom::new(&adj).solve();
+    if mate.contains(&NONE) {
+        let unmatched = needy
+            .iter()
+            .zip(mate.iter())
+            .filter(|(_, m)| **m == NONE)
+            .map(|(a, _)| *a)
+            .collect();
+        return Err(RuatomError::NoKekuleStructure(unmatched));
+    }
+    Ok(mate
+        .iter()
+        .enumerate()
+        .filter(|(i, m)| i < *m)
+        .map(|(i, m)| sorted_pair(needy[i], needy[*m]))
+        .collect())
+}
+
+pub(crate) fn sorted_pair(u: AtomIdx, v: AtomIdx) -> [AtomIdx; 2] {
+    if u < v {
+        [u, v]
+    } else {
+        [v, u]
+    }
+}
+
+/// Bonds between aromatic atoms written without an order, the ones a
+/// Kekulé form assigns.
+pub(crate) fn is_delocalised(mol: &Molecule, u: AtomIdx, v: AtomIdx) -> Result<bool> {
+    let b = mol.edge_at(u, v)?;
+    Ok(mol.atom_at(&u)?.is_aromatic()
+        && mol.atom_at(&v)?.is_aromatic()
+        && b.electron() == 1
+        && !b.direction()
+        && !b.is("-"))
+}
+
+/// Whether one more bond order brings the atom to a default valence for its
+/// charge.
+fn needs_double(mol: &Molecule, at: &AtomIdx) -> Result<bool> {
+    let atom = mol.atom_at(at)?;
+    let mut sum = mol.hydrogen_count(at)? as i16;
+    for n in mol.graph().neighbors(at)? {
+        sum += mol.edge_at(*at, *n)?.electron() as i16;
+    }
+    let e = atom.element();
+    let charge = atom.charge() as i16;
+    let valences: Vec<u8> = e.default_valences().collect();
+    Ok(valences.into_iter().any(|v| {
+        let v = v as i16;
+        let v = match e.atomic_number() {
+            5 | 13 => v - charge,
+            6 | 14 | 32 => v - charge.abs(),
+            _ => v + charge,
+        };
+        v == sum + 1
+    }))
+}
+
+/// Maximum matching in a general graph.
+struct Blossom<'a> {
+    adj: &'a [Vec<usize>],
+    mate: Vec<usize>,
+    parent: Vec<usize>,
+    base: Vec<usize>,
+    used: Vec<bool>,
+    blossom: Vec<bool>,
+}
+
+impl<'a> Blossom<'a> {
+    fn new(adj: &'a [Vec<usize>]) -> Self {
+        let n = adj.len();
+        Self {
+            adj,
+            mate: vec![NONE; n],
+            parent: vec![NONE; n],
+            base: (0..n).collect(),
+            used: vec![false; n],
+            blossom: vec![false; n],
+        }
+    }
+
+    fn solve(mut self) -> Vec<usize> {
+        // greedy start, fewest options first
+        let mut order: Vec<usize> = (0..self.adj.len()).collect();
+        order.sort_by_key(|v| self.adj[*v].len());
+        for v in order.iter() {
+            if self.mate[*v] != NONE {
+                continue;
+            }
+            if let Some(u) = self.adj[*v].iter().find(|u| self.mate[**u] == NONE) {
+                self.mate[*v] = *u;
+                self.mate[*u] = *v;
+            }
+        }
+        for v in order {
+            if self.mate[v] == NONE {
+                if let Some(end) = self.find_path(v) {
+                    self.augment(end);
+                }
+            }
+        }
+        self.mate
+    }
+
+    fn augment(&mut self, mut v: usize) {
+        while v != NONE {
+            let pv = self.parent[v];
+            let next = self.mate[pv];
+            self.mate[v] = pv;
+            self.mate[pv] = v;
+            v = next;
+        }
+    }
+
+    fn lca(&self, mut a: usize, mut b: usize) -> usize {
+        let mut seen = vec![false; self.adj.len()];
+        loop {
+            a = self.base[a];
+            seen[a] = true;
+            if self.mate[a] == NONE {
+                break;
+            }
+            a = self.parent[self.mate[a]];
+        }
+        loop {
+            b = self.base[b];
+            if seen[b] {
+                return b;
+            }
+            b = self.parent[self.mate[b]];
+        }
+    }
+
+    fn mark_path(&mut self, mut v: usize, b: usize, mut child: usize) {
+        while self.base[v] != b {
+            self.blossom[self.base[v]] = true;
+            self.blossom[self.base[self.mate[v]]] = true;
+            self.parent[v] = child;
+            child = self.mate[v];
+            v = self.parent[self.mate[v]];
+        }
+    }
+
+    fn find_path(&mut self, root: usize) -> Option<usize> {
+        let n = self.adj.len();
+        self.used = vec![false; n];
+        self.parent = vec![NONE; n];
+        self.base = (0..n).collect();
+        self.used[root] = true;
+        let mut queue = VecDeque::from([root]);
+        while let Some(v) = queue.pop_front() {
+            for &to in self.adj[v].iter() {
+                if self.base[v] == self.base[to] || self.mate[v] == to {
+                    continue;
+                }
+                if to == root || (self.mate[to] != NONE && self.parent[self.mate[to]] != NONE) {
+                    let cur = self.lca(v, to);
+                    self.blossom = vec![false; n];
+                    self.mark_path(v, cur, to);
+                    self.mark_path(to, cur, v);
+                    for i in 0..n {
+                        if self.blossom[self.base[i]] {
+                            self.base[i] = cur;
+                            if !self.used[i] {
+                                self.used[i] = true;
+                                queue.push_back(i);
+                            }
+                        }
+                    }
+                } else if self.parent[to] == NONE {
+                    self.parent[to] = v;
+                    if self.mate[to] == NONE {
+                        return Some(to);
+                    }
+                    self.used[self.mate[to]] = true;
+                    queue.push_back(self.mate[to]);
+                }
+            }
+        }
+        None
+    }
+}
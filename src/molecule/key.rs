@@ -0,0 +1,168 @@
+//! Fixed-length canonical keys for registering and deduplicating molecules.
+//!
+//! A [`MolKey`] is two 64 bit hashes of canonical certificates, the first of
+//! the connectivity layer (elements, hydrogen counts, aromaticity and bonds)
+//! and the second of that plus the charge, isotope and stereo layers asked
+//! for. The second is zero when those layers add nothing, so `CCO` and
+//! `[13CH3]CO` share the first half and only differ in the second.
+//! Hydrogens written as atoms, as in `[H]OCC`, are atoms of the key like any
+//! other.
+//!
+//! Keys are stable: the same molecule gives the same key across crate
+//! versions and platforms, and a release that changes any key is a breaking
+//! one.
+
+use super::{
+    canon::{canonical_labelling, Labels},
+    Molecule,
+};
+use crate::error::{Result, RuatomError};
+use std::{fmt, str::FromStr};
+
+/// Canonical key of a molecule, written as two blocks of 16 hexadecimal
+/// digits, `connectivity-layers`.
+///
+/// ```
+/// use ruatom::Parser;
+///
+/// let a = Parser::new("OCC").parse().unwrap();
+/// let b = Parser::new("C(O)C").parse().unwrap();
+/// assert_eq!(a.key().unwrap(), b.key().unwrap());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MolKey {
+    connectivity: u64,
+    layers: u64,
+}
+
+impl MolKey {
+    pub fn connectivity(&self) -> u64 {
+        self.connectivity
+    }
+
+    /// Zero when the molecule has no charge, isotope or stereo layer.
+    pub fn layers(&self) -> u64 {
+        self.layers
+    }
+}
+
+impl fmt::Display for MolKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016X}-{:016X}", self.connectivity, self.layers)
+    }
+}
+
+impl FromStr for MolKey {
+    type Err = RuatomError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let block = |b: &str| match b.len() == 16 && b.bytes().all(|c| c.is_ascii_hexdigit()) {
+            true => u64::from_str_radix(b, 16).ok(),
+            false => None,
+        };
+        match s.split_once('-') {
+            Some((c, l)) => match (block(c), block(l)) {
+                (Some(connectivity), Some(layers)) => Ok(MolKey {
+                    connectivity,
+                    layers,
+                }),
+                _ => Err(RuatomError::IllegalMolKey(s.to_string())),
+            },
+            None => Err(RuatomError::IllegalMolKey(s.to_string())),
+        }
+    }
+}
+
+/// Computes [`MolKey`]s, the default matches [`Molecule::key`] and keeps
+/// every layer.
+///
+/// ```
+/// use ruatom::molecule::MolKeyBuilder;
+/// use ruatom::Parser;
+///
+/// let cis = Parser::new("C/C=C\\C").parse().unwrap();
+/// let trans = Parser::new("C/C=C/C").parse().unwrap();
+/// let flat = MolKeyBuilder::new().stereo(false);
+/// assert_eq!(flat.build(&cis).unwrap(), flat.build(&trans).unwrap());
+/// assert_ne!(cis.key().unwrap(), trans.key().unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MolKeyBuilder {
+    charges: bool,
+    isotopes: bool,
+    stereo: bool,
+}
+
+impl Default for MolKeyBuilder {
+    fn default() -> Self {
+        Self {
+            charges: true,
+            isotopes: true,
+            stereo: true,
+        }
+    }
+}
+
+impl MolKeyBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn charges(mut self, on: bool) -> Self {
+        self.charges = on;
+        self
+    }
+
+    pub fn isotopes(mut self, on: bool) -> Self {
+        self.isotopes = on;
+        self
+    }
+
+    /// Tetrahedral and double bond configurations.
+    pub fn stereo(mut self, on: bool) -> Self {
+        self.stereo = on;
+        self
+    }
+
+    /// Aromaticity is that of [`Huckel`](super::Huckel), so Kekulé and aromatic input get
+    /// the same key whatever model `mol` was read with.
+    pub fn build(&self, mol: &Molecule) -> Result<MolKey> {
+        let skeleton = Labels {
+            charge: false,
+            isotope: false,
+            atom_class: false,
+            stereo: false,
+            tokens: false,
+        };
+        let (_, connectivity) = canonical_labelling(mol, skeleton)?;
+        let labels = Labels {
+            charge: self.charges,
+            isotope: self.isotopes,
+            stereo: self.stereo,
+            ..skeleton
+        };
+        let (_, layers) = canonical_labelling(mol, labels)?;
+        Ok(MolKey {
+            connectivity: hash(&connectivity),
+            layers: match layers == connectivity {
+                true => 0,
+                false => hash(&layers),
+            },
+        })
+    }
+}
+
+/// FNV-1a over the little endian bytes with a final avalanche, fixed here
+/// rather than taken from `std` whose hashers may change between releases.
+pub(crate) fn hash(cert: &[i64]) -> u64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in cert.iter().flat_map(|x| x.to_le_bytes()) {
+        h ^= b as u64;
+        h = h.wrapping_mul(0x0100_0000_01b3);
+    }
+    h ^= h >> 30;
+    h = h.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h ^= h >> 27;
+    h = h.wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^ (h >> 31)
+}
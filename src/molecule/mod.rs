@@ -1,23 +1,40 @@
 #[macro_use]
 pub mod element;
+pub mod aromaticity;
 pub mod atom;
 pub mod bond;
-mod canon;
+pub(crate) mod canon;
 pub mod configuration;
+pub mod formula;
+mod kekule;
+pub mod key;
+#[allow(clippy::module_inception)]
 pub mod molecule;
+mod rings;
+pub mod substructure;
 pub mod topology;
 pub mod transform;
+pub mod writer;
 
 use std::borrow::Borrow;
 use std::borrow::Cow;
 
+pub use aromaticity::{Aromatic, AromaticityModel, Basic, Huckel, Mdl};
 pub(crate) use atom::Atom;
 pub(crate) use bond::RingBond;
 pub use configuration::*;
 pub(crate) use element::H;
+pub use formula::FormulaBuilder;
+pub use key::{MolKey, MolKeyBuilder};
 pub use molecule::Molecule;
-pub use topology::{create, Topology, TopologySeq};
-pub use transform::{collapse, expand};
+pub use rings::{Ring, RingSystem, RingSystemKind};
+pub use substructure::SubstructureMatcher;
+pub use topology::{create, StereoGroup, Topology, TopologySeq};
+pub use transform::{collapse, expand, kekulize};
+pub use writer::{Hydrogens, SmilesWriter};
+
+/// Index of an atom in a [`Molecule`], starting from 1.
+pub type AtomIdx = crate::graph::VertexIdx;
 
 pub const HAS_AROM: u8 = 0x1;
 pub const HAS_EXT_STRO: u8 = 0x4;
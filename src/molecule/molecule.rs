@@ -1,96 +1,47 @@
 use super::{
+    aromaticity::{AromaticityModel, Basic},
     atom::AtomKind,
-    bond::{Bond, IMPLICT},
+    bond::{Bond, DOUBLE, IMPLICT},
     canon::{is_unique_array, prime, rank, rank_matrix},
     element::{valid_element_symbol, Specification},
-    leftpad_with,
-    topology::Topology,
-    Atom, RingBond,
+    kekule::{kekule_bonds, sorted_pair},
+    leftpad_with, rings,
+    topology::{StereoGroup, Topology, TopologySeq},
+    Atom, AtomIdx, FormulaBuilder, MolKey, MolKeyBuilder, Ring, RingBond, RingSystem, SmilesWriter,
+    SubstructureMatcher,
 };
 use super::{configuration::*, H};
 use crate::error::{Result, RuatomError};
 use crate::graph::{Edge, Graph};
-use phf::phf_set;
 use primitive_types::U256;
 use rayon::prelude::*;
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::num::ParseIntError;
-use std::sync::mpsc::channel;
-
-static RING_SIZE: phf::Set<u8> = phf_set! {
-    5u8,
-    6u8,
-    7u8
-};
-
-struct DataBus {
-    pub ancestors: Vec<u8>,
-    pub visited: Vec<u8>,
-    pub opening_closures: HashMap<u8, Vec<u8>>,
-    pub closing_closures: HashMap<u8, Vec<(u8, u8)>>,
-    pub dh: Vec<u8>,
-}
-
-impl DataBus {
-    fn new() -> Self {
-        Self {
-            ancestors: vec![],
-            visited: vec![],
-            opening_closures: HashMap::<u8, Vec<u8>>::new(),
-            closing_closures: HashMap::<u8, Vec<(u8, u8)>>::new(),
-            dh: Vec::new(),
-        }
-    }
-
-    fn update_open<F>(&mut self, at: &u8, f: &mut F)
-    where
-        F: FnMut(u8),
-    {
-        if let Some(ocs) = self.opening_closures.get(at) {
-            for oc in ocs.iter() {
-                let mut digit: u8 = 1;
-                while digit < 100 {
-                    if !self.dh.contains(&digit) {
-                        break;
-                    }
-                    digit += 1;
-                }
-                self.dh.push(digit);
-                f(digit);
-                let oadts = self.closing_closures.entry(*oc).or_insert(vec![]);
-                oadts.push((*at, digit));
-            }
-        }
-    }
-
-    fn sort_close_and_delete<F>(&mut self, at: &u8, f: &mut F) -> Result<()>
-    where
-        F: FnMut(u8, u8) -> Result<()>,
-    {
-        if let Some(oadts) = self.closing_closures.get_mut(at) {
-            oadts.sort_by_key(|oadt| oadt.1);
-            for oadt in oadts.iter() {
-                f(oadt.0, oadt.1)?;
-                let index = self.dh.iter().position(|x| *x == oadt.1).unwrap();
-                self.dh.remove(index);
-            }
-        }
-        Ok(())
-    }
-}
+use std::sync::{mpsc::channel, Arc};
 
 pub struct Molecule {
     graph: Graph<Atom, Bond>,
-    atoms: Vec<u8>,
+    atoms: Vec<AtomIdx>,
     ring_num: u8,
     ring_bonds: HashMap<u8, RingBond>,
     flag: u8,
-    valences: HashMap<u8, u8>,
-    topologies: HashMap<u8, Box<dyn Topology + Sync>>,
+    valences: HashMap<AtomIdx, u8>,
+    topologies: HashMap<AtomIdx, Box<dyn Topology + Send + Sync>>,
     n_ssr: u16,
-    bonds: Vec<[u8; 2]>,
-    chiralatoms_count: u8,
-    ring_atoms_pair: HashSet<[u8; 2]>,
+    rings: Vec<Ring>,
+    relevant: Vec<Ring>,
+    bonds: Vec<[AtomIdx; 2]>,
+    chiralatoms_count: u32,
+    ring_atoms_pair: HashSet<[AtomIdx; 2]>,
+    coordinates: HashMap<AtomIdx, [f64; 3]>,
+    opaque_blocks: Vec<(String, Vec<String>)>,
+    aromaticity: Arc<dyn AromaticityModel>,
+}
+
+impl Default for Molecule {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Molecule {
@@ -104,38 +55,66 @@ impl Molecule {
             valences: HashMap::new(),
             topologies: HashMap::new(),
             n_ssr: 0,
+            rings: Vec::new(),
+            relevant: Vec::new(),
             bonds: Vec::new(),
             chiralatoms_count: 0,
             ring_atoms_pair: HashSet::new(),
+            coordinates: HashMap::new(),
+            opaque_blocks: Vec::new(),
+            aromaticity: Arc::new(Basic),
         }
     }
 
-    pub fn atoms(&self) -> &Vec<u8> {
+    pub fn atoms(&self) -> &Vec<AtomIdx> {
         &self.atoms
     }
 
+    /// Bonds in the order they were added.
+    pub fn bonds(&self) -> &Vec<[AtomIdx; 2]> {
+        &self.bonds
+    }
+
+    /// Position of the atom at `loc`, if the molecule came with coordinates.
+    pub fn coordinates(&self, loc: &AtomIdx) -> Option<[f64; 3]> {
+        self.coordinates.get(loc).copied()
+    }
+
+    pub fn set_coordinates(&mut self, loc: AtomIdx, xyz: [f64; 3]) {
+        self.coordinates.insert(loc, xyz);
+    }
+
+    /// Connection table blocks read along with the molecule but not
+    /// interpreted, such as V3000 SGroups, as `(name, lines)`.
+    ///
+    /// They refer to atoms by index, so removing atoms or bonds drops them.
+    pub fn opaque_blocks(&self) -> &[(String, Vec<String>)] {
+        &self.opaque_blocks
+    }
+
+    pub fn add_opaque_block(&mut self, name: &str, lines: Vec<String>) {
+        self.opaque_blocks.push((name.to_string(), lines));
+    }
+
     pub(crate) fn graph(&self) -> &Graph<Atom, Bond> {
         &self.graph
     }
 
-    pub fn add_atom(&mut self, atom: Atom) -> Result<u8> {
-        let index = self.atoms.len() as u8 + 1;
+    pub fn add_atom(&mut self, atom: Atom) -> Result<AtomIdx> {
+        let index = AtomIdx::try_from(self.atoms.len() + 1)
+            .map_err(|_| RuatomError::IllegalMolecule("too many atoms"))?;
         self.graph.add_vertex(index, atom)?;
         self.valences.insert(index, 0);
         self.atoms.push(index);
         Ok(index)
     }
 
-    pub fn add_bond(&mut self, u: u8, v: u8, bond: Bond) -> Result<bool> {
-        let ok: bool;
-        if bond.direction() {
-            ok = self
-                .graph
-                .add_direction_edge(u, v, bond, bond.inverse())
-                .and_then(|ok| Ok(ok))?;
+    pub fn add_bond(&mut self, u: AtomIdx, v: AtomIdx, bond: Bond) -> Result<bool> {
+        let ok = if bond.direction() {
+            self.graph.add_direction_edge(u, v, bond, bond.inverse())?
         } else {
-            ok = self.graph.add_edge(u, v, bond).and_then(|ok| Ok(ok))?;
-        }
+            self.graph.add_edge(u, v, bond)?
+        };
         let eu = self.valences.entry(u).or_insert(0);
         *eu += bond.electron();
         let ev = self.valences.entry(v).or_insert(0);
@@ -146,7 +125,155 @@ impl Molecule {
         Ok(ok)
     }
 
-    pub fn symbol(&self, loc: &u8) -> Result<String> {
+    /// Removes the atom at `loc` together with its bonds.
+    ///
+    /// Atoms above `loc` move down by one, the returned map gives the new
+    /// index of every remaining atom keyed by its old one. Stereo
+    /// configurations centred on `loc` or expressed against it are dropped.
+    pub fn remove_atom(&mut self, loc: &AtomIdx) -> Result<HashMap<AtomIdx, AtomIdx>> {
+        let k = *loc;
+        let n = self.atoms.len() as AtomIdx;
+        let nei: Vec<AtomIdx> = self.graph.neighbors(loc)?.copied().collect();
+        for n in nei {
+            let bond = *self.edge_at(k, n)?;
+            self.unbind(k, n, &bond)?;
+        }
+        self.graph.remove_vertex(k)?;
+        self.atoms.pop();
+
+        let shift = |v: AtomIdx| if v > k { v - 1 } else { v };
+        let mapping: HashMap<AtomIdx, AtomIdx> =
+            (1..=n).filter(|v| *v != k).map(|v| (v, shift(v))).collect();
+
+        self.valences = self
+            .valences
+            .drain()
+            .filter(|(v, _)| *v != k)
+            .map(|(v, e)| (shift(v), e))
+            .collect();
+        self.coordinates = self
+            .coordinates
+            .drain()
+            .filter(|(v, _)| *v != k)
+            .map(|(v, xyz)| (shift(v), xyz))
+            .collect();
+        self.opaque_blocks.clear();
+        self.bonds.retain(|b| !b.contains(&k));
+        self.bonds
+            .iter_mut()
+            .flat_map(|b| b.iter_mut())
+            .for_each(|v| *v = shift(*v));
+        self.ring_atoms_pair = self
+            .ring_atoms_pair
+            .drain()
+            .filter(|b| !b.contains(&k))
+            .map(|[u, v]| [shift(u), shift(v)])
+            .collect();
+        self.topologies = self
+            .topologies
+            .drain()
+            .filter(|(u, t)| *u != k && !t.carriers().contains(&(k as i64)))
+            .map(|(u, t)| {
+                let vs = t
+                    .carriers()
+                    .iter()
+                    .map(|v| if *v > k as i64 { v - 1 } else { *v })
+                    .collect();
+                (shift(u), t.relabel(shift(u), vs))
+            })
+            .collect();
+        self.refresh()?;
+        Ok(mapping)
+    }
+
+    /// Removes the bond between `u` and `v` and returns it as seen from `u`.
+    ///
+    /// Stereo configurations that depend on the bond are dropped.
+    pub fn remove_bond(&mut self, u: AtomIdx, v: AtomIdx) -> Result<Bond> {
+        let bond = self.graph.remove_edge(u, v)?;
+        self.unbind(u, v, &bond)?;
+        self.bonds.retain(|b| b != &[u, v] && b != &[v, u]);
+        self.ring_atoms_pair.remove(&[u, v]);
+        self.ring_atoms_pair.remove(&[v, u]);
+        self.opaque_blocks.clear();
+        self.topologies.retain(|c, t| {
+            let vs = t.carriers();
+            let extended = t.seq() == TopologySeq::ExtendedTetrahedral;
+            !(*c == u && vs.contains(&(v as i64))
+                || *c == v && vs.contains(&(u as i64))
+                || extended && (vs.contains(&(u as i64)) || vs.contains(&(v as i64))))
+        });
+        self.refresh()?;
+        Ok(bond)
+    }
+
+    /// Replaces the bond between `u` and `v` by `bond` and returns the old one
+    /// as seen from `u`.
+    ///
+    /// Stereo configurations centred on either end are dropped.
+    pub fn change_bond_order(&mut self, u: AtomIdx, v: AtomIdx, bond: Bond) -> Result<Bond> {
+        if bond.electron() == 0 {
+            return Err(RuatomError::InvalidEdge(u, v));
+        }
+        let old = self.rebond(u, v, bond)?;
+        self.topologies.remove(&u);
+        self.topologies.remove(&v);
+        self.refresh()?;
+        Ok(old)
+    }
+
+    /// Swaps the bond between `u` and `v` for `bond`, keeping its ring
+    /// information and the valences of its ends right.
+    fn rebond(&mut self, u: AtomIdx, v: AtomIdx, mut bond: Bond) -> Result<Bond> {
+        let old = *self.edge_at(u, v)?;
+        self.unbind(u, v, &old)?;
+        bond.set_ring_membership(old.ring_membership());
+        bond.set_ring_size(old.ring_size());
+        *self.edge_mut(u, v)? = bond;
+        *self.edge_mut(v, u)? = bond.inverse();
+        for w in [u, v] {
+            self.valences.entry(w).and_modify(|e| *e += bond.electron());
+            self.graph.vertex_mut(&w)?.incr_degree(bond.electron());
+        }
+        Ok(old)
+    }
+
+    /// Takes the electrons of `bond` off the valences and degrees of its ends.
+    fn unbind(&mut self, u: AtomIdx, v: AtomIdx, bond: &Bond) -> Result<()> {
+        for w in [u, v] {
+            self.valences.entry(w).and_modify(|e| *e -= bond.electron());
+            self.graph.vertex_mut(&w)?.decr_degree(bond.electron());
+        }
+        Ok(())
+    }
+
+    /// Re-runs ring, aromaticity, symmetry and stereocenter perception after
+    /// the molecule has been edited.
+    pub(crate) fn refresh(&mut self) -> Result<()> {
+        for atom in self.atoms.clone() {
+            self.atom_mut(&atom)?.reset_perception();
+        }
+        for [u, v] in self.bonds.clone() {
+            for (a, b) in [(u, v), (v, u)] {
+                let bond = self.edge_mut(a, b)?;
+                bond.set_ring_membership(0);
+                bond.set_ring_size(0);
+            }
+        }
+        self.n_ssr = 0;
+        self.rings.clear();
+        self.relevant.clear();
+        self.chiralatoms_count = 0;
+        if self.atoms.is_empty() {
+            return Ok(());
+        }
+        self.rings_detection()?;
+        self.aromaticity_detection()?;
+        self.symmetry_detection()?;
+        self.stereocenter_detection()
+    }
+
+    pub fn symbol(&self, loc: &AtomIdx) -> Result<String> {
         let at = self.atom_at(loc)?;
         if at.is_aromatic() {
             Ok(at.element().symbol().to_lowercase())
@@ -155,7 +282,7 @@ impl Molecule {
         }
     }
 
-    pub fn open_ring(&mut self, rloc: u8, bond: Bond, pos: Option<u8>, u: u8) {
+    pub fn open_ring(&mut self, rloc: u8, bond: Bond, pos: Option<usize>, u: AtomIdx) {
         if self.ring_bonds.contains_key(&rloc) {
             panic!()
         }
@@ -164,7 +291,7 @@ impl Molecule {
         self.ring_num += 1;
     }
 
-    pub fn close_ring(&mut self, rloc: u8, u: u8, sbond: Bond) -> Result<u8> {
+    pub fn close_ring(&mut self, rloc: u8, u: AtomIdx, sbond: Bond) -> Result<AtomIdx> {
         let rb = self.ring_bonds.remove(&rloc);
         if let Some(rb) = rb {
             if self.graph.adjancent(rb.vertex(), u) {
@@ -173,12 +300,9 @@ impl Molecule {
             let bond = self.decide_bond(sbond.inverse(), rb.bond())?;
             if bond.direction() {
                 self.graph
-                    .add_direction_edge(rb.vertex(), u, bond, bond.inverse())
-                    .and_then(|ok| Ok(ok))?;
+                    .add_direction_edge(rb.vertex(), u, bond, bond.inverse())?;
             } else {
-                self.graph
-                    .add_edge(rb.vertex(), u, bond)
-                    .and_then(|ok| Ok(ok))?;
+                self.graph.add_edge(rb.vertex(), u, bond)?;
             }
             self.valences
                 .entry(rb.vertex())
@@ -194,13 +318,13 @@ impl Molecule {
             self.n_ssr += 1;
             return Ok(rb.vertex());
         }
-        return Err(RuatomError::InvalidRingBond);
+        Err(RuatomError::InvalidRingBond)
     }
     pub fn ring_num(&self) -> u8 {
         self.ring_num
     }
 
-    fn degree(&self, u: &u8) -> Result<u8> {
+    fn degree(&self, u: &AtomIdx) -> Result<u8> {
         let deg = self.graph.bound_count(u)?;
         Ok(deg as u8)
     }
@@ -220,7 +344,7 @@ impl Molecule {
         if b.inverse() != a {
             return Err(RuatomError::IllegalMolecule("ring bond not match"));
         };
-        return Ok(IMPLICT);
+        Ok(IMPLICT)
     }
 
     pub fn set_flags(&mut self, flag: u8) {
@@ -230,34 +354,33 @@ impl Molecule {
         self.flag & mask
     }
 
-    pub fn add_topology(&mut self, t: Box<dyn Topology + Sync>) {
+    pub fn add_topology(&mut self, t: Box<dyn Topology + Send + Sync>) {
         let atom_idx = t.atom();
         if atom_idx != -1 {
-            self.topologies.insert(atom_idx as u8, t);
+            self.topologies.insert(atom_idx as AtomIdx, t);
         }
     }
 
-    pub fn valence(&self, loc: &u8) -> Result<u8> {
-        let atom = self.graph.vertex(&loc)?;
+    pub fn valence(&self, loc: &AtomIdx) -> Result<u8> {
+        let atom = self.graph.vertex(loc)?;
         let init_count = match atom.kind() {
             AtomKind::Bracket(_) => atom.explicit_hydrogens(),
             _ => 0,
         };
-        let valence: u8;
-        if self.valences.len() < 1 {
-            valence = init_count + 0;
+        let valence = if self.valences.is_empty() {
+            init_count
         } else {
-            valence = init_count + self.bond_valences(loc)?;
-        }
-        if atom.is_aromatic() && self.degree(&loc)? == self.bond_valences(loc)? {
+            init_count + self.bond_valences(loc)?
+        };
+        if atom.is_aromatic() && self.degree(loc)? == self.bond_valences(loc)? {
             return Ok(valence + 1);
         }
-        return Ok(valence);
+        Ok(valence)
     }
 
-    pub fn hydrogen_count(&self, loc: &u8) -> Result<u8> {
-        let atom = self.graph.vertex(&loc)?;
-        return Ok(atom.implict_hydrogen_amount(self.valence(loc)?) + atom.explicit_hydrogens());
+    pub fn hydrogen_count(&self, loc: &AtomIdx) -> Result<u8> {
+        let atom = self.graph.vertex(loc)?;
+        Ok(atom.implict_hydrogen_amount(self.valence(loc)?) + atom.explicit_hydrogens())
     }
 
     pub fn trans_astrix_atom(&mut self) -> Result<()> {
@@ -295,88 +418,77 @@ impl Molecule {
         Ok(())
     }
 
-    fn bond_valences(&self, u: &u8) -> Result<u8> {
+    fn bond_valences(&self, u: &AtomIdx) -> Result<u8> {
         let v = self.valences.get(u).ok_or(RuatomError::NoSuchVertex(*u))?;
         Ok(*v)
     }
 
-    fn update_atom(&mut self, loc: u8, atom: Atom) {
+    fn update_atom(&mut self, loc: AtomIdx, atom: Atom) {
         self.graph.update_vertex(loc, atom);
     }
 
-    fn find_double_bond(&self, u: u8, v: u8) -> Result<i8> {
+    fn find_double_bond(&self, u: AtomIdx, v: AtomIdx) -> Result<i64> {
         let mut another = -1;
-        let neighbors = self.graph.neighbors(&u);
-        if neighbors.is_err() {
-            return Err(RuatomError::NoSuchVertex(u));
-        }
-        if neighbors.is_ok() {
-            for atom in neighbors.unwrap() {
-                let other = *atom;
-                let bond = self.graph.edge_with_vertex(other, u)?;
-                if bond.is("=") && other != v {
-                    another = other as i8;
-                    break;
-                }
+        let neighbors = self
+            .graph
+            .neighbors(&u)
+            .map_err(|_| RuatomError::NoSuchVertex(u))?;
+        for atom in neighbors {
+            let other = *atom;
+            let bond = self.graph.edge_with_vertex(other, u)?;
+            if bond.is("=") && other != v {
+                another = other as i64;
+                break;
             }
         }
-        return Ok(another);
+        Ok(another)
     }
 
-    pub fn find_extend_tetrahedral_ends(&self, u: u8) -> Result<Vec<u8>> {
+    pub fn find_extend_tetrahedral_ends(&self, u: AtomIdx) -> Result<Vec<AtomIdx>> {
         if self.degree(&u)? < 2 {
             return Err(RuatomError::IllegalMolecule("invalid atom num"));
         }
         let mut nei = self.graph.neighbors(&u)?;
         let mut pre_e1 = u;
         let mut pre_e2 = u;
-        let mut e1 = *(nei.next().unwrap()) as i8;
-        let mut e2 = *(nei.next().unwrap()) as i8;
-        let mut tmp: i8;
+        let mut e1 = *(nei.next().unwrap()) as i64;
+        let mut e2 = *(nei.next().unwrap()) as i64;
+        let mut tmp: i64;
         while e1 >= 0 && e2 >= 0 {
-            tmp = self.find_double_bond(e1 as u8, pre_e1)?;
-            pre_e1 = e1 as u8;
+            tmp = self.find_double_bond(e1 as AtomIdx, pre_e1)?;
+            pre_e1 = e1 as AtomIdx;
             e1 = tmp;
-            tmp = self.find_double_bond(e2 as u8, pre_e2)?;
-            pre_e2 = e2 as u8;
+            tmp = self.find_double_bond(e2 as AtomIdx, pre_e2)?;
+            pre_e2 = e2 as AtomIdx;
             e2 = tmp;
         }
         Ok(vec![pre_e1, pre_e2])
     }
 
-    pub fn edge_at(&self, u: u8, v: u8) -> Result<&Bond> {
+    pub fn edge_at(&self, u: AtomIdx, v: AtomIdx) -> Result<&Bond> {
         let b = self.graph.edge_with_vertex(u, v)?;
-        return Ok(b);
+        Ok(b)
     }
 
-    pub(crate) fn edge_mut(&mut self, u: u8, v: u8) -> Result<&mut Bond> {
+    pub(crate) fn edge_mut(&mut self, u: AtomIdx, v: AtomIdx) -> Result<&mut Bond> {
         let e = Edge::new(u, v);
         let b = self.graph.edge_mut(&e)?;
-        return Ok(b);
+        Ok(b)
     }
 
-    pub fn atom_at(&self, u: &u8) -> Result<&Atom> {
+    pub fn atom_at(&self, u: &AtomIdx) -> Result<&Atom> {
         let a = self.graph.vertex(u)?;
         Ok(a)
     }
 
-    pub fn atom_mut(&mut self, loc: &u8) -> Result<&mut Atom> {
-        self.graph.vertex_mut(&loc)
-    }
-
-    fn to_aliphatic(&mut self, loc: &u8) -> Result<()> {
-        let al = self.atom_at(loc)?.to_aliphatic();
-        if let Some(al) = al {
-            let v = self.graph.vertex_mut(&loc)?;
-            *v = al;
-        }
-        return Ok(());
+    pub fn atom_mut(&mut self, loc: &AtomIdx) -> Result<&mut Atom> {
+        self.graph.vertex_mut(loc)
     }
 
     pub fn to_explict_configuration(
         // add unit test
         &self,
-        atom: u8,
+        atom: AtomIdx,
         conf: &Configuration,
     ) -> Result<Configuration> {
         if !conf.is_implict() {
@@ -411,7 +523,7 @@ impl Molecule {
                 if conf.is_anti_clockwise() {
                     return Ok(TH1);
                 }
-                return Ok(TH2);
+                Ok(TH2)
             }
             3 => {
                 let am = self.graph.vertex(&atom)?;
@@ -436,16 +548,15 @@ impl Molecule {
                             }
                         }
                     }
-                    "P" | "N" => {
+                    "P" | "N"
                         if self.valences.get(&atom) == Some(&3)
                             && am.charge() == 0
-                            && self.hydrogen_count(&atom)? == 0
-                        {
-                            if conf.is_anti_clockwise() {
-                                return Ok(TH1);
-                            }
-                            return Ok(TH2);
+                            && self.hydrogen_count(&atom)? == 0 =>
+                    {
+                        if conf.is_anti_clockwise() {
+                            return Ok(TH1);
                         }
+                        return Ok(TH2);
                     }
                     _ => {}
                 }
@@ -460,55 +571,61 @@ impl Molecule {
                         if conf.is_anti_clockwise() {
                             return Ok(DB1);
                         }
-                        return Ok(DB2);
-                    }
-                    _ => {
-                        return Ok(UNKNOWN);
+                        Ok(DB2)
                     }
+                    _ => Ok(UNKNOWN),
                 }
             }
             5 => {
                 if conf.is_anti_clockwise() {
                     return Ok(TB1);
                 }
-                return Ok(TB2);
+                Ok(TB2)
             }
             6 => {
                 if conf.is_anti_clockwise() {
                     return Ok(OH1);
                 }
-                return Ok(OH2);
-            }
-            _ => {
-                return Ok(UNKNOWN);
+                Ok(OH2)
             }
+            _ => Ok(UNKNOWN),
         }
     }
 
-    pub fn topology_at(&self, loc: &u8) -> Option<&Box<dyn Topology + Sync>> {
-        self.topologies.get(loc)
+    pub fn topology_at(&self, loc: &AtomIdx) -> Option<&(dyn Topology + Send + Sync)> {
+        self.topologies.get(loc).map(|t| t.as_ref())
     }
 
-    pub fn validate_up_down(&self, directional_bonds: HashMap<u8, bool>) -> Result<()> {
+    /// Puts the configuration at `loc` into an enhanced stereo group.
+    pub fn set_stereo_group(&mut self, loc: &AtomIdx, group: StereoGroup) -> Result<()> {
+        let t = self
+            .topologies
+            .get_mut(loc)
+            .ok_or(RuatomError::IllegalMolecule("no configuration at atom"))?;
+        t.set_group(group);
+        Ok(())
+    }
+
+    pub fn validate_up_down(&self, directional_bonds: HashMap<AtomIdx, bool>) -> Result<()> {
         for v in directional_bonds.keys() {
             let mut n_up_v = 0;
             let mut n_down_v = 0;
             let mut n_up_w = 0;
             let mut n_down_w = 0;
-            let mut w: i8 = -1;
+            let mut w: i64 = -1;
             self.graph.map_edge(v, |bond, v| {
                 if bond.is("/") {
                     n_up_v += 1;
                 } else if bond.is("\\") {
                     n_down_v += 1;
                 } else if bond.is("=") {
-                    w = *v as i8;
+                    w = *v as i64;
                 }
             })?;
             if w < 0 {
                 continue;
             }
-            self.graph.map_edge(&(w as u8), |bond, _| {
+            self.graph.map_edge(&(w as AtomIdx), |bond, _| {
                 if bond.is("/") {
                     n_up_w += 1;
                 } else if bond.is("\\") {
@@ -524,24 +641,24 @@ impl Molecule {
                 ));
             }
         }
-        return Ok(());
+        Ok(())
     }
 
     #[inline]
     pub fn order(&self) -> usize {
-        return self.graph.order();
+        self.graph.order()
     }
 
     #[inline]
     pub fn size(&self) -> usize {
-        return self.graph.size();
+        self.graph.size()
     }
 
     pub fn map_bonds<Func>(&self, f: Func) -> Result<()>
     where
         Func: FnMut(&Edge, &Bond),
     {
-        self.graph.map_edges(f).map_err(|e| e)
+        self.graph.map_edges(f)
     }
 
     pub fn molecule_weight(&self) -> Result<f64> {
@@ -572,6 +689,17 @@ impl Molecule {
         Ok(res)
     }
 
+    /// Hill formula with the net charge, see [`FormulaBuilder`] for
+    /// isotopes.
+    pub fn formula(&self) -> Result<String> {
+        FormulaBuilder::default().build(self)
+    }
+
+    /// Hill formula of each dot-disconnected fragment.
+    pub fn fragment_formulas(&self) -> Result<Vec<String>> {
+        FormulaBuilder::default().fragments(self)
+    }
+
     pub fn heavy_atom_amount(&self, symbol: &str) -> Result<u16> {
         let mut amount = 0;
         if !valid_element_symbol(symbol) {
@@ -582,37 +710,70 @@ impl Molecule {
                 amount += 1;
             }
         }
-        return Ok(amount);
+        Ok(amount)
     }
 
     pub fn n_ssr(&self) -> u16 {
-        return self.n_ssr;
+        self.n_ssr
+    }
+
+    /// Smallest set of smallest rings, shortest first.
+    pub fn rings(&self) -> &[Ring] {
+        &self.rings
+    }
+
+    /// Relevant cycles, the union of all SSSRs, shortest first.
+    pub fn relevant_cycles(&self) -> &[Ring] {
+        &self.relevant
+    }
+
+    /// SSSR rings through `loc`.
+    pub fn atom_rings(&self, loc: &AtomIdx) -> Vec<&Ring> {
+        self.rings.iter().filter(|r| r.contains(loc)).collect()
+    }
+
+    /// Whether `loc` is in a relevant cycle of `size` atoms.
+    pub fn is_in_ring_of_size(&self, loc: &AtomIdx, size: usize) -> bool {
+        self.relevant
+            .iter()
+            .any(|r| r.len() == size && r.contains(loc))
+    }
+
+    /// SSSR rings grouped by shared atoms, see [`RingSystem`].
+    pub fn ring_systems(&self) -> Result<Vec<RingSystem>> {
+        rings::ring_systems(self)
+    }
+
+    /// Whether `query` is a substructure, see [`SubstructureMatcher`] for
+    /// other matching options.
+    pub fn has_substructure(&self, query: &Molecule) -> Result<bool> {
+        SubstructureMatcher::new(query).is_match(self)
     }
 
-    pub fn total_hs(&self, isotope: bool) -> Result<u8> {
+    pub fn total_hs(&self, isotope: bool) -> Result<u32> {
         let mut hs = 0;
         for i in self.atoms.iter() {
             let at = self.atom_at(i)?;
             if at.is("H") {
-                if isotope || !isotope && at.isotope() < 0 {
+                if isotope || at.isotope() < 0 {
                     hs += 1;
                 } else {
                     continue;
                 }
             } else {
-                hs += self.hydrogen_count(i)?;
+                hs += self.hydrogen_count(i)? as u32;
             }
         }
         Ok(hs)
     }
 
     #[inline]
-    fn connectivity(&self, loc: &u8) -> Result<u8> {
-        let con = self.bond_degree_of(&loc)? + self.hydrogen_count(loc)?;
+    fn connectivity(&self, loc: &AtomIdx) -> Result<u8> {
+        let con = self.bond_degree_of(loc)? + self.hydrogen_count(loc)?;
         Ok(con)
     }
 
-    pub(crate) fn ring_size_of(&self, a: u8, b: u8) -> Result<u8> {
+    pub(crate) fn ring_size_of(&self, a: AtomIdx, b: AtomIdx) -> Result<u32> {
         let mut distance = 1;
         let mut visited: Vec<usize> = vec![0; self.atoms.len() + 1];
         let mut queue = vec![a, 0];
@@ -647,13 +808,13 @@ impl Molecule {
         if one == b {
             return Ok(distance);
         }
-        return Ok(0);
+        Ok(0)
     }
 
     pub fn rings_detection(&mut self) -> Result<()> {
         let atoms = self.atoms.clone();
         for atom in atoms.iter() {
-            if self.bond_degree_of(&atom)? >= 2 {
+            if self.bond_degree_of(atom)? >= 2 {
                 self.atom_mut(atom).unwrap().set_membership(1);
             }
         }
@@ -700,15 +861,18 @@ impl Molecule {
         }
 
         for atom in atoms.iter() {
-            let g = self.graph.clone();
             if self.atom_at(atom)?.ring_membership() == 1 {
-                self.update_atom_ring_info(atom, g)?;
+                self.update_atom_ring_info(atom)?;
             }
         }
-        return Ok(());
+        let (sssr, relevant) = rings::perceive(self);
+        self.n_ssr = sssr.len() as u16;
+        self.rings = sssr;
+        self.relevant = relevant;
+        Ok(())
     }
 
-    pub fn chirality(&self, loc: &u8) -> Result<u8> {
+    pub fn chirality(&self, loc: &AtomIdx) -> Result<u8> {
         if !self.atom_at(loc)?.is_stereocenter() {
             return Ok(0);
         }
@@ -721,13 +885,13 @@ impl Molecule {
         }
     }
 
-    fn update_atom_ring_info(&mut self, loc: &u8, graph: Graph<Atom, Bond>) -> Result<()> {
-        let nei = graph.neighbors(loc)?;
-        let mut n = self.atoms.len() as u8;
+    fn update_atom_ring_info(&mut self, loc: &AtomIdx) -> Result<()> {
+        let nei: Vec<AtomIdx> = self.graph.neighbors(loc)?.copied().collect();
+        let mut n = self.atoms.len() as u32;
         for j in nei {
-            let rm = self.edge_at(*loc, *j)?.ring_membership();
+            let rm = self.edge_at(*loc, j)?.ring_membership();
             self.atom_mut(loc)?.incr_ring_connectivity(rm);
-            let rs = self.edge_at(*loc, *j)?.ring_size();
+            let rs = self.edge_at(*loc, j)?.ring_size();
             if rs < n {
                 n = rs;
             }
@@ -740,20 +904,20 @@ impl Molecule {
         };
         let con = self.atom_at(loc)?.ring_connectivity();
         self.atom_mut(loc)?.set_membership(con);
-        return Ok(());
+        Ok(())
     }
 
-    pub(crate) fn init_rank(&self, loc: &u8) -> Result<usize> {
+    pub(crate) fn init_rank(&self, loc: &AtomIdx) -> Result<usize> {
         let atom = self.atom_at(loc)?;
         let mut irank = String::from("");
-        irank.push_str(&self.degree(&loc)?.to_string());
+        irank.push_str(&self.degree(loc)?.to_string());
         irank.push_str(&leftpad_with(
             atom.element().atomic_number().to_string(),
             3,
             '0',
         ));
-        irank.push_str(&self.hydrogen_count(&loc)?.to_string());
-        let charge = self.atom_at(&loc)?.charge();
+        irank.push_str(&self.hydrogen_count(loc)?.to_string());
+        let charge = self.atom_at(loc)?.charge();
         if charge >= 0 {
             irank.push('0');
             irank.push_str(&charge.to_string());
@@ -761,32 +925,53 @@ impl Molecule {
             irank.push('1');
             irank.push_str(&(0 - charge).to_string());
         }
-        irank.push_str(&self.connectivity(&loc)?.to_string());
+        irank.push_str(&self.connectivity(loc)?.to_string());
         irank.push_str(
             &self
-                .atom_at(&loc)?
+                .atom_at(loc)?
                 .element()
                 .implict_atom_hydrogen(0)
                 .to_string(),
         );
         irank.push_str(&leftpad_with(
-            self.atom_at(&loc)?.get_mass().floor().to_string(),
+            self.atom_at(loc)?.get_mass().floor().to_string(),
             3,
             '0',
         ));
-        irank.push_str(&self.chirality(&loc)?.to_string());
-        Ok(irank
+        irank.push_str(&self.chirality(loc)?.to_string());
+        irank
             .parse()
-            .map_err(|e: ParseIntError| RuatomError::StdError(e.to_string()))?)
+            .map_err(|e: ParseIntError| RuatomError::StdError(e.to_string()))
     }
 
-    pub fn bond_degree_of(&self, loc: &u8) -> Result<u8> {
+    pub fn bond_degree_of(&self, loc: &AtomIdx) -> Result<u8> {
         let deg = self.atom_at(loc)?.bond_degree();
         Ok(deg)
     }
 
-    pub(crate) fn distance_count(&self, loc: &u8) -> Result<u128> {
-        if self.atom_at(&loc)?.ring_connectivity() == 0 {
+    /// Number of bonds on a shortest path between every two atoms, indexed
+    /// by atom - 1, none between atoms of different fragments.
+    pub fn distance_matrix(&self) -> Result<Vec<Vec<Option<u32>>>> {
+        let n = self.atoms.len();
+        let mut matrix = vec![vec![None; n]; n];
+        for (ix, row) in matrix.iter_mut().enumerate() {
+            row[ix] = Some(0);
+            let mut queue = VecDeque::from([ix as AtomIdx + 1]);
+            while let Some(u) = queue.pop_front() {
+                let d = row[u as usize - 1].unwrap() + 1;
+                for v in self.graph.neighbors(&u)? {
+                    if row[*v as usize - 1].is_none() {
+                        row[*v as usize - 1] = Some(d);
+                        queue.push_back(*v);
+                    }
+                }
+            }
+        }
+        Ok(matrix)
+    }
+
+    pub(crate) fn distance_count(&self, loc: &AtomIdx) -> Result<u128> {
+        if self.atom_at(loc)?.ring_connectivity() == 0 {
             return Ok(1);
         };
         let mut distance: u128 = 0;
@@ -807,7 +992,7 @@ impl Molecule {
             if one == 0 {
                 break;
             }
-            distance = distance + 10_u128.pow(level);
+            distance = distance.wrapping_add(10_u128.wrapping_pow(level));
             for j in self.graph.neighbors(&one)? {
                 let cj = *j;
                 if self.edge_at(one, cj)?.ring_membership() > 0 && visited[cj as usize] == 0 {
@@ -816,121 +1001,84 @@ impl Molecule {
                 }
             }
         }
-        return Ok((distance - 1) / 10);
+        Ok(distance.wrapping_sub(1) / 10)
     }
 
+    /// Marks what the aromaticity model finds aromatic, atoms that stop being
+    /// aromatic get their Kekulé double bonds back.
     pub(crate) fn aromaticity_detection(&mut self) -> Result<()> {
-        let mut sp2atoms: HashMap<u8, u8> = HashMap::new();
+        let model = self.aromaticity.clone();
+        let aromatic = model.perceive(self)?;
+        let mut leaving = HashSet::new();
         for atom in self.atoms.iter() {
-            sp2atoms.insert(*atom, 0);
-            let at = self.atom_at(atom)?;
-            let hc = self.hydrogen_count(atom)?;
-            if at.is_aromatic() {
-                sp2atoms.entry(*atom).and_modify(|e| *e = 1);
-            }
-            if !at.ele_is_any()
-                && at.ring_membership() > 0
-                && (RING_SIZE.contains(&at.ring_size())
-                    || RING_SIZE.contains(&at.max_bonds_ringsize()))
-                && Molecule::maybe_aromaticity(at, hc)
-            {
-                let mut count = 0;
-                for j in self.graph.neighbors(atom)? {
-                    let elec = self.edge_at(*atom, *j)?.electron();
-                    if self.atom_at(j)?.ring_membership() > 0 && elec == 2 {
-                        count += 1;
-                    } else if elec == 2 {
-                        sp2atoms.entry(*atom).and_modify(|e| *e = 0);
-                    }
-                }
-                if count == 1 {
-                    sp2atoms.entry(*atom).and_modify(|e| *e = 1);
-                }
+            if self.atom_at(atom)?.is_aromatic() && !aromatic.atoms.contains(atom) {
+                leaving.insert(*atom);
             }
         }
+        let doubles = match leaving.is_empty() {
+            true => HashSet::new(),
+            false => kekule_bonds(self).unwrap_or_default(),
+        };
+        let mut hydrogens = HashMap::new();
         for atom in self.atoms.iter() {
-            let at = self.atom_at(atom)?;
-            let exist = sp2atoms.get(atom).unwrap();
-            if exist == &0
-                && !at.ele_is_any()
-                && at.ring_membership() > 0
-                && (RING_SIZE.contains(&at.ring_size())
-                    || RING_SIZE.contains(&at.max_bonds_ringsize()))
-                && Molecule::maybe_aromaticity_nonsp2(at)
-            {
-                let mut count = 0;
-                for j in self.graph.neighbors(atom)? {
-                    if self.edge_at(*atom, *j)?.electron() == 1 {
-                        count += sp2atoms.get(j).unwrap();
-                    }
-                }
-                if count >= 2 {
-                    sp2atoms.entry(*atom).and_modify(|e| *e = 1);
-                } else if self.atom_at(atom)?.is_aromatic() {
-                    sp2atoms.entry(*atom).and_modify(|e| *e = 0);
-                }
+            if self.atom_at(atom)?.is_aromatic() != aromatic.atoms.contains(atom) {
+                hydrogens.insert(*atom, self.hydrogen_count(atom)?);
             }
         }
-        loop {
-            let mut flag = 0;
-            for atom in self.atoms.iter() {
-                if sp2atoms.get(atom).unwrap() == &1 {
-                    let mut count = 0;
-                    for j in self.graph.neighbors(atom)? {
-                        if self.edge_at(*atom, *j)?.electron() == 1 {
-                            count += sp2atoms.get(j).unwrap();
-                        }
-                    }
-                    if count < 2 {
-                        sp2atoms.entry(*atom).and_modify(|e| *e = 0);
-                        flag = 1;
-                    }
-                }
+        for atom in self.atoms.clone() {
+            match aromatic.atoms.contains(&atom) {
+                true => self.atom_mut(&atom)?.set_aromatic(),
+                false => self.atom_mut(&atom)?.set_aliphatic(),
             }
-            if flag == 0 {
-                break;
+        }
+        for [u, v] in self.bonds.clone() {
+            let pair = sorted_pair(u, v);
+            if aromatic.bonds.contains(&pair) {
+                if self.edge_at(u, v)?.electron() == 2 {
+                    self.rebond(u, v, IMPLICT)?;
+                }
+            } else if doubles.contains(&pair) && (leaving.contains(&u) || leaving.contains(&v)) {
+                self.rebond(u, v, DOUBLE)?;
             }
         }
-        let atoms = self.atoms.clone();
-        let g = self.graph.clone();
-        for atom in atoms.iter() {
-            if sp2atoms.get(atom).unwrap() == &1 {
-                let nei = g.neighbors(atom)?;
-                for j in nei {
-                    let bond = self.edge_mut(*atom, *j)?;
-                    if sp2atoms.get(j).unwrap() == &1 && bond.electron() == 2 {
-                        bond.to_single();
-                    }
+        // a subset atom whose implied hydrogens changed, like the N of a
+        // Kekulé pyridone, keeps them in brackets, a bracket only aromaticity
+        // needed, like [nH] of a pyrrole that is no longer aromatic, goes
+        for (atom, count) in hydrogens.into_iter() {
+            let leaving = !aromatic.atoms.contains(&atom);
+            let subset = match leaving && !self.topologies.contains_key(&atom) {
+                true => self.atom_at(&atom)?.to_subset(),
+                false => None,
+            };
+            if let Some(subset) = subset {
+                let bracket = std::mem::replace(self.atom_mut(&atom)?, subset);
+                if self.hydrogen_count(&atom)? != count {
+                    self.update_atom(atom, bracket);
+                }
+            } else if self.hydrogen_count(&atom)? != count {
+                if let Some(mut bracket) = self.atom_at(&atom)?.to_bracket() {
+                    bracket.set_explicit_hydrogens(count);
+                    self.update_atom(atom, bracket);
                 }
-                self.atom_mut(atom)?.set_aromatic();
-            } else if self.atom_at(atom)?.is_aromatic() {
-                self.to_aliphatic(atom)?;
             }
         }
         Ok(())
     }
 
-    #[inline]
-    fn maybe_aromaticity(atom: &Atom, hc: u8) -> bool {
-        match atom.element().atomic_number() {
-            5 => atom.is_organogen(),
-            6 => atom.is_organogen() || hc == 0 && (atom.charge() == 1 || atom.charge() == -1),
-            7 | 15 => atom.is_organogen() || atom.charge() == 1,
-            8 | 16 | 34 | 52 => atom.charge() == 1,
-            33 => hc == 0 && atom.charge() == 0,
-            _ => false,
-        }
+    /// Perceives aromaticity again with `model`, which is kept for later
+    /// edits of the molecule.
+    pub fn perceive_aromaticity(&mut self, model: impl AromaticityModel + 'static) -> Result<()> {
+        self.aromaticity = Arc::new(model);
+        self.refresh()
     }
 
-    #[inline]
-    fn maybe_aromaticity_nonsp2(atom: &Atom) -> bool {
-        match atom.element().atomic_number() {
-            6 => atom.charge() == -1,
-            7 | 15 => atom.is_organogen() || atom.charge() == -1,
-            8 | 16 => atom.is_organogen(),
-            33 | 34 | 52 => atom.charge() == 0,
-            _ => false,
-        }
+    pub(crate) fn set_aromaticity_model(&mut self, model: Arc<dyn AromaticityModel>) {
+        self.aromaticity = model;
+    }
+
+    /// Uses the aromaticity model of `other`, for molecules built from it.
+    pub(crate) fn share_aromaticity(&mut self, other: &Molecule) {
+        self.aromaticity = other.aromaticity.clone();
     }
 
     pub(crate) fn symmetry_detection(&mut self) -> Result<()> {
@@ -941,7 +1089,7 @@ impl Molecule {
             for j in self.graph.neighbors(atom)? {
                 let rs = self.edge_at(*atom, *j)?.ring_size() as usize;
                 if rs > 0 {
-                    ring_invariant = ring_invariant * prime(rs);
+                    ring_invariant *= prime(rs);
                 }
             }
             inv.push([
@@ -962,42 +1110,47 @@ impl Molecule {
     }
 
     fn canon(&mut self) -> Result<usize> {
-        if self.atoms.len() < 2 {
-            return Ok(1);
-        }
         let mut ranks = Vec::with_capacity(self.atoms.len());
         for atom in self.atoms.iter() {
-            ranks.push(U256::from(self.atom_at(atom)?.rank()));
+            ranks.push(self.atom_at(atom)?.rank());
+        }
+        let (ranks, dist) = self.refine_ranks(ranks)?;
+        for (ix, r) in ranks.into_iter().enumerate() {
+            self.atom_mut(&(ix as AtomIdx + 1))?.set_rank(r);
+        }
+        Ok(dist)
+    }
+
+    /// Iterates `ranks` over the neighbourhoods until the partition is stable,
+    /// returns the refined ranks and the number of distinct ones.
+    fn refine_ranks(&self, ranks: Vec<usize>) -> Result<(Vec<usize>, usize)> {
+        if self.atoms.len() < 2 {
+            return Ok((ranks, 1));
         }
+        let mut ranks: Vec<U256> = ranks.into_iter().map(U256::from).collect();
         let mut dist = 0;
         rank(&mut ranks, &mut dist);
         let mut predist = dist - 1;
         while dist < self.atoms.len() && dist > predist {
             let preranks = ranks.clone();
             predist = dist;
-            for ix in 0..self.atoms.len() {
+            for (ix, atom) in self.atoms.iter().enumerate() {
                 ranks[ix] = (U256::from(prime(ranks[ix].as_usize()))).pow(U256::from(8));
-                for n in self.graph.neighbors(&self.atoms[ix])? {
-                    let b = self.edge_at(self.atoms[ix], *n)?;
+                for n in self.graph.neighbors(atom)? {
+                    let b = self.edge_at(*atom, *n)?;
                     match b.electron() {
-                        1 => {
-                            ranks[ix] =
-                                ranks[ix] * U256::from(prime(preranks[*n as usize - 1].as_usize()))
-                        }
+                        1 => ranks[ix] *= U256::from(prime(preranks[*n as usize - 1].as_usize())),
                         2 => {
-                            ranks[ix] = ranks[ix]
-                                * U256::from(prime(preranks[*n as usize - 1].as_usize()))
-                                    .pow(U256::from(2))
+                            ranks[ix] *= U256::from(prime(preranks[*n as usize - 1].as_usize()))
+                                .pow(U256::from(2))
                         }
                         3 => {
-                            ranks[ix] = ranks[ix]
-                                * U256::from(prime(preranks[*n as usize - 1].as_usize()))
-                                    .pow(U256::from(3))
+                            ranks[ix] *= U256::from(prime(preranks[*n as usize - 1].as_usize()))
+                                .pow(U256::from(3))
                         }
                         4 => {
-                            ranks[ix] = ranks[ix]
-                                * U256::from(prime(preranks[*n as usize - 1].as_usize()))
-                                    .pow(U256::from(4))
+                            ranks[ix] *= U256::from(prime(preranks[*n as usize - 1].as_usize()))
+                                .pow(U256::from(4))
                         }
                         _ => unreachable!(),
                     };
@@ -1005,11 +1158,7 @@ impl Molecule {
             }
             rank(&mut ranks, &mut dist);
         }
-        for ix in 0..ranks.len() {
-            self.atom_mut(&(ix as u8 + 1))?
-                .set_rank(ranks[ix].as_usize());
-        }
-        Ok(dist)
+        Ok((ranks.iter().map(|r| r.as_usize()).collect(), dist))
     }
 
     pub(crate) fn stereocenter_detection(&mut self) -> Result<()> {
@@ -1040,223 +1189,18 @@ impl Molecule {
         Ok(())
     }
 
-    pub fn chiralatoms_count(&self) -> u8 {
+    pub fn chiralatoms_count(&self) -> u32 {
         self.chiralatoms_count
     }
 
-    pub(crate) fn rerank(&mut self) -> Result<()> {
-        let atoms = self.atoms.clone();
-        let mut ranks = Vec::with_capacity(atoms.len());
-        for at in atoms.iter() {
-            ranks.push(self.init_rank(at)?);
-            for j in self.graph.neighbors(at)? {
-                let rs = self.edge_at(*at, *j)?.ring_size() as usize;
-                if rs > 0 {
-                    ranks[*at as usize - 1] = prime(rs);
-                }
-            }
-        }
-        for ix in 0..atoms.len() {
-            self.atom_mut(&atoms[ix])?.set_rank(ranks[ix]);
-        }
-        self.canon()?;
-        return Ok(());
+    pub fn to_smiles(&self) -> Result<String> {
+        SmilesWriter::default().write(self)
     }
 
-    fn tie_rank(&mut self) -> Result<()> {
-        let mut hm = HashMap::new();
-        let atoms = self.atoms.clone();
-        for at in atoms.iter() {
-            let r = self.atom_at(at)?.rank();
-            let e = hm.entry(r).or_insert((0, vec![]));
-            e.0 += 1;
-            e.1.push(*at);
-        }
-        let mut p: usize = 0;
-        for (v, _) in hm.values() {
-            p += v - 1;
-        }
-        let pow = p.pow(3);
-        for at in atoms.iter() {
-            let old = self.atom_at(at)?.rank();
-            self.atom_mut(at)?.set_rank(old * pow);
-        }
-        for (_, ats) in hm.values() {
-            for (ix, at) in ats.iter().enumerate() {
-                let old = self.atom_at(at)?.rank();
-                self.atom_mut(at)?.set_rank(old - (ats.len() - 1 - ix));
-            }
-        }
-        self.canon()?;
-        return Ok(());
-    }
-
-    pub fn to_smiles(&mut self) -> Result<String> {
-        let mut dp = DataBus::new();
-        let mut ranks = Vec::with_capacity(self.atoms.len());
-        let mut min_atom = self.atoms[0];
-        let mut max_rank = 1;
-        for at in self.atoms.iter() {
-            let r = self.atom_at(at)?.rank();
-            if r > max_rank {
-                max_rank = r;
-            }
-            ranks.push(r);
-            if r < self.atom_at(&min_atom)?.rank() {
-                min_atom = *at;
-            }
-        }
-        if max_rank < ranks.len() {
-            self.tie_rank()?;
-        }
-        for at in self.atoms.iter() {
-            let r = self.atom_at(at)?.rank();
-            if r < self.atom_at(&min_atom)?.rank() {
-                min_atom = *at;
-            }
-        }
-        self.get_closures_for_atom(min_atom, None, &mut dp)?;
-
-        dp.visited.clear();
-        let smiles = self.build_smiles_for_atom(min_atom, None, &mut dp)?;
-        Ok(smiles)
-    }
-
-    fn get_closures_for_atom(
-        &self,
-        atom_current: u8,
-        atom_parent_opt: Option<u8>,
-        dp: &mut DataBus,
-    ) -> Result<()> {
-        dp.ancestors.push(atom_current);
-        dp.visited.push(atom_current);
-
-        let mut nbors = Vec::new();
-        for n in self.graph.neighbors(&atom_current)? {
-            if let Some(parent) = atom_parent_opt {
-                if &parent == n {
-                    continue;
-                }
-            }
-            nbors.push(n);
-        }
-        nbors.sort_by_key(|idx| self.atom_at(idx).unwrap().rank());
-
-        for nb in nbors.iter() {
-            if dp.ancestors.contains(nb) {
-                dp.opening_closures
-                    .entry(**nb)
-                    .or_insert(vec![])
-                    .push(atom_current);
-            } else {
-                if !dp.visited.contains(nb) {
-                    self.get_closures_for_atom(**nb, Some(atom_current), dp)?;
-                }
-            }
-        }
-
-        let index = dp
-            .ancestors
-            .iter()
-            .position(|x| *x == atom_current)
-            .unwrap();
-        dp.ancestors.remove(index);
-        Ok(())
-    }
-
-    fn build_smiles_for_atom(
-        &self,
-        atom_current: u8,
-        atom_parent_opt: Option<u8>,
-        dp: &mut DataBus,
-    ) -> Result<String> {
-        dp.visited.push(atom_current);
-        let mut seq: String = String::from("");
-
-        match atom_parent_opt {
-            Some(atom_parent) => {
-                seq += self.edge_at(atom_parent, atom_current)?.token();
-            }
-
-            None => {}
-        }
-        let current = self.atom_at(&atom_current)?;
-        let top = self
-            .topologies
-            .get(&atom_current)
-            .map_or("".to_string(), |top| {
-                top.configuration().unwrap().shorthand().to_string()
-            });
-        if current.is_bracket_atom() {
-            seq += "[";
-            seq += self.symbol(&atom_current)?.as_str();
-            seq += top.as_str();
-            if current.explicit_hydrogens() > 0 {
-                seq += "H";
-                if current.explicit_hydrogens() > 1 {
-                    seq += current.explicit_hydrogens().to_string().as_str();
-                }
-            }
-            if current.charge() < 0 {
-                seq += "-";
-                if current.charge().lt(&-1) {
-                    seq += (current.charge() * -1).to_string().as_str();
-                }
-            } else if current.charge() > 0 {
-                seq += "+";
-                if current.charge() > 1 {
-                    seq += current.charge().to_string().as_str();
-                }
-            }
-            seq += "]";
-        } else {
-            seq += self.symbol(&atom_current)?.as_str();
-        }
-
-        dp.sort_close_and_delete(&atom_current, &mut |one, two| {
-            seq += self.edge_at(atom_current, one)?.token();
-            if two > 9 {
-                seq += "%";
-            }
-            seq += &two.to_string();
-            Ok(())
-        })?;
-        dp.update_open(&atom_current, &mut |d: u8| {
-            if d > 9 {
-                seq += "%";
-            }
-            seq += &d.to_string();
-        });
-
-        let mut nbors = Vec::new();
-        for n in self.graph.neighbors(&atom_current)? {
-            if let Some(parent) = atom_parent_opt {
-                if &parent == n {
-                    continue;
-                }
-            }
-            nbors.push(n);
-        }
-        nbors.sort_by_key(|idx| self.atom_at(idx).unwrap().rank());
-
-        let mut branches: Vec<String> = vec![];
-        for n in nbors.iter() {
-            if !dp.visited.contains(n) {
-                branches.push(self.build_smiles_for_atom(**n, Some(atom_current), dp)?);
-            }
-        }
-
-        if branches.len() > 1 {
-            for branch in branches[..(branches.len() - 1)].iter() {
-                seq += &format!("({})", branch);
-            }
-        }
-
-        if branches.len() > 0 {
-            seq += &branches[branches.len() - 1];
-        }
-
-        Ok(seq)
+    /// Canonical key with every layer, see [`MolKeyBuilder`] to leave some
+    /// out.
+    pub fn key(&self) -> Result<MolKey> {
+        MolKeyBuilder::default().build(self)
     }
 }
 
@@ -1419,8 +1363,8 @@ fn test_rank() {
     m.rings_detection().unwrap();
     m.aromaticity_detection().unwrap();
     m.symmetry_detection().unwrap();
-    let ranks = vec![4, 3, 2, 3, 4, 6, 5, 1];
-    let distances = vec![122, 122, 122, 122, 122, 122, 1, 1];
+    let ranks = [4, 3, 2, 3, 4, 6, 5, 1];
+    let distances = [122, 122, 122, 122, 122, 122, 1, 1];
     for i in 1..9 {
         assert_eq!(ranks[(i - 1) as usize], m.atom_at(&i).unwrap().rank());
         assert_eq!(distances[(i - 1) as usize], m.distance_count(&i).unwrap());
@@ -1467,13 +1411,115 @@ fn test_tie_rank() {
     m.aromaticity_detection().unwrap();
     m.symmetry_detection().unwrap();
     m.stereocenter_detection().unwrap();
-    if m.chiralatoms_count() >= 2 {
-        m.rerank().unwrap();
+    let ranks = super::canon::canonical_ranks(&m, true).unwrap();
+    let mut sorted = ranks.clone();
+    sorted.sort();
+    assert_eq!(sorted, (1..=8).collect::<Vec<_>>());
+    assert_eq!(ranks[7], 1);
+    assert_ne!(ranks[0], ranks[4]);
+}
+
+/// The molecule with its atoms added in `order`, old indices, and its bonds
+/// in reverse.
+#[cfg(test)]
+fn renumbered(m: &Molecule, order: &[AtomIdx]) -> Molecule {
+    let mut new_of = vec![0; order.len() + 1];
+    for (ix, at) in order.iter().enumerate() {
+        new_of[*at as usize] = ix as AtomIdx + 1;
+    }
+    let mut r = Molecule::new();
+    r.share_aromaticity(m);
+    for at in order.iter() {
+        r.add_atom(m.atom_at(at).unwrap().clone()).unwrap();
+    }
+    for [u, v] in m.bonds().iter().rev() {
+        let bond = *m.edge_at(*u, *v).unwrap();
+        r.add_bond(new_of[*u as usize], new_of[*v as usize], bond)
+            .unwrap();
     }
-    m.tie_rank().unwrap();
-    let ranks = vec![5, 3, 2, 4, 6, 8, 7, 1];
-    for i in 1..9 {
-        assert_eq!(ranks[(i - 1) as usize], m.atom_at(&i).unwrap().rank());
+    for (at, t) in m.topologies.iter() {
+        let carriers = t
+            .carriers()
+            .iter()
+            .map(|c| match *c {
+                c if c > 0 => new_of[c as usize] as i64,
+                c => c,
+            })
+            .collect();
+        r.topologies.insert(
+            new_of[*at as usize],
+            t.relabel(new_of[*at as usize], carriers),
+        );
+    }
+    r.refresh().unwrap();
+    r
+}
+
+#[test]
+fn test_canonical_smiles_ignores_atom_order() {
+    let mut seed: u64 = 0x2545f4914f6cdd1d;
+    let mut next = move |n: usize| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed % n as u64) as usize
+    };
+    for smi in [
+        "N[C@@H](C)C(=O)O",
+        "Cl[C@@H](Br)CC[C@H](CO)C#N",
+        "C[C@@H](O)[C@H](C)O",
+        "C[C@@H](O)[C@@H](C)O",
+        "O[C@H]1CC[C@@H](O)CC1",
+        "O[C@H]1CC[C@H](O)CC1",
+        "F/C=C/F",
+        "F/C=C\\F",
+        "OC(=O)/C=C\\C(=O)O",
+        "C/C=C/C=C\\C",
+        "C/C=C(/F)\\C=C\\C",
+        "CC(C)(C)c1cc(C(C)(C)C)cc(C(C)(C)C)c1",
+        "C12C3C4C1C5C2C3C45",
+        "c1ccc2ccccc2c1",
+        "[13CH3]C([2H])([2H])O.[Na+].[Cl-]",
+    ] {
+        let m = crate::Parser::new(smi).parse().unwrap();
+        let expected = m.to_smiles().unwrap();
+        let back = crate::Parser::new(&expected).parse().unwrap();
+        assert_eq!(back.to_smiles().unwrap(), expected, "{}", smi);
+        for _ in 0..10 {
+            let mut order = m.atoms().clone();
+            for i in (1..order.len()).rev() {
+                order.swap(i, next(i + 1));
+            }
+            let shuffled = renumbered(&m, &order);
+            assert_eq!(
+                shuffled.to_smiles().unwrap(),
+                expected,
+                "{} {:?}",
+                smi,
+                order
+            );
+        }
+    }
+    // stereoisomers stay apart
+    for isomers in [
+        ["C/C=C/C=C/C", "C/C=C/C=C\\C", "C/C=C\\C=C/C"],
+        [
+            "C[C@@H](O)[C@H](C)O",
+            "C[C@@H](O)[C@@H](C)O",
+            "C[C@H](O)[C@H](C)O",
+        ],
+    ] {
+        let written: HashSet<String> = isomers
+            .iter()
+            .map(|smi| {
+                crate::Parser::new(smi)
+                    .parse()
+                    .unwrap()
+                    .to_smiles()
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(written.len(), 3, "{:?}", written);
     }
 }
 
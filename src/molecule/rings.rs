@@ -0,0 +1,523 @@
+//! Ring perception.
+//!
+//! Ring families follow Vismara: for every root atom, searching only the
+//! atoms before it, an odd family for each bond whose ends are equally far
+//! from the root and an even family for each atom reached from the root
+//! through two neighbours, as long as the shortest paths to either end only
+//! meet at the root. Each family is the set of cycles along those paths and
+//! is represented by one of them. Taken shortest first, the SSSR keeps the
+//! prototypes independent over GF(2) of the ones kept before, the relevant
+//! cycles are the members of families independent of all strictly shorter
+//! prototypes.
+
+use super::{AtomIdx, Molecule};
+use crate::error::Result;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+
+/// Paths enumerated per candidate end and cycles kept per relevant family,
+/// keeps highly symmetric cages and long chains of rings bounded.
+const MAX_PATHS: usize = 64;
+
+/// A ring as atoms in ring order and the indices in [`Molecule::bonds`] of
+/// the bonds between them, bond `i` joining atom `i` to atom `i + 1`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Ring {
+    atoms: Vec<AtomIdx>,
+    bonds: Vec<usize>,
+}
+
+impl Ring {
+    pub fn atoms(&self) -> &[AtomIdx] {
+        &self.atoms
+    }
+
+    pub fn bonds(&self) -> &[usize] {
+        &self.bonds
+    }
+
+    pub fn len(&self) -> usize {
+        self.atoms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.atoms.is_empty()
+    }
+
+    pub fn contains(&self, atom: &AtomIdx) -> bool {
+        self.atoms.contains(atom)
+    }
+}
+
+/// How the rings of a ring system are joined, by the most atoms any two of
+/// them share.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RingSystemKind {
+    /// A single ring.
+    Isolated,
+    /// Rings only meet at single atoms.
+    Spiro,
+    /// Some rings share a bond, none share more.
+    Fused,
+    /// Some rings share more than one bond.
+    Bridged,
+}
+
+/// SSSR rings connected through shared atoms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RingSystem {
+    rings: Vec<usize>,
+    atoms: Vec<AtomIdx>,
+    spiro_atoms: Vec<AtomIdx>,
+    kind: RingSystemKind,
+    aromatic: usize,
+    hetero: usize,
+}
+
+impl RingSystem {
+    /// Indices in [`Molecule::rings`].
+    pub fn rings(&self) -> &[usize] {
+        &self.rings
+    }
+
+    /// Atoms of the system, sorted.
+    pub fn atoms(&self) -> &[AtomIdx] {
+        &self.atoms
+    }
+
+    /// Atoms that are the only one shared by two rings.
+    pub fn spiro_atoms(&self) -> &[AtomIdx] {
+        &self.spiro_atoms
+    }
+
+    pub fn kind(&self) -> RingSystemKind {
+        self.kind
+    }
+
+    pub fn ring_count(&self) -> usize {
+        self.rings.len()
+    }
+
+    /// Rings made of aromatic atoms only.
+    pub fn aromatic_rings(&self) -> usize {
+        self.aromatic
+    }
+
+    pub fn aliphatic_rings(&self) -> usize {
+        self.rings.len() - self.aromatic
+    }
+
+    /// Rings with at least one atom other than carbon.
+    pub fn hetero_rings(&self) -> usize {
+        self.hetero
+    }
+}
+
+/// Groups the SSSR of `mol` into ring systems, in order of their first ring.
+pub(crate) fn ring_systems(mol: &Molecule) -> Result<Vec<RingSystem>> {
+    let rings = mol.rings();
+    let shared = |i: usize, j: usize| {
+        rings[i]
+            .atoms()
+            .iter()
+            .filter(|a| rings[j].contains(a))
+            .count()
+    };
+    let mut system: Vec<usize> = (0..rings.len()).collect();
+    for i in 0..rings.len() {
+        for j in 0..i {
+            if shared(i, j) > 0 {
+                let (from, to) = (system[i], system[j]);
+                system
+                    .iter_mut()
+                    .filter(|s| **s == from)
+                    .for_each(|s| *s = to);
+            }
+        }
+    }
+
+    let mut systems = vec![];
+    for first in 0..rings.len() {
+        if system[first] != first {
+            continue;
+        }
+        let members: Vec<usize> = (first..rings.len())
+            .filter(|r| system[*r] == first)
+            .collect();
+        let mut atoms: Vec<AtomIdx> = members
+            .iter()
+            .flat_map(|r| rings[*r].atoms().iter().copied())
+            .collect();
+        atoms.sort();
+        atoms.dedup();
+
+        let mut most = 0;
+        let mut spiro_atoms = vec![];
+        for (n, i) in members.iter().enumerate() {
+            for j in members[..n].iter() {
+                let count = shared(*i, *j);
+                most = most.max(count);
+                if count == 1 {
+                    let at = rings[*i].atoms().iter().find(|a| rings[*j].contains(a));
+                    spiro_atoms.extend(at);
+                }
+            }
+        }
+        spiro_atoms.sort();
+        spiro_atoms.dedup();
+        let kind = match (members.len(), most) {
+            (1, _) => RingSystemKind::Isolated,
+            (_, 1) => RingSystemKind::Spiro,
+            (_, 2) => RingSystemKind::Fused,
+            _ => RingSystemKind::Bridged,
+        };
+
+        let (mut aromatic, mut hetero) = (0, 0);
+        for r in members.iter() {
+            let mut all_aromatic = true;
+            let mut any_hetero = false;
+            for at in rings[*r].atoms().iter() {
+                let atom = mol.atom_at(at)?;
+                all_aromatic &= atom.is_aromatic();
+                any_hetero |= !atom.is("C");
+            }
+            aromatic += all_aromatic as usize;
+            hetero += any_hetero as usize;
+        }
+        systems.push(RingSystem {
+            rings: members,
+            atoms,
+            spiro_atoms,
+            kind,
+            aromatic,
+            hetero,
+        });
+    }
+    Ok(systems)
+}
+
+/// The SSSR and the relevant cycles of `mol`, shortest rings first.
+pub(crate) fn perceive(mol: &Molecule) -> (Vec<Ring>, Vec<Ring>) {
+    let graph = RingGraph::new(mol);
+    let rank = graph.cycle_rank();
+    if rank == 0 {
+        return (vec![], vec![]);
+    }
+    let families = graph.families();
+
+    let mut sssr = vec![];
+    let mut basis = Basis::default();
+    for family in families.iter() {
+        if basis.insert(graph.bits(&family.prototype)) {
+            sssr.push(graph.ring(&family.prototype));
+            if sssr.len() == rank {
+                break;
+            }
+        }
+    }
+
+    // members of a family only differ by shorter cycles, so testing the
+    // prototype against every shorter one decides the whole family
+    let mut relevant = vec![];
+    let mut seen = HashSet::new();
+    let mut shorter = Basis::default();
+    let mut i = 0;
+    while i < families.len() {
+        let len = families[i].prototype.len();
+        let same: Vec<&Family> = families[i..]
+            .iter()
+            .take_while(|f| f.prototype.len() == len)
+            .collect();
+        for family in same.iter() {
+            if shorter.independent(graph.bits(&family.prototype)) {
+                for ring in graph.members(family) {
+                    if seen.insert(ring.clone()) {
+                        relevant.push(graph.ring(&ring));
+                    }
+                }
+            }
+        }
+        for family in same.iter() {
+            shorter.insert(graph.bits(&family.prototype));
+        }
+        i += same.len();
+    }
+    (sssr, relevant)
+}
+
+/// Cycles closed from `root` through `left`, `middle` and `right` along any
+/// of their shortest paths, represented by the first one found.
+struct Family {
+    prototype: Vec<usize>,
+    root: usize,
+    preds: Rc<Vec<Vec<usize>>>,
+    left: usize,
+    middle: Vec<usize>,
+    right: usize,
+}
+
+/// The molecule as 0-based adjacency lists with indexed bonds.
+struct RingGraph {
+    adj: Vec<Vec<usize>>,
+    edges: HashMap<[usize; 2], usize>,
+}
+
+impl RingGraph {
+    fn new(mol: &Molecule) -> Self {
+        let mut adj: Vec<Vec<usize>> = vec![vec![]; mol.atoms().len()];
+        let mut edges = HashMap::new();
+        for (i, [u, v]) in mol.bonds().iter().enumerate() {
+            if mol.edge_at(*u, *v).is_ok_and(|b| b.electron() < 1) {
+                continue;
+            }
+            let (a, b) = (*u as usize - 1, *v as usize - 1);
+            if edges.insert(key(a, b), i).is_none() {
+                adj[a].push(b);
+                adj[b].push(a);
+            }
+        }
+        Self { adj, edges }
+    }
+
+    fn cycle_rank(&self) -> usize {
+        let mut seen = vec![false; self.adj.len()];
+        let mut components = 0;
+        for root in 0..self.adj.len() {
+            if seen[root] {
+                continue;
+            }
+            components += 1;
+            seen[root] = true;
+            let mut stack = vec![root];
+            while let Some(a) = stack.pop() {
+                for b in self.adj[a].iter() {
+                    if !seen[*b] {
+                        seen[*b] = true;
+                        stack.push(*b);
+                    }
+                }
+            }
+        }
+        (self.edges.len() + components).saturating_sub(self.adj.len())
+    }
+
+    /// Ring families by prototype length, then prototype atoms.
+    fn families(&self) -> Vec<Family> {
+        let mut seen: HashSet<Vec<usize>> = HashSet::new();
+        let mut families = vec![];
+        for root in 0..self.adj.len() {
+            let (dist, preds, hops) = self.shortest_paths(root);
+            let preds = Rc::new(preds);
+            let mut closures = vec![];
+            for (a, nbors) in self.adj.iter().enumerate() {
+                if dist[a] == usize::MAX {
+                    continue;
+                }
+                // odd cycles close on a bond, even ones on an atom, both only
+                // when every path to one end misses every path to the other
+                let apart = |u: usize, v: usize| hops[u] & hops[v] == 0;
+                for b in nbors.iter().filter(|b| **b > a && dist[**b] == dist[a]) {
+                    if apart(a, *b) {
+                        closures.push((a, vec![], *b));
+                    }
+                }
+                for (i, p) in preds[a].iter().enumerate() {
+                    for q in preds[a][i + 1..].iter().filter(|q| apart(*p, **q)) {
+                        closures.push((*p, vec![a], *q));
+                    }
+                }
+            }
+            for (left, middle, right) in closures {
+                let mut family = Family {
+                    prototype: vec![],
+                    root,
+                    preds: Rc::clone(&preds),
+                    left,
+                    middle,
+                    right,
+                };
+                let prototype = cycles(&family).next();
+                if let Some(ring) = prototype.filter(|r| seen.insert(r.clone())) {
+                    family.prototype = ring;
+                    families.push(family);
+                }
+            }
+        }
+        families.sort_by(|a, b| {
+            let (a, b) = (&a.prototype, &b.prototype);
+            a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+        });
+        families
+    }
+
+    /// Members of a relevant family, at most [`MAX_PATHS`] of them.
+    fn members(&self, family: &Family) -> Vec<Vec<usize>> {
+        let mut members: Vec<Vec<usize>> = cycles(family).take(MAX_PATHS).collect();
+        members.sort();
+        members.dedup();
+        members
+    }
+
+    /// Breadth first from `root` over the atoms before it. Besides distances
+    /// and predecessors, marks for each atom the neighbours of the root its
+    /// shortest paths leave through.
+    fn shortest_paths(&self, root: usize) -> (Vec<usize>, Vec<Vec<usize>>, Vec<u64>) {
+        let mut dist = vec![usize::MAX; self.adj.len()];
+        let mut preds = vec![vec![]; self.adj.len()];
+        let mut hops = vec![0u64; self.adj.len()];
+        dist[root] = 0;
+        let mut queue = VecDeque::from([root]);
+        while let Some(a) = queue.pop_front() {
+            let nbors = self.adj[a].iter().enumerate();
+            for (i, b) in nbors.filter(|(_, b)| **b < root) {
+                if dist[*b] == usize::MAX {
+                    dist[*b] = dist[a] + 1;
+                    queue.push_back(*b);
+                }
+                if dist[*b] == dist[a] + 1 {
+                    preds[*b].push(a);
+                    hops[*b] |= if a == root { 1 << (i % 64) } else { hops[a] };
+                }
+            }
+        }
+        (dist, preds, hops)
+    }
+
+    fn bits(&self, ring: &[usize]) -> Vec<u64> {
+        let mut bits = vec![0u64; self.edges.len().div_ceil(64)];
+        for (i, a) in ring.iter().enumerate() {
+            let e = self.edges[&key(*a, ring[(i + 1) % ring.len()])];
+            bits[e / 64] ^= 1 << (e % 64);
+        }
+        bits
+    }
+
+    fn ring(&self, ring: &[usize]) -> Ring {
+        Ring {
+            atoms: ring.iter().map(|a| *a as AtomIdx + 1).collect(),
+            bonds: (0..ring.len())
+                .map(|i| self.edges[&key(ring[i], ring[(i + 1) % ring.len()])])
+                .collect(),
+        }
+    }
+}
+
+/// Joins the shortest paths from the root to `left` with those to `right`
+/// through `middle`, normalised.
+fn cycles(family: &Family) -> impl Iterator<Item = Vec<usize>> + '_ {
+    let (preds, root) = (&family.preds, family.root);
+    Paths::new(preds, root, family.left).flat_map(move |l| {
+        Paths::new(preds, root, family.right).map(move |r| {
+            let mut ring = l.clone();
+            ring.extend(family.middle.iter());
+            ring.extend(r.iter().rev());
+            ring.pop();
+            normalise(ring)
+        })
+    })
+}
+
+fn key(a: usize, b: usize) -> [usize; 2] {
+    if a < b {
+        [a, b]
+    } else {
+        [b, a]
+    }
+}
+
+/// Shortest paths from the root to an atom, root first, at most
+/// [`MAX_PATHS`] of them.
+struct Paths<'a> {
+    preds: &'a [Vec<usize>],
+    root: usize,
+    stack: Vec<Vec<usize>>,
+    found: usize,
+}
+
+impl<'a> Paths<'a> {
+    fn new(preds: &'a [Vec<usize>], root: usize, to: usize) -> Self {
+        Self {
+            preds,
+            root,
+            stack: vec![vec![to]],
+            found: 0,
+        }
+    }
+}
+
+impl Iterator for Paths<'_> {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.found < MAX_PATHS {
+            let mut path = self.stack.pop()?;
+            let last = *path.last().unwrap();
+            if last == self.root {
+                self.found += 1;
+                path.reverse();
+                return Some(path);
+            }
+            for p in self.preds[last].iter() {
+                let mut next = path.clone();
+                next.push(*p);
+                self.stack.push(next);
+            }
+        }
+        None
+    }
+}
+
+/// Rotates the ring to start at its lowest atom, going round towards the
+/// lower of the two neighbours.
+fn normalise(mut ring: Vec<usize>) -> Vec<usize> {
+    let start = (0..ring.len()).min_by_key(|i| ring[*i]).unwrap();
+    ring.rotate_left(start);
+    if ring[ring.len() - 1] < ring[1] {
+        ring[1..].reverse();
+    }
+    ring
+}
+
+/// Cycles in row echelon form over GF(2).
+#[derive(Default)]
+struct Basis {
+    rows: Vec<Vec<u64>>,
+}
+
+impl Basis {
+    fn leading(bits: &[u64]) -> Option<usize> {
+        bits.iter()
+            .enumerate()
+            .rev()
+            .find(|(_, w)| **w != 0)
+            .map(|(i, w)| i * 64 + 63 - w.leading_zeros() as usize)
+    }
+
+    fn reduce(&self, bits: &mut [u64]) {
+        for row in self.rows.iter() {
+            let lead = Basis::leading(row).unwrap();
+            if bits[lead / 64] & (1 << (lead % 64)) != 0 {
+                for (b, r) in bits.iter_mut().zip(row.iter()) {
+                    *b ^= r;
+                }
+            }
+        }
+    }
+
+    fn independent(&self, mut bits: Vec<u64>) -> bool {
+        self.reduce(&mut bits);
+        bits.iter().any(|w| *w != 0)
+    }
+
+    /// Adds the cycle when it is independent of the basis.
+    fn insert(&mut self, mut bits: Vec<u64>) -> bool {
+        self.reduce(&mut bits);
+        if bits.iter().all(|w| *w == 0) {
+            return false;
+        }
+        self.rows.push(bits);
+        self.rows
+            .sort_by_key(|b| std::cmp::Reverse(Basis::leading(b)));
+        true
+    }
+}
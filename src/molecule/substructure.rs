@@ -0,0 +1,334 @@
+//! Substructure search.
+//!
+//! A VF2 style depth first search: query atoms are visited in breadth first
+//! order so that each one, but the first of a component, is tried only
+//! against the unmapped target neighbours of where its parent went. A pair is
+//! feasible when the atoms match, the target atom has at least as many
+//! neighbours, and every bond to an already mapped query neighbour exists in
+//! the target and matches.
+
+use super::{
+    kekule::is_delocalised, topology::permutation_parity, AtomIdx, Molecule, TopologySeq, TH1,
+};
+use crate::error::Result;
+use std::collections::{HashSet, VecDeque};
+
+/// What the search maps into a target, atoms are `1..=order`.
+pub(crate) trait Query {
+    fn order(&self) -> usize;
+    fn neighbors(&self, u: AtomIdx) -> Result<Vec<AtomIdx>>;
+    fn match_atom(&self, u: AtomIdx, target: &Molecule, v: AtomIdx) -> Result<bool>;
+    /// Matches the query bond `u`-`w` against the target bond `v`-`x`.
+    fn match_bond(&self, u: [AtomIdx; 2], target: &Molecule, v: [AtomIdx; 2]) -> Result<bool>;
+    /// Checks a complete mapping, `mapping[u - 1]` being where `u` went.
+    fn accept(&self, _target: &Molecule, _mapping: &[AtomIdx]) -> Result<bool> {
+        Ok(true)
+    }
+}
+
+/// Which mappings a search collects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Collect {
+    All,
+    First,
+    /// One mapping per set of target atoms.
+    Unique,
+}
+
+/// Every mapping of `query` into `target` as target atoms in query atom order.
+pub(crate) fn search<Q: Query>(
+    query: &Q,
+    target: &Molecule,
+    collect: Collect,
+) -> Result<Vec<Vec<AtomIdx>>> {
+    if query.order() == 0 || query.order() > target.order() {
+        return Ok(vec![]);
+    }
+    let mut state = State::new(query, target, collect, None)?;
+    state.extend(0)?;
+    Ok(state.found)
+}
+
+/// Whether `query` maps into `target` with its first atom on `root`.
+pub(crate) fn matches_at<Q: Query>(query: &Q, target: &Molecule, root: AtomIdx) -> Result<bool> {
+    if query.order() == 0 || query.order() > target.order() {
+        return Ok(false);
+    }
+    let mut state = State::new(query, target, Collect::First, Some(root))?;
+    state.extend(0)?;
+    Ok(!state.found.is_empty())
+}
+
+struct State<'a, Q: Query> {
+    query: &'a Q,
+    target: &'a Molecule,
+    collect: Collect,
+    root: Option<AtomIdx>,
+    order: Vec<AtomIdx>,
+    parent: Vec<Option<AtomIdx>>,
+    neighbors: Vec<Vec<AtomIdx>>,
+    core: Vec<AtomIdx>,
+    used: Vec<bool>,
+    seen: HashSet<Vec<AtomIdx>>,
+    found: Vec<Vec<AtomIdx>>,
+}
+
+impl<'a, Q: Query> State<'a, Q> {
+    fn new(
+        query: &'a Q,
+        target: &'a Molecule,
+        collect: Collect,
+        root: Option<AtomIdx>,
+    ) -> Result<Self> {
+        let n = query.order();
+        let mut neighbors = vec![vec![]];
+        for u in 1..=n as AtomIdx {
+            neighbors.push(query.neighbors(u)?);
+        }
+        let mut order = Vec::with_capacity(n);
+        let mut parent = Vec::with_capacity(n);
+        let mut visited = vec![false; n + 1];
+        for root in 1..=n as AtomIdx {
+            if visited[root as usize] {
+                continue;
+            }
+            visited[root as usize] = true;
+            let mut queue = VecDeque::from([(root, None)]);
+            while let Some((u, p)) = queue.pop_front() {
+                order.push(u);
+                parent.push(p);
+                for w in neighbors[u as usize].iter() {
+                    if !visited[*w as usize] {
+                        visited[*w as usize] = true;
+                        queue.push_back((*w, Some(u)));
+                    }
+                }
+            }
+        }
+        Ok(Self {
+            query,
+            target,
+            collect,
+            root,
+            order,
+            parent,
+            neighbors,
+            core: vec![0; n + 1],
+            used: vec![false; target.atoms().len() + 1],
+            seen: HashSet::new(),
+            found: vec![],
+        })
+    }
+
+    fn done(&self) -> bool {
+        self.collect == Collect::First && !self.found.is_empty()
+    }
+
+    fn extend(&mut self, depth: usize) -> Result<()> {
+        if depth == self.order.len() {
+            let mapping = self.core[1..].to_vec();
+            if !self.query.accept(self.target, &mapping)? {
+                return Ok(());
+            }
+            if self.collect == Collect::Unique {
+                let mut atoms = mapping.clone();
+                atoms.sort();
+                if !self.seen.insert(atoms) {
+                    return Ok(());
+                }
+            }
+            self.found.push(mapping);
+            return Ok(());
+        }
+        let u = self.order[depth];
+        let candidates: Vec<AtomIdx> = match self.parent[depth] {
+            None if depth == 0 && self.root.is_some() => self.root.into_iter().collect(),
+            Some(p) => self
+                .target
+                .graph()
+                .neighbors(&self.core[p as usize])?
+                .copied()
+                .collect(),
+            None => self.target.atoms().clone(),
+        };
+        for v in candidates {
+            if self.used[v as usize] || !self.feasible(u, v)? {
+                continue;
+            }
+            self.core[u as usize] = v;
+            self.used[v as usize] = true;
+            self.extend(depth + 1)?;
+            self.core[u as usize] = 0;
+            self.used[v as usize] = false;
+            if self.done() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn feasible(&self, u: AtomIdx, v: AtomIdx) -> Result<bool> {
+        let graph = self.target.graph();
+        if graph.bound_count(&v)? < self.neighbors[u as usize].len()
+            || !self.query.match_atom(u, self.target, v)?
+        {
+            return Ok(false);
+        }
+        for w in self.neighbors[u as usize].iter() {
+            let x = self.core[*w as usize];
+            if x == 0 {
+                continue;
+            }
+            if !graph.adjancent(v, x) || !self.query.match_bond([u, *w], self.target, [v, x])? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Matches one molecule as a substructure of others.
+///
+/// Atoms always match on element and aromaticity, `*` matching any atom, and
+/// bonds on order, aromatic bonds only matching aromatic ones. A charge or
+/// isotope is only required when the query atom has one.
+#[derive(Clone, Copy)]
+pub struct SubstructureMatcher<'a> {
+    query: &'a Molecule,
+    charge: bool,
+    isotope: bool,
+    chirality: bool,
+}
+
+impl<'a> SubstructureMatcher<'a> {
+    pub fn new(query: &'a Molecule) -> Self {
+        Self {
+            query,
+            charge: true,
+            isotope: true,
+            chirality: false,
+        }
+    }
+
+    /// Whether charged query atoms require the same charge, on by default.
+    pub fn charge(mut self, charge: bool) -> Self {
+        self.charge = charge;
+        self
+    }
+
+    /// Whether query isotopes must be matched, on by default.
+    pub fn isotope(mut self, isotope: bool) -> Self {
+        self.isotope = isotope;
+        self
+    }
+
+    /// Whether tetrahedral centres of the query require the same
+    /// configuration in the target, off by default.
+    pub fn chirality(mut self, chirality: bool) -> Self {
+        self.chirality = chirality;
+        self
+    }
+
+    pub fn is_match(&self, target: &Molecule) -> Result<bool> {
+        Ok(!search(self, target, Collect::First)?.is_empty())
+    }
+
+    /// The first mapping found, target atoms in query atom order.
+    pub fn first(&self, target: &Molecule) -> Result<Option<Vec<AtomIdx>>> {
+        Ok(search(self, target, Collect::First)?.pop())
+    }
+
+    /// Every mapping, including those only differing by query symmetry.
+    pub fn matches(&self, target: &Molecule) -> Result<Vec<Vec<AtomIdx>>> {
+        search(self, target, Collect::All)
+    }
+
+    /// One mapping for each distinct set of target atoms.
+    pub fn unique_matches(&self, target: &Molecule) -> Result<Vec<Vec<AtomIdx>>> {
+        search(self, target, Collect::Unique)
+    }
+
+    /// Whether the tetrahedral centre `u` of the query has the same
+    /// configuration at `mapping[u - 1]` in the target.
+    fn same_chirality(&self, u: AtomIdx, target: &Molecule, mapping: &[AtomIdx]) -> Result<bool> {
+        let query = match self.query.topology_at(&u) {
+            Some(t) if t.seq() == TopologySeq::Tetrahedral => t,
+            _ => return Ok(true),
+        };
+        let v = mapping[u as usize - 1];
+        let other = match target.topology_at(&v) {
+            Some(t) if t.seq() == TopologySeq::Tetrahedral => t,
+            _ => return Ok(false),
+        };
+        let mut carriers: Vec<i64> = query
+            .carriers()
+            .iter()
+            .map(|c| match *c {
+                -1 => -1,
+                c => mapping[c as usize - 1] as i64,
+            })
+            .collect();
+        // the implicit hydrogen of the query takes whatever is left over
+        let left: Vec<i64> = other
+            .carriers()
+            .iter()
+            .filter(|c| !carriers.contains(c) || **c == -1)
+            .copied()
+            .collect();
+        if let (Some(h), [rest]) = (carriers.iter().position(|c| *c == -1), left.as_slice()) {
+            carriers[h] = *rest;
+        }
+        let parity = match permutation_parity(&carriers, other.carriers()) {
+            Some(p) => p,
+            None => return Ok(false),
+        };
+        let sign = |c: bool| if c { -1 } else { 1 };
+        Ok(sign(query.configuration()? == TH1) * parity == sign(other.configuration()? == TH1))
+    }
+}
+
+impl Query for SubstructureMatcher<'_> {
+    fn order(&self) -> usize {
+        self.query.order()
+    }
+
+    fn neighbors(&self, u: AtomIdx) -> Result<Vec<AtomIdx>> {
+        Ok(self.query.graph().neighbors(&u)?.copied().collect())
+    }
+
+    fn match_atom(&self, u: AtomIdx, target: &Molecule, v: AtomIdx) -> Result<bool> {
+        let (a, b) = (self.query.atom_at(&u)?, target.atom_at(&v)?);
+        if a.ele_is_any() {
+            return Ok(true);
+        }
+        Ok(a.element().atomic_number() == b.element().atomic_number()
+            && a.is_aromatic() == b.is_aromatic()
+            && (!self.charge || a.charge() == 0 || a.charge() == b.charge())
+            && (!self.isotope || a.isotope() < 0 || a.isotope() == b.isotope()))
+    }
+
+    fn match_bond(&self, u: [AtomIdx; 2], target: &Molecule, v: [AtomIdx; 2]) -> Result<bool> {
+        Ok(bond_order(self.query, u)? == bond_order(target, v)?)
+    }
+
+    fn accept(&self, target: &Molecule, mapping: &[AtomIdx]) -> Result<bool> {
+        if !self.chirality {
+            return Ok(true);
+        }
+        for u in 1..=self.query.order() as AtomIdx {
+            if !self.same_chirality(u, target, mapping)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Bond order for matching, `None` for an aromatic bond.
+pub(crate) fn bond_order(mol: &Molecule, [u, v]: [AtomIdx; 2]) -> Result<Option<u8>> {
+    let bond = mol.edge_at(u, v)?;
+    if bond.is_aromatic() || (bond.ring_membership() == 1 && is_delocalised(mol, u, v)?) {
+        return Ok(None);
+    }
+    Ok(Some(bond.electron()))
+}
@@ -1,4 +1,5 @@
 use super::configuration::*;
+use super::AtomIdx;
 use crate::error::RuatomError;
 
 const SQUAREPLANARPERMUTATIONS: [[i8; 32]; 3] = [
@@ -331,24 +332,49 @@ pub enum TopologySeq {
     Octahedral,
 }
 
+/// Enhanced stereo group of a configuration, the numbers tell groups of the
+/// same kind apart.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Hash)]
+pub enum StereoGroup {
+    /// The configuration is known as written.
+    #[default]
+    Absolute,
+    /// A mixture of the configuration as written and its inverse.
+    And(u32),
+    /// Either the configuration as written or its inverse.
+    Or(u32),
+}
+
 pub trait Topology {
-    fn new_topology(u: u8, conf: Configuration, vs: Vec<i8>) -> Result<Self, RuatomError>
+    fn new_topology(u: AtomIdx, conf: Configuration, vs: Vec<i64>) -> Result<Self, RuatomError>
     where
         Self: Sized;
     fn configuration(&self) -> Result<Configuration, RuatomError> {
-        return Ok(UNKNOWN);
+        Ok(UNKNOWN)
     }
-    fn atom(&self) -> i8;
+    fn atom(&self) -> i64;
     fn seq(&self) -> TopologySeq;
-    fn order_by(&self, ranks: &Vec<i8>) -> Option<Box<dyn Topology>>;
-    fn configuration_of(&self, ranks: &Vec<i8>) -> Result<Configuration, RuatomError> {
+    /// Neighbours the configuration is expressed against, `-1` stands for an
+    /// implicit hydrogen.
+    fn carriers(&self) -> &[i64] {
+        &[]
+    }
+    /// Enhanced stereo group the configuration belongs to.
+    fn group(&self) -> StereoGroup {
+        StereoGroup::Absolute
+    }
+    fn set_group(&mut self, _group: StereoGroup) {}
+    /// Same configuration centred on `u` with the given carriers.
+    fn relabel(&self, u: AtomIdx, vs: Vec<i64>) -> Box<dyn Topology + Send + Sync>;
+    fn order_by(&self, ranks: &[usize]) -> Option<Box<dyn Topology>>;
+    fn configuration_of(&self, ranks: &[usize]) -> Result<Configuration, RuatomError> {
         let topology = self.order_by(ranks);
-        return match topology {
+        match topology {
             None => Ok(UNKNOWN),
             Some(t) => t.configuration(),
-        };
+        }
     }
-    fn parity(&self, atoms: Vec<u8>, ranks: Vec<u8>) -> i8 {
+    fn parity(&self, atoms: Vec<AtomIdx>, ranks: Vec<usize>) -> i8 {
         let mut count = 0;
         for (ix, atom) in atoms.iter().enumerate() {
             let mut j = ix + 1;
@@ -362,10 +388,10 @@ pub trait Topology {
         if count & 0x1 == 1 {
             return -1;
         }
-        return 1;
+        1
     }
 
-    fn parity4(&self, atoms: &Vec<i8>, ranks: &Vec<i8>) -> i8 {
+    fn parity4(&self, atoms: &[i64], ranks: &[usize]) -> i8 {
         let mut count = 0;
         let mut ix = 0;
         while ix < 4 {
@@ -381,10 +407,10 @@ pub trait Topology {
         if count & 0x1 == 1 {
             return -1;
         }
-        return 1;
+        1
     }
 
-    fn parity3(&self, atoms: &Vec<i8>, ranks: &Vec<i8>) -> i8 {
+    fn parity3(&self, atoms: &[i64], ranks: &[usize]) -> i8 {
         let mut count = 0;
         let mut ix = 0;
         while ix < 3 {
@@ -400,10 +426,10 @@ pub trait Topology {
         if count & 0x1 == 1 {
             return -1;
         }
-        return 1;
+        1
     }
 
-    fn sort(&self, atoms: &mut Vec<i8>, ranks: &Vec<i8>) {
+    fn sort(&self, atoms: &mut [i64], ranks: &[usize]) {
         let mut ix = 0;
         let mut jx = 0;
         let leg = atoms.len() - 1;
@@ -429,17 +455,17 @@ pub trait Topology {
         }
     }
 
-    fn apply_inv(&self, src: &Vec<i8>, perm: &[i8]) -> Vec<i8> {
+    fn apply_inv(&self, src: &[i64], perm: &[i8]) -> Vec<i64> {
         let mut ix = 0;
         let mut res = Vec::with_capacity(src.len());
         while ix < src.len() {
             res[ix] = src[perm[ix] as usize];
             ix += 1;
         }
-        return res;
+        res
     }
 
-    fn indirect_sort(&self, dst: &mut Vec<i8>, rank: &Vec<i8>) {
+    fn indirect_sort(&self, dst: &mut [i64], rank: &[usize]) {
         let mut ix = 0;
         while ix < dst.len() {
             let mut jx = ix;
@@ -454,7 +480,7 @@ pub trait Topology {
         }
     }
 
-    fn check(&self, dst: &Vec<i8>, src: &Vec<i8>, perm: &[i8], step: usize, skip: usize) -> bool {
+    fn check(&self, dst: &[i64], src: &[i64], perm: &[i8], step: usize, skip: usize) -> bool {
         let mut ix = 0;
         while ix < perm.len() {
             let mut jx = 0;
@@ -472,74 +498,95 @@ pub trait Topology {
                 ix += step;
             }
         }
-        return false;
+        false
     }
 }
 
 #[derive(PartialEq, Eq, Clone)]
 struct BaseTopology {
-    u: u8,
+    u: AtomIdx,
     p: i8,
-    vs: Vec<i8>,
+    vs: Vec<i64>,
+    group: StereoGroup,
 }
 
 impl BaseTopology {
-    fn new(u: u8, p: i8, vs: Vec<i8>) -> Self {
-        Self { u, p, vs }
+    fn new(u: AtomIdx, p: i8, vs: Vec<i64>) -> Self {
+        Self {
+            u,
+            p,
+            vs,
+            group: StereoGroup::Absolute,
+        }
+    }
+
+    fn relabel(&self, u: AtomIdx, vs: Vec<i64>) -> Self {
+        Self {
+            u,
+            vs,
+            ..self.clone()
+        }
     }
 }
 
 pub struct Tetrahedral(BaseTopology);
 
 impl Topology for Tetrahedral {
-    fn new_topology(u: u8, conf: Configuration, vs: Vec<i8>) -> Result<Self, RuatomError> {
+    fn new_topology(u: AtomIdx, conf: Configuration, vs: Vec<i64>) -> Result<Self, RuatomError> {
         if !conf.is_implict() && !conf.is_tetrahedral() {
             return Err(RuatomError::IllegalMolecule(
                 "invalid Tetrahedral configuration",
             ));
         }
-        let p;
-        if conf.is_anti_clockwise() {
-            p = -1;
-        } else {
-            p = 1;
-        }
+        let p = if conf.is_anti_clockwise() { -1 } else { 1 };
         Ok(Self(BaseTopology::new(u, p, vs)))
     }
 
     fn configuration(&self) -> Result<Configuration, RuatomError> {
         if self.0.p == -1 {
-            return Ok(TH1);
+            Ok(TH1)
         } else if self.0.p == 1 {
-            return Ok(TH2);
+            Ok(TH2)
         } else {
-            return Err(RuatomError::IllegalMolecule(
+            Err(RuatomError::IllegalMolecule(
                 "invalid Tetrahedral configuration",
-            ));
+            ))
         }
     }
-    fn atom(&self) -> i8 {
-        return self.0.u.clone() as i8;
+    fn atom(&self) -> i64 {
+        self.0.u as i64
+    }
+    fn carriers(&self) -> &[i64] {
+        &self.0.vs
+    }
+    fn group(&self) -> StereoGroup {
+        self.0.group
+    }
+    fn set_group(&mut self, group: StereoGroup) {
+        self.0.group = group;
+    }
+    fn relabel(&self, u: AtomIdx, vs: Vec<i64>) -> Box<dyn Topology + Send + Sync> {
+        Box::new(Self(self.0.relabel(u, vs)))
     }
     fn seq(&self) -> TopologySeq {
         TopologySeq::Tetrahedral
     }
-    fn order_by(&self, ranks: &Vec<i8>) -> Option<Box<dyn Topology>> {
+    fn order_by(&self, ranks: &[usize]) -> Option<Box<dyn Topology>> {
         let mut ams = self.0.vs.clone();
         self.sort(&mut ams, ranks);
-        return Some(Box::new(Self(BaseTopology::new(
+        Some(Box::new(Self(BaseTopology::new(
             self.0.u,
             self.0.p * self.parity4(&self.0.vs, ranks),
             ams,
-        ))));
+        ))))
     }
 
-    fn configuration_of(&self, ranks: &Vec<i8>) -> Result<Configuration, RuatomError> {
+    fn configuration_of(&self, ranks: &[usize]) -> Result<Configuration, RuatomError> {
         let c = self.0.p * self.parity4(&self.0.vs, ranks);
         if c < 0 {
-            return Ok(TH1);
+            Ok(TH1)
         } else {
-            return Ok(TH2);
+            Ok(TH2)
         }
     }
 }
@@ -547,113 +594,122 @@ impl Topology for Tetrahedral {
 pub struct Trigonal(BaseTopology);
 
 impl Topology for Trigonal {
-    fn new_topology(u: u8, conf: Configuration, vs: Vec<i8>) -> Result<Self, RuatomError> {
+    fn new_topology(u: AtomIdx, conf: Configuration, vs: Vec<i64>) -> Result<Self, RuatomError> {
         if !conf.is_implict() && !conf.is_trigonal() {
             return Err(RuatomError::IllegalMolecule(
                 "invalid Trigonal configuration",
             ));
         }
-        let p;
-        if conf.is_anti_clockwise() {
-            p = -1;
-        } else {
-            p = 1;
-        }
+        let p = if conf.is_anti_clockwise() { -1 } else { 1 };
         Ok(Self(BaseTopology::new(u, p, vs)))
     }
 
     fn configuration(&self) -> Result<Configuration, RuatomError> {
         if self.0.p == -1 {
-            return Ok(DB1);
+            Ok(DB1)
         } else if self.0.p == 1 {
-            return Ok(DB2);
+            Ok(DB2)
         } else {
-            return Err(RuatomError::IllegalMolecule(
+            Err(RuatomError::IllegalMolecule(
                 "invalid Trigonal configuration",
-            ));
+            ))
         }
     }
-    fn atom(&self) -> i8 {
-        return self.0.u.clone() as i8;
+    fn atom(&self) -> i64 {
+        self.0.u as i64
+    }
+    fn carriers(&self) -> &[i64] {
+        &self.0.vs
+    }
+    fn group(&self) -> StereoGroup {
+        self.0.group
+    }
+    fn set_group(&mut self, group: StereoGroup) {
+        self.0.group = group;
+    }
+    fn relabel(&self, u: AtomIdx, vs: Vec<i64>) -> Box<dyn Topology + Send + Sync> {
+        Box::new(Self(self.0.relabel(u, vs)))
     }
 
     fn seq(&self) -> TopologySeq {
         TopologySeq::Trigonal
     }
-    fn order_by(&self, ranks: &Vec<i8>) -> Option<Box<dyn Topology>> {
+    fn order_by(&self, ranks: &[usize]) -> Option<Box<dyn Topology>> {
         let mut ams = self.0.vs.clone();
         self.sort(&mut ams, ranks);
-        return Some(Box::new(Self(BaseTopology::new(
+        Some(Box::new(Self(BaseTopology::new(
             self.0.u,
             self.0.p * self.parity3(&self.0.vs, ranks),
             ams,
-        ))));
+        ))))
     }
 }
 
 pub struct ExtendedTetrahedral(BaseTopology);
 
 impl Topology for ExtendedTetrahedral {
-    fn new_topology(u: u8, conf: Configuration, vs: Vec<i8>) -> Result<Self, RuatomError> {
+    fn new_topology(u: AtomIdx, conf: Configuration, vs: Vec<i64>) -> Result<Self, RuatomError> {
         if !conf.is_implict() && !conf.is_extend_tetrahedral() {
             return Err(RuatomError::IllegalMolecule(
                 "invalid ExtendedTetrahedral configuration",
             ));
         }
-        let p;
-        if conf.is_anti_clockwise() {
-            p = -1;
-        } else {
-            p = 1;
-        }
+        let p = if conf.is_anti_clockwise() { -1 } else { 1 };
         Ok(Self(BaseTopology::new(u, p, vs)))
     }
 
     fn configuration(&self) -> Result<Configuration, RuatomError> {
         if self.0.p == -1 {
-            return Ok(AL1);
+            Ok(AL1)
         } else if self.0.p == 1 {
-            return Ok(AL2);
+            Ok(AL2)
         } else {
-            return Err(RuatomError::IllegalMolecule(
+            Err(RuatomError::IllegalMolecule(
                 "invalid ExtendedTetrahedral configuration",
-            ));
+            ))
         }
     }
-    fn atom(&self) -> i8 {
-        return self.0.u.clone() as i8;
+    fn atom(&self) -> i64 {
+        self.0.u as i64
+    }
+    fn carriers(&self) -> &[i64] {
+        &self.0.vs
+    }
+    fn group(&self) -> StereoGroup {
+        self.0.group
+    }
+    fn set_group(&mut self, group: StereoGroup) {
+        self.0.group = group;
+    }
+    fn relabel(&self, u: AtomIdx, vs: Vec<i64>) -> Box<dyn Topology + Send + Sync> {
+        Box::new(Self(self.0.relabel(u, vs)))
     }
 
     fn seq(&self) -> TopologySeq {
         TopologySeq::ExtendedTetrahedral
     }
 
-    fn order_by(&self, ranks: &Vec<i8>) -> Option<Box<dyn Topology>> {
+    fn order_by(&self, ranks: &[usize]) -> Option<Box<dyn Topology>> {
         let mut ams = self.0.vs.clone();
         self.sort(&mut ams, ranks);
-        return Some(Box::new(Self(BaseTopology::new(
+        Some(Box::new(Self(BaseTopology::new(
             self.0.u,
             self.0.p * self.parity4(&self.0.vs, ranks),
             ams,
-        ))));
+        ))))
     }
 }
 
 pub struct SquarePlanar(BaseTopology, u8);
 
 impl Topology for SquarePlanar {
-    fn new_topology(u: u8, conf: Configuration, vs: Vec<i8>) -> Result<Self, RuatomError> {
+    fn new_topology(u: AtomIdx, conf: Configuration, vs: Vec<i64>) -> Result<Self, RuatomError> {
         if !conf.is_square_plannar() {
             return Err(RuatomError::IllegalMolecule(
                 "invalid SquarePlanar configuration",
             ));
         }
-        let p;
-        if conf.is_anti_clockwise() {
-            p = -1;
-        } else {
-            p = 1;
-        }
+        let p = if conf.is_anti_clockwise() { -1 } else { 1 };
         Ok(Self(BaseTopology::new(u, p, vs), conf.seq()))
     }
 
@@ -667,15 +723,27 @@ impl Topology for SquarePlanar {
             )),
         }
     }
-    fn atom(&self) -> i8 {
-        return self.0.u.clone() as i8;
+    fn atom(&self) -> i64 {
+        self.0.u as i64
+    }
+    fn carriers(&self) -> &[i64] {
+        &self.0.vs
+    }
+    fn group(&self) -> StereoGroup {
+        self.0.group
+    }
+    fn set_group(&mut self, group: StereoGroup) {
+        self.0.group = group;
+    }
+    fn relabel(&self, u: AtomIdx, vs: Vec<i64>) -> Box<dyn Topology + Send + Sync> {
+        Box::new(Self(self.0.relabel(u, vs), self.1))
     }
 
     fn seq(&self) -> TopologySeq {
         TopologySeq::SquarePlanar
     }
 
-    fn order_by(&self, ranks: &Vec<i8>) -> Option<Box<dyn Topology>> {
+    fn order_by(&self, ranks: &[usize]) -> Option<Box<dyn Topology>> {
         if self.1 < 1 || self.1 > 20 {
             return None;
         }
@@ -701,25 +769,20 @@ impl Topology for SquarePlanar {
             }
             ix += 1;
         }
-        return None;
+        None
     }
 }
 
 pub struct TrigonalBipyramidal(BaseTopology, u8);
 
 impl Topology for TrigonalBipyramidal {
-    fn new_topology(u: u8, conf: Configuration, vs: Vec<i8>) -> Result<Self, RuatomError> {
+    fn new_topology(u: AtomIdx, conf: Configuration, vs: Vec<i64>) -> Result<Self, RuatomError> {
         if conf.seq() < 1 && conf.seq() > 20 {
             return Err(RuatomError::IllegalMolecule(
                 "invalid TrigonalBipyramidal configuration",
             ));
         }
-        let p;
-        if conf.is_anti_clockwise() {
-            p = -1;
-        } else {
-            p = 1;
-        }
+        let p = if conf.is_anti_clockwise() { -1 } else { 1 };
         Ok(Self(BaseTopology::new(u, p, vs), conf.seq()))
     }
 
@@ -731,15 +794,27 @@ impl Topology for TrigonalBipyramidal {
             ))?)
         .clone())
     }
-    fn atom(&self) -> i8 {
-        return self.0.u.clone() as i8;
+    fn atom(&self) -> i64 {
+        self.0.u as i64
+    }
+    fn carriers(&self) -> &[i64] {
+        &self.0.vs
+    }
+    fn group(&self) -> StereoGroup {
+        self.0.group
+    }
+    fn set_group(&mut self, group: StereoGroup) {
+        self.0.group = group;
+    }
+    fn relabel(&self, u: AtomIdx, vs: Vec<i64>) -> Box<dyn Topology + Send + Sync> {
+        Box::new(Self(self.0.relabel(u, vs), self.1))
     }
 
     fn seq(&self) -> TopologySeq {
         TopologySeq::TrigonalBipyramidal
     }
 
-    fn order_by(&self, ranks: &Vec<i8>) -> Option<Box<dyn Topology>> {
+    fn order_by(&self, ranks: &[usize]) -> Option<Box<dyn Topology>> {
         let src = self.apply_inv(
             &self.0.vs,
             TRIGONALBIPYRAMIDALPERMUTATIONS[(self.1 - 1) as usize].as_slice(),
@@ -762,25 +837,20 @@ impl Topology for TrigonalBipyramidal {
             }
             ix += 1;
         }
-        return None;
+        None
     }
 }
 
 pub struct Octahedral(BaseTopology, u8);
 
 impl Topology for Octahedral {
-    fn new_topology(u: u8, conf: Configuration, vs: Vec<i8>) -> Result<Self, RuatomError> {
+    fn new_topology(u: AtomIdx, conf: Configuration, vs: Vec<i64>) -> Result<Self, RuatomError> {
         if conf.seq() < 1 && conf.seq() > 30 {
             return Err(RuatomError::IllegalMolecule(
                 "invalid Octahedral configuration",
             ));
         }
-        let p;
-        if conf.is_anti_clockwise() {
-            p = -1;
-        } else {
-            p = 1;
-        }
+        let p = if conf.is_anti_clockwise() { -1 } else { 1 };
         Ok(Self(BaseTopology::new(u, p, vs), conf.seq()))
     }
 
@@ -792,15 +862,27 @@ impl Topology for Octahedral {
             ))?)
         .clone())
     }
-    fn atom(&self) -> i8 {
-        return self.0.u.clone() as i8;
+    fn atom(&self) -> i64 {
+        self.0.u as i64
+    }
+    fn carriers(&self) -> &[i64] {
+        &self.0.vs
+    }
+    fn group(&self) -> StereoGroup {
+        self.0.group
+    }
+    fn set_group(&mut self, group: StereoGroup) {
+        self.0.group = group;
+    }
+    fn relabel(&self, u: AtomIdx, vs: Vec<i64>) -> Box<dyn Topology + Send + Sync> {
+        Box::new(Self(self.0.relabel(u, vs), self.1))
     }
 
     fn seq(&self) -> TopologySeq {
         TopologySeq::Octahedral
     }
 
-    fn order_by(&self, ranks: &Vec<i8>) -> Option<Box<dyn Topology>> {
+    fn order_by(&self, ranks: &[usize]) -> Option<Box<dyn Topology>> {
         let src = self.apply_inv(
             &self.0.vs,
             OCTAHEDRALPERMUTATIONS[(self.1 - 1) as usize].as_slice(),
@@ -817,33 +899,60 @@ impl Topology for Octahedral {
             }
             ix += 1;
         }
-        return None;
+        None
     }
 }
 
-pub struct UnknownTopology(u8);
+pub struct UnknownTopology(AtomIdx);
 
 impl Topology for UnknownTopology {
-    fn new_topology(u: u8, _conf: Configuration, _vs: Vec<i8>) -> Result<Self, RuatomError> {
+    fn new_topology(u: AtomIdx, _conf: Configuration, _vs: Vec<i64>) -> Result<Self, RuatomError> {
         Ok(Self(u))
     }
-    fn atom(&self) -> i8 {
-        return self.0 as i8;
+    fn atom(&self) -> i64 {
+        self.0 as i64
+    }
+    fn relabel(&self, u: AtomIdx, _vs: Vec<i64>) -> Box<dyn Topology + Send + Sync> {
+        Box::new(Self(u))
     }
 
     fn seq(&self) -> TopologySeq {
         TopologySeq::UnknownTopology
     }
-    fn order_by(&self, _ranks: &Vec<i8>) -> Option<Box<dyn Topology>> {
-        return Some(Box::new(Self(self.0)));
+    fn order_by(&self, _ranks: &[usize]) -> Option<Box<dyn Topology>> {
+        Some(Box::new(Self(self.0)))
+    }
+}
+
+/// Parity of the permutation taking `from` to `to`, `None` when the two are
+/// not permutations of each other.
+pub(crate) fn permutation_parity(from: &[i64], to: &[i64]) -> Option<i8> {
+    if from.len() != to.len() {
+        return None;
+    }
+    let mut perm = Vec::with_capacity(to.len());
+    for v in to.iter() {
+        perm.push(from.iter().position(|f| f == v)?);
+    }
+    let mut count = 0;
+    for ix in 0..perm.len() {
+        for jx in ix + 1..perm.len() {
+            if perm[jx] < perm[ix] {
+                count += 1;
+            }
+        }
+    }
+    if count & 0x1 == 1 {
+        return Some(-1);
     }
+    Some(1)
 }
 
 pub fn create(
-    u: u8,
+    u: AtomIdx,
     conf: Configuration,
-    vs: Vec<i8>,
-) -> Result<Box<dyn Topology + Sync>, RuatomError> {
+    vs: Vec<i64>,
+) -> Result<Box<dyn Topology + Send + Sync>, RuatomError> {
     if conf.is_tetrahedral() {
         return Ok(Box::new(Tetrahedral::new_topology(u, conf, vs)?));
     } else if conf.is_trigonal() {
@@ -857,5 +966,5 @@ pub fn create(
     } else if conf.is_octahedral() {
         return Ok(Box::new(Octahedral::new_topology(u, conf, vs)?));
     }
-    return Ok(Box::new(UnknownTopology::new_topology(u, conf, vs)?));
+    Ok(Box::new(UnknownTopology::new_topology(u, conf, vs)?))
 }
@@ -1,7 +1,9 @@
 use crate::error::Result;
-use crate::molecule::bond::{Bond, AROMATIC, IMPLICT, SINGLE};
-use crate::molecule::{Atom, Molecule};
+use crate::molecule::bond::{Bond, AROMATIC, DOUBLE, IMPLICT, SINGLE};
+use crate::molecule::kekule::{is_delocalised, kekule_bonds, sorted_pair};
+use crate::molecule::{Atom, AtomIdx, Molecule};
 use crate::molecule::{DB1, DB2};
+use std::collections::HashSet;
 
 pub fn collapse(mol: &Molecule) -> Result<Molecule> {
     let mol = implicit_to_explicit(mol)?;
@@ -15,6 +17,91 @@ pub fn expand(mol: &Molecule) -> Result<Molecule> {
     explicit_to_implicit(&mol)
 }
 
+/// Replaces aromatic atoms and bonds with an alternating single/double form.
+///
+/// Atoms that already have all their bond orders, `[nH]`, `o`, `[n+]([O-])`
+/// and the like, keep single bonds. Fails with
+/// [`RuatomError::NoKekuleStructure`](crate::error::RuatomError) when the
+/// double bonds cannot be placed.
+pub fn kekulize(mol: &Molecule) -> Result<Molecule> {
+    kekule_form(mol, &kekule_bonds(mol)?)
+}
+
+/// `mol` with the aromatic bonds written out as `doubles`, see [`kekulize`].
+pub(crate) fn kekule_form(mol: &Molecule, doubles: &HashSet<[AtomIdx; 2]>) -> Result<Molecule> {
+    let mut result = Molecule::new();
+
+    for &idx in mol.atoms() {
+        let atom = mol.atom_at(&idx)?;
+        if !atom.is_aromatic() {
+            result.add_atom(atom.clone())?;
+            continue;
+        }
+        let hydrogens = mol.hydrogen_count(&idx)?;
+        let mut sum = 0;
+        for n in mol.graph().neighbors(&idx)? {
+            sum += match doubles.contains(&sorted_pair(idx, *n)) {
+                true => 2,
+                false => mol.edge_at(idx, *n)?.electron(),
+            };
+        }
+        // [nH] and the like only needed brackets for the aromatic hydrogen
+        let plain = !atom.is_bracket_atom()
+            || (atom.isotope() < 0
+                && atom.charge() == 0
+                && atom.atom_class() == 0
+                && atom.element().is_subset_symbol()
+                && mol.topology_at(&idx).is_none());
+        let aliphatic = Atom::new_aliphatic(atom.element(), atom.is_organogen());
+        if plain && aliphatic.implict_hydrogen_amount(sum) == hydrogens {
+            result.add_atom(aliphatic)?;
+        } else {
+            let mut bracket = Atom::new_bracket(
+                atom.element(),
+                atom.isotope(),
+                hydrogens,
+                atom.charge(),
+                false,
+                atom.is_organogen(),
+            );
+            bracket.set_atom_class(atom.atom_class());
+            result.add_atom(bracket)?;
+        }
+    }
+
+    for &idx in mol.atoms() {
+        if let Some(topo) = mol.topology_at(&idx) {
+            let conf = topo.configuration()?;
+            let mut t = crate::molecule::topology::create(idx, conf, topo.carriers().to_vec())?;
+            t.set_group(topo.group());
+            result.add_topology(t);
+        }
+    }
+
+    for &idx in mol.atoms() {
+        for neighbor in mol.graph().neighbors(&idx)? {
+            if *neighbor > idx {
+                let b = mol.edge_at(idx, *neighbor)?;
+                let new_b = if doubles.contains(&[idx, *neighbor]) {
+                    DOUBLE
+                } else if b.is_aromatic() || is_delocalised(mol, idx, *neighbor)? {
+                    IMPLICT
+                } else {
+                    *b
+                };
+                result.add_bond(idx, *neighbor, new_b)?;
+            }
+        }
+    }
+
+    result.set_flags(mol.get_flag(0xFF) & !crate::molecule::HAS_AROM);
+    result.share_aromaticity(mol);
+    result.rings_detection()?;
+    result.symmetry_detection()?;
+    result.stereocenter_detection()?;
+    Ok(result)
+}
+
 pub fn atom_based_db_stereo(mol: &Molecule) -> Result<Molecule> {
     let mut result = Molecule::new();
 
@@ -23,16 +110,16 @@ pub fn atom_based_db_stereo(mol: &Molecule) -> Result<Molecule> {
         if let Some(topo) = mol.topology_at(&u) {
             // Only copy non-trigonal topologies (they will be replaced)
             if topo.seq() != crate::molecule::topology::TopologySeq::Trigonal {
-                let atom_idx = topo.atom() as u8;
+                let atom_idx = topo.atom() as AtomIdx;
                 let conf = topo.configuration().unwrap_or(crate::molecule::UNKNOWN);
                 let vs = match topo.seq() {
                     crate::molecule::topology::TopologySeq::Tetrahedral => {
                         // For tetrahedral, get the 4 vertices
                         vec![
-                            atom_idx as i8,
-                            atom_idx as i8,
-                            atom_idx as i8,
-                            atom_idx as i8,
+                            atom_idx as i64,
+                            atom_idx as i64,
+                            atom_idx as i64,
+                            atom_idx as i64,
                         ]
                     }
                     _ => vec![],
@@ -91,7 +178,7 @@ pub fn atom_based_db_stereo(mol: &Molecule) -> Result<Molecule> {
             } else {
                 atom.element().implicit_hydrogen_count(sum)
             };
-            let bracket = Atom::new_bracket(
+            let mut bracket = Atom::new_bracket(
                 atom.element(),
                 -1,
                 hydrogens as u8,
@@ -99,6 +186,7 @@ pub fn atom_based_db_stereo(mol: &Molecule) -> Result<Molecule> {
                 atom.is_aromatic(),
                 atom.is_organogen(),
             );
+            bracket.set_atom_class(atom.atom_class());
             result.add_atom(bracket)?;
             added = true;
         }
@@ -119,6 +207,7 @@ pub fn atom_based_db_stereo(mol: &Molecule) -> Result<Molecule> {
     }
 
     result.set_flags(mol.get_flag(0xFF));
+    result.share_aromaticity(mol);
     run_post_processing(&mut result)?;
     Ok(result)
 }
@@ -126,22 +215,22 @@ pub fn atom_based_db_stereo(mol: &Molecule) -> Result<Molecule> {
 pub fn bond_based_db_stereo(mol: &Molecule) -> Result<Molecule> {
     use std::collections::HashMap;
 
-    let mut visited: HashMap<u8, bool> = HashMap::new();
-    let mut ordering: HashMap<u8, usize> = HashMap::new();
-    let mut replacements: HashMap<(u8, u8), crate::molecule::bond::Bond> = HashMap::new();
+    let mut visited: HashMap<AtomIdx, bool> = HashMap::new();
+    let mut ordering: HashMap<AtomIdx, usize> = HashMap::new();
+    let mut replacements: HashMap<(AtomIdx, AtomIdx), crate::molecule::bond::Bond> = HashMap::new();
 
     fn visit(
         mol: &Molecule,
-        p: u8,
-        u: u8,
-        visited: &mut HashMap<u8, bool>,
-        ordering: &mut HashMap<u8, usize>,
-        replacements: &mut HashMap<(u8, u8), crate::molecule::bond::Bond>,
+        p: AtomIdx,
+        u: AtomIdx,
+        visited: &mut HashMap<AtomIdx, bool>,
+        ordering: &mut HashMap<AtomIdx, usize>,
+        replacements: &mut HashMap<(AtomIdx, AtomIdx), crate::molecule::bond::Bond>,
     ) {
         visited.insert(u, true);
 
         // collect neighbor list
-        let mut es: Vec<u8> = Vec::new();
+        let mut es: Vec<AtomIdx> = Vec::new();
         if let Ok(neighbors) = mol.graph().neighbors(&u) {
             for n in neighbors {
                 es.push(*n);
@@ -175,10 +264,10 @@ pub fn bond_based_db_stereo(mol: &Molecule) -> Result<Molecule> {
 
                 // Build ordering vector for order_by
                 let max_atom_idx = mol.atoms().iter().max().copied().unwrap_or(0) as usize;
-                let mut ordering_vec: Vec<i8> = vec![0; max_atom_idx + 1];
+                let mut ordering_vec: Vec<usize> = vec![0; max_atom_idx + 1];
                 for (&k, &v) in ordering.iter() {
-                    if k <= max_atom_idx as u8 {
-                        ordering_vec[k as usize] = v as i8;
+                    if k <= max_atom_idx as AtomIdx {
+                        ordering_vec[k as usize] = v;
                     }
                 }
 
@@ -221,7 +310,7 @@ pub fn bond_based_db_stereo(mol: &Molecule) -> Result<Molecule> {
                     if let Some(existing) = replacements.get(&key) {
                         if *existing != label {
                             // on conflict: collect all current labels for inversion
-                            let keys_to_invert: Vec<(u8, u8)> =
+                            let keys_to_invert: Vec<(AtomIdx, AtomIdx)> =
                                 replacements.keys().cloned().collect();
                             for &k in &keys_to_invert {
                                 if let Some(bond) = replacements.get(&k) {
@@ -295,24 +384,25 @@ pub fn bond_based_db_stereo(mol: &Molecule) -> Result<Molecule> {
     }
 
     result.set_flags(mol.get_flag(0xFF));
+    result.share_aromaticity(mol);
     run_post_processing(&mut result)?;
     Ok(result)
 }
 
 fn to_trigonal(
     mol: &Molecule,
-    u: u8,
-    v: u8,
-) -> Result<Box<dyn crate::molecule::topology::Topology + Sync>> {
+    u: AtomIdx,
+    v: AtomIdx,
+) -> Result<Box<dyn crate::molecule::topology::Topology + Send + Sync>> {
     // Collect neighbors into a Vec to allow indexing
-    let es: Vec<u8> = mol.graph().neighbors(&u)?.copied().collect();
+    let es: Vec<AtomIdx> = mol.graph().neighbors(&u)?.copied().collect();
     let offset = es.iter().position(|&x| x == v);
     if offset.is_none() {
         return crate::molecule::topology::create(u, crate::molecule::UNKNOWN, vec![]);
     }
     let offset = offset.unwrap();
 
-    let mut vs: Vec<i8> = vec![v as i8, u as i8, u as i8];
+    let mut vs: Vec<i64> = vec![v as i64, u as i64, u as i64];
 
     // Helper functions to check bond direction
     fn is_up(bond: &crate::molecule::bond::Bond) -> bool {
@@ -326,10 +416,10 @@ fn to_trigonal(
     if es.len() == 2 {
         let e1 = es[(offset + 1) % 2];
         let bond = mol.edge_at(u, e1)?;
-        if is_up(&bond) {
-            vs[1] = e1 as i8;
-        } else if is_down(&bond) {
-            vs[2] = e1 as i8;
+        if is_up(bond) {
+            vs[1] = e1 as i64;
+        } else if is_down(bond) {
+            vs[2] = e1 as i64;
         }
     } else if es.len() == 3 {
         let e1 = es[(offset + 1) % 3];
@@ -339,20 +429,20 @@ fn to_trigonal(
 
         // Check if b1 is single/implicit (non-directional)
         if b1.is("-") || b1.is("") {
-            if is_up(&b2) {
-                vs[1] = e2 as i8;
-                vs[2] = e1 as i8;
-            } else if is_down(&b2) {
-                vs[1] = e1 as i8;
-                vs[2] = e2 as i8;
+            if is_up(b2) {
+                vs[1] = e2 as i64;
+                vs[2] = e1 as i64;
+            } else if is_down(b2) {
+                vs[1] = e1 as i64;
+                vs[2] = e2 as i64;
             }
         } else {
-            if is_up(&b1) {
-                vs[1] = e1 as i8;
-                vs[2] = e2 as i8;
-            } else if is_down(&b1) {
-                vs[1] = e2 as i8;
-                vs[2] = e1 as i8;
+            if is_up(b1) {
+                vs[1] = e1 as i64;
+                vs[2] = e2 as i64;
+            } else if is_down(b1) {
+                vs[1] = e2 as i64;
+                vs[2] = e1 as i64;
             }
         }
     }
@@ -364,7 +454,7 @@ fn to_trigonal(
     // Use the corrected logic:
     // Configuration is DB2 if the double bond partner is at a lower index than the current atom
     // This ensures the left carbon in SMILES gets DB1 and the right carbon gets DB2
-    let double_bond_partner = es[offset as usize];
+    let double_bond_partner = es[offset];
     let conf = if double_bond_partner < u { DB2 } else { DB1 };
     crate::molecule::topology::create(u, conf, vs)
 }
@@ -383,21 +473,21 @@ pub fn explicit_to_implicit(mol: &Molecule) -> Result<Molecule> {
             let conf = topo.configuration().unwrap_or(crate::molecule::UNKNOWN);
             let vs = match topo.seq() {
                 crate::molecule::topology::TopologySeq::Tetrahedral => {
-                    vec![idx as i8, idx as i8, idx as i8, idx as i8]
+                    vec![idx as i64, idx as i64, idx as i64, idx as i64]
                 }
                 crate::molecule::topology::TopologySeq::Trigonal => {
                     let mut vs = Vec::new();
                     for n in mol.graph().neighbors(&idx)? {
                         if mol.edge_at(idx, *n)?.is("=") {
-                            vs.insert(0, *n as i8);
+                            vs.insert(0, *n as i64);
                         } else {
-                            vs.push(*n as i8);
+                            vs.push(*n as i64);
                         }
                     }
                     if vs.len() >= 3 {
                         vec![vs[0], vs[1], vs[2]]
                     } else {
-                        vec![0, idx as i8, idx as i8]
+                        vec![0, idx as i64, idx as i64]
                     }
                 }
                 _ => vec![],
@@ -421,6 +511,7 @@ pub fn explicit_to_implicit(mol: &Molecule) -> Result<Molecule> {
     }
 
     result.set_flags(mol.get_flag(0xFF));
+    result.share_aromaticity(mol);
 
     run_post_processing(&mut result)?;
 
@@ -458,21 +549,21 @@ pub fn implicit_to_explicit(mol: &Molecule) -> Result<Molecule> {
             let conf = topo.configuration().unwrap_or(crate::molecule::UNKNOWN);
             let vs = match topo.seq() {
                 crate::molecule::topology::TopologySeq::Tetrahedral => {
-                    vec![idx as i8, idx as i8, idx as i8, idx as i8]
+                    vec![idx as i64, idx as i64, idx as i64, idx as i64]
                 }
                 crate::molecule::topology::TopologySeq::Trigonal => {
                     let mut vs = Vec::new();
                     for n in mol.graph().neighbors(&idx)? {
                         if mol.edge_at(idx, *n)?.is("=") {
-                            vs.insert(0, *n as i8);
+                            vs.insert(0, *n as i64);
                         } else {
-                            vs.push(*n as i8);
+                            vs.push(*n as i64);
                         }
                     }
                     if vs.len() >= 3 {
                         vec![vs[0], vs[1], vs[2]]
                     } else {
-                        vec![0, idx as i8, idx as i8]
+                        vec![0, idx as i64, idx as i64]
                     }
                 }
                 _ => vec![],
@@ -496,6 +587,7 @@ pub fn implicit_to_explicit(mol: &Molecule) -> Result<Molecule> {
     }
 
     result.set_flags(mol.get_flag(0xFF));
+    result.share_aromaticity(mol);
 
     run_post_processing(&mut result)?;
 
@@ -541,13 +633,14 @@ fn to_subset_atoms(mol: &Molecule) -> Result<Molecule> {
     }
 
     result.set_flags(mol.get_flag(0xFF));
+    result.share_aromaticity(mol);
 
     run_post_processing(&mut result)?;
 
     Ok(result)
 }
 
-fn to_subset(atom: &Atom, mol: &Molecule, idx: u8) -> Result<Atom> {
+fn to_subset(atom: &Atom, mol: &Molecule, idx: AtomIdx) -> Result<Atom> {
     if atom.is_aliphatic() || atom.is_aromatic() {
         return Ok(atom.clone());
     }
@@ -556,7 +649,7 @@ fn to_subset(atom: &Atom, mol: &Molecule, idx: u8) -> Result<Atom> {
         return Ok(atom.clone());
     }
 
-    if atom.charge() != 0 || atom.isotope() >= 0 {
+    if atom.charge() != 0 || atom.isotope() >= 0 || atom.atom_class() > 0 {
         return Ok(atom.clone());
     }
 
@@ -597,6 +690,7 @@ fn from_subset_atoms(mol: &Molecule) -> Result<Molecule> {
     }
 
     result.set_flags(mol.get_flag(0xFF));
+    result.share_aromaticity(mol);
 
     run_post_processing(&mut result)?;
 
@@ -612,7 +706,7 @@ fn from_subset(atom: &Atom, valence: u8) -> Atom {
             if implicit_h == 0 {
                 return atom.clone();
             }
-            return Atom::new_bracket(
+            let mut bracket = Atom::new_bracket(
                 atom.element(),
                 -1,
                 implicit_h as u8,
@@ -620,12 +714,14 @@ fn from_subset(atom: &Atom, valence: u8) -> Atom {
                 true,
                 atom.is_organogen(),
             );
+            bracket.set_atom_class(atom.atom_class());
+            return bracket;
         } else {
             let implicit_h = atom.element().implicit_hydrogen_count(valence as i32);
             if implicit_h == 0 {
                 return atom.clone();
             }
-            return Atom::new_bracket(
+            let mut bracket = Atom::new_bracket(
                 atom.element(),
                 -1,
                 implicit_h as u8,
@@ -633,6 +729,8 @@ fn from_subset(atom: &Atom, valence: u8) -> Atom {
                 false,
                 atom.is_organogen(),
             );
+            bracket.set_atom_class(atom.atom_class());
+            return bracket;
         }
     }
 
@@ -0,0 +1,597 @@
+//! SMILES output.
+
+use super::{
+    canon::{canonical_ranks, double_bond_stereo},
+    kekule::ranked_kekule_bonds,
+    topology::{permutation_parity, Topology},
+    transform::kekule_form,
+    AtomIdx, Molecule,
+};
+use crate::error::Result;
+use std::collections::HashMap;
+
+/// How hydrogens are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Hydrogens {
+    /// Implied by the organic subset where possible, `[CH3+]` otherwise.
+    #[default]
+    Implicit,
+    /// Every atom in brackets with its hydrogen count, `[CH3][OH]`.
+    Bracket,
+    /// Hydrogens as `[H]` atoms, `C([H])([H])([H])O[H]`.
+    Atoms,
+}
+
+/// Writes molecules as SMILES, the default matches [`Molecule::to_smiles`].
+///
+/// ```
+/// use ruatom::molecule::SmilesWriter;
+/// use ruatom::Parser;
+///
+/// let m = Parser::new("[13CH3]c1ccccc1").parse().unwrap();
+/// let w = SmilesWriter::new().canonical(false).aromatic(false).isotopes(false);
+/// assert_eq!(w.write(&m).unwrap(), "CC1=CC=CC=C1");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmilesWriter {
+    canonical: bool,
+    aromatic: bool,
+    stereo: bool,
+    isotopes: bool,
+    atom_classes: bool,
+    hydrogens: Hydrogens,
+}
+
+impl Default for SmilesWriter {
+    fn default() -> Self {
+        Self {
+            canonical: true,
+            aromatic: true,
+            stereo: true,
+            isotopes: true,
+            atom_classes: true,
+            hydrogens: Hydrogens::Implicit,
+        }
+    }
+}
+
+impl SmilesWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Canonical atom order when on, the same molecule then giving the same
+    /// SMILES whatever order its atoms came in, atom index order otherwise.
+    pub fn canonical(mut self, on: bool) -> Self {
+        self.canonical = on;
+        self
+    }
+
+    /// Lowercase aromatic atoms when on, a Kekulé form otherwise. The form is
+    /// picked by the output order, canonical output then matching that of
+    /// the same Kekulé form read as is.
+    pub fn aromatic(mut self, on: bool) -> Self {
+        self.aromatic = on;
+        self
+    }
+
+    /// Chirality and double bond directions.
+    pub fn stereo(mut self, on: bool) -> Self {
+        self.stereo = on;
+        self
+    }
+
+    pub fn isotopes(mut self, on: bool) -> Self {
+        self.isotopes = on;
+        self
+    }
+
+    pub fn atom_classes(mut self, on: bool) -> Self {
+        self.atom_classes = on;
+        self
+    }
+
+    pub fn hydrogens(mut self, hydrogens: Hydrogens) -> Self {
+        self.hydrogens = hydrogens;
+        self
+    }
+
+    pub fn write(&self, mol: &Molecule) -> Result<String> {
+        if mol.atoms().is_empty() {
+            return Ok(String::new());
+        }
+        let ranks = match self.canonical {
+            true => canonical_ranks(mol, self.stereo)?,
+            false => (1..=mol.atoms().len()).collect(),
+        };
+        let aromatic = mol
+            .atoms()
+            .iter()
+            .any(|at| mol.atom_at(at).is_ok_and(|a| a.is_aromatic()));
+        if !self.aromatic && aromatic {
+            // double bonds placed by the ranks, then ranked again as a
+            // Kekulé molecule so Kekulé input writes the same
+            let kekule = kekule_form(mol, &ranked_kekule_bonds(mol, &ranks)?)?;
+            return self.write(&kekule);
+        }
+        let directions = match self.canonical && self.stereo {
+            true => Some(directions(mol, &ranks)?),
+            false => None,
+        };
+        let mut emitter = Emitter {
+            mol,
+            opts: self,
+            ranks,
+            directions,
+            dp: DataBus::new(),
+        };
+        emitter.write()
+    }
+}
+
+struct DataBus {
+    pub ancestors: Vec<AtomIdx>,
+    pub visited: Vec<AtomIdx>,
+    pub opening_closures: HashMap<AtomIdx, Vec<AtomIdx>>,
+    pub closing_closures: HashMap<AtomIdx, Vec<(AtomIdx, u8)>>,
+    pub dh: Vec<u8>,
+}
+
+/// Pending atom of the iterative SMILES writer.
+struct SmilesFrame {
+    atom: AtomIdx,
+    seq: String,
+    nbors: Vec<AtomIdx>,
+    index: usize,
+    branches: Vec<String>,
+}
+
+impl DataBus {
+    fn new() -> Self {
+        Self {
+            ancestors: vec![],
+            visited: vec![],
+            opening_closures: HashMap::<AtomIdx, Vec<AtomIdx>>::new(),
+            closing_closures: HashMap::<AtomIdx, Vec<(AtomIdx, u8)>>::new(),
+            dh: Vec::new(),
+        }
+    }
+
+    fn update_open<F>(&mut self, at: &AtomIdx, f: &mut F)
+    where
+        F: FnMut(u8),
+    {
+        if let Some(ocs) = self.opening_closures.get(at) {
+            for oc in ocs.iter() {
+                let mut digit: u8 = 1;
+                while digit < 100 {
+                    if !self.dh.contains(&digit) {
+                        break;
+                    }
+                    digit += 1;
+                }
+                self.dh.push(digit);
+                f(digit);
+                let oadts = self.closing_closures.entry(*oc).or_insert(vec![]);
+                oadts.push((*at, digit));
+            }
+        }
+    }
+
+    fn sort_close_and_delete<F>(&mut self, at: &AtomIdx, f: &mut F) -> Result<()>
+    where
+        F: FnMut(AtomIdx, u8) -> Result<()>,
+    {
+        if let Some(oadts) = self.closing_closures.get_mut(at) {
+            oadts.sort_by_key(|oadt| oadt.1);
+            for oadt in oadts.iter() {
+                f(oadt.0, oadt.1)?;
+                let index = self.dh.iter().position(|x| *x == oadt.1).unwrap();
+                self.dh.remove(index);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// State of one [`SmilesWriter::write`] call.
+struct Emitter<'a> {
+    mol: &'a Molecule,
+    opts: &'a SmilesWriter,
+    /// Output order, indexed by atom - 1.
+    ranks: Vec<usize>,
+    /// Whether `/` is written from the first atom to the second, replacing
+    /// the directional bonds as read when writing canonically.
+    directions: Option<HashMap<[AtomIdx; 2], bool>>,
+    dp: DataBus,
+}
+
+impl Emitter<'_> {
+    fn rank(&self, at: &AtomIdx) -> usize {
+        self.ranks[*at as usize - 1]
+    }
+
+    fn write(&mut self) -> Result<String> {
+        // one root per fragment, each starting from its lowest rank
+        let mut atoms: Vec<AtomIdx> = self.mol.atoms().clone();
+        atoms.sort_by_key(|at| self.rank(at));
+        let mut roots = vec![];
+        for at in atoms.into_iter() {
+            if !self.dp.visited.contains(&at) {
+                roots.push(at);
+                self.get_closures_for_atom(at)?;
+            }
+        }
+
+        self.dp.visited.clear();
+        let mut fragments = Vec::with_capacity(roots.len());
+        for root in roots.into_iter() {
+            fragments.push(self.build_smiles_for_atom(root)?);
+        }
+        Ok(fragments.join("."))
+    }
+
+    fn get_closures_for_atom(&mut self, root: AtomIdx) -> Result<()> {
+        self.dp.ancestors.push(root);
+        self.dp.visited.push(root);
+        let mut stack = vec![(root, self.written_neighbors(root, None)?, 0)];
+        while let Some((atom, nbors, index)) = stack.last_mut() {
+            let atom = *atom;
+            if *index == nbors.len() {
+                stack.pop();
+                self.dp.ancestors.retain(|x| *x != atom);
+                continue;
+            }
+            let nb = nbors[*index];
+            *index += 1;
+            if self.dp.ancestors.contains(&nb) {
+                self.dp.opening_closures.entry(nb).or_default().push(atom);
+            } else if !self.dp.visited.contains(&nb) {
+                self.dp.ancestors.push(nb);
+                self.dp.visited.push(nb);
+                let nbors = self.written_neighbors(nb, Some(atom))?;
+                stack.push((nb, nbors, 0));
+            }
+        }
+        Ok(())
+    }
+
+    /// Stereo shorthand of `at` relative to the order its neighbours are
+    /// written in.
+    fn written_configuration(
+        &self,
+        at: AtomIdx,
+        parent: Option<AtomIdx>,
+        nbors: &[AtomIdx],
+    ) -> Result<String> {
+        if !self.opts.stereo {
+            return Ok("".to_string());
+        }
+        match self.mol.topology_at(&at) {
+            None => Ok("".to_string()),
+            Some(top) => {
+                let order = self.written_order(at, parent, nbors, top);
+                let conf = top.configuration()?;
+                match permutation_parity(top.carriers(), &order) {
+                    Some(-1) => Ok(conf.inverse().shorthand().to_string()),
+                    _ => Ok(conf.shorthand().to_string()),
+                }
+            }
+        }
+    }
+
+    /// Neighbours of `at` in the order they appear around it in the written
+    /// SMILES, `at` itself standing for an implicit hydrogen or lone pair.
+    fn written_order(
+        &self,
+        at: AtomIdx,
+        parent: Option<AtomIdx>,
+        nbors: &[AtomIdx],
+        top: &(dyn Topology + Send + Sync),
+    ) -> Vec<i64> {
+        let mut order: Vec<i64> = parent.iter().map(|p| *p as i64).collect();
+        let implicit = top.carriers().contains(&(at as i64));
+        // a bracket hydrogen comes before the ring closures, a hydrogen atom
+        // is written as the first branch after them
+        let hydrogen_atom = implicit && self.opts.hydrogens == Hydrogens::Atoms;
+        if implicit && !hydrogen_atom {
+            order.push(at as i64);
+        }
+        let mut closures = vec![];
+        if let Some(oadts) = self.dp.closing_closures.get(&at) {
+            let mut oadts = oadts.clone();
+            oadts.sort_by_key(|oadt| oadt.1);
+            closures.extend(oadts.iter().map(|oadt| oadt.0));
+        }
+        if let Some(ocs) = self.dp.opening_closures.get(&at) {
+            closures.extend(ocs.iter());
+        }
+        order.extend(closures.iter().map(|c| *c as i64));
+        if hydrogen_atom {
+            order.push(at as i64);
+        }
+        for n in nbors.iter() {
+            if !closures.contains(n) {
+                order.push(*n as i64);
+            }
+        }
+        order
+    }
+
+    fn build_smiles_for_atom(&mut self, root: AtomIdx) -> Result<String> {
+        let nbors = self.written_neighbors(root, None)?;
+        let seq = self.build_smiles_head(root, None, &nbors)?;
+        let branches = self.hydrogen_branches(&root)?;
+        let mut stack = vec![SmilesFrame {
+            atom: root,
+            seq,
+            nbors,
+            index: 0,
+            branches,
+        }];
+        while let Some(frame) = stack.last_mut() {
+            if frame.index < frame.nbors.len() {
+                let nb = frame.nbors[frame.index];
+                let atom = frame.atom;
+                frame.index += 1;
+                if !self.dp.visited.contains(&nb) {
+                    let nbors = self.written_neighbors(nb, Some(atom))?;
+                    let seq = self.build_smiles_head(nb, Some(atom), &nbors)?;
+                    let branches = self.hydrogen_branches(&nb)?;
+                    stack.push(SmilesFrame {
+                        atom: nb,
+                        seq,
+                        nbors,
+                        index: 0,
+                        branches,
+                    });
+                }
+                continue;
+            }
+            let SmilesFrame {
+                mut seq, branches, ..
+            } = stack.pop().unwrap();
+            if let Some((last, rest)) = branches.split_last() {
+                for branch in rest.iter() {
+                    seq += &format!("({})", branch);
+                }
+                seq += last;
+            }
+            match stack.last_mut() {
+                Some(parent) => parent.branches.push(seq),
+                None => return Ok(seq),
+            }
+        }
+        Ok(String::new())
+    }
+
+    /// `[H]` branches of `at` when hydrogens are written as atoms.
+    fn hydrogen_branches(&self, at: &AtomIdx) -> Result<Vec<String>> {
+        if self.opts.hydrogens != Hydrogens::Atoms {
+            return Ok(vec![]);
+        }
+        let count = self.mol.hydrogen_count(at)? as usize;
+        Ok(vec!["[H]".to_string(); count])
+    }
+
+    /// Neighbours of `at` except `parent`, in output order.
+    fn written_neighbors(&self, at: AtomIdx, parent: Option<AtomIdx>) -> Result<Vec<AtomIdx>> {
+        let mut nbors = Vec::new();
+        for n in self.mol.graph().neighbors(&at)? {
+            if parent != Some(*n) {
+                nbors.push(*n);
+            }
+        }
+        nbors.sort_by_key(|idx| self.rank(idx));
+        Ok(nbors)
+    }
+
+    fn bond_token(&self, u: AtomIdx, v: AtomIdx) -> Result<&'static str> {
+        let bond = self.mol.edge_at(u, v)?;
+        if let Some(directions) = self.directions.as_ref() {
+            match directions.get(&[u, v]) {
+                Some(true) => return Ok("/"),
+                Some(false) => return Ok("\\"),
+                None if bond.direction() => return Ok(""),
+                None => (),
+            }
+        }
+        Ok(match bond.token() {
+            "/" | "\\" if !self.opts.stereo => "",
+            "/" => "/",
+            "\\" => "\\",
+            "" => "",
+            "-" => "-",
+            "=" => "=",
+            "#" => "#",
+            "$" => "$",
+            ":" => ":",
+            _ => "",
+        })
+    }
+
+    fn symbol(&self, at: &AtomIdx) -> Result<String> {
+        let atom = self.mol.atom_at(at)?;
+        match atom.is_aromatic() && self.opts.aromatic {
+            true => Ok(atom.element().symbol().to_lowercase()),
+            false => Ok(atom.element().symbol().to_string()),
+        }
+    }
+
+    /// Hydrogens the organic subset form of `at` implies.
+    fn implied_hydrogens(&self, at: &AtomIdx) -> Result<u8> {
+        let atom = self.mol.atom_at(at)?;
+        let e = atom.element();
+        let bonds = self.mol.valence(at)? - atom.explicit_hydrogens().min(self.mol.valence(at)?);
+        let bonds = match atom.is_bracket_atom() {
+            true => bonds,
+            false => self.mol.valence(at)?,
+        };
+        Ok(match atom.is_aromatic() {
+            true => e.implict_atom_hydrogen(bonds),
+            false => e.implict_hydrogen_amount(bonds),
+        })
+    }
+
+    /// Subset atoms carrying anything the short form cannot say are promoted
+    /// to brackets, brackets only needed for suppressed output are dropped.
+    fn written_in_brackets(&self, at: &AtomIdx) -> Result<bool> {
+        let opts = self.opts;
+        let atom = self.mol.atom_at(at)?;
+        if opts.hydrogens == Hydrogens::Bracket {
+            return Ok(true);
+        }
+        let isotope = opts.isotopes && atom.isotope() >= 0;
+        let class = opts.atom_classes && atom.atom_class() > 0;
+        let e = atom.element();
+        if isotope || class || atom.charge() != 0 || !e.is_subset_symbol() || e.atomic_number() == 1
+        {
+            return Ok(true);
+        }
+        let hydrogens = self.mol.hydrogen_count(at)?;
+        let implied = hydrogens == self.implied_hydrogens(at)?
+            || (opts.hydrogens == Hydrogens::Atoms && self.implied_hydrogens(at)? == 0);
+        if atom.is_bracket_atom() {
+            let suppressed = (!opts.stereo && self.mol.topology_at(at).is_some())
+                || (!opts.isotopes && atom.isotope() >= 0)
+                || (!opts.atom_classes && atom.atom_class() > 0);
+            return Ok(!(suppressed && implied));
+        }
+        Ok(atom.is_aliphatic() && !atom.element().has_default_valence(self.mol.valence(at)?))
+    }
+
+    /// Bond token, atom and ring closure digits written for `atom_current`.
+    fn build_smiles_head(
+        &mut self,
+        atom_current: AtomIdx,
+        atom_parent_opt: Option<AtomIdx>,
+        nbors: &[AtomIdx],
+    ) -> Result<String> {
+        self.dp.visited.push(atom_current);
+        let mut seq: String = String::from("");
+
+        if let Some(atom_parent) = atom_parent_opt {
+            seq += self.bond_token(atom_parent, atom_current)?;
+        }
+        let current = self.mol.atom_at(&atom_current)?;
+
+        let top = self.written_configuration(atom_current, atom_parent_opt, nbors)?;
+        if self.written_in_brackets(&atom_current)? {
+            let hydrogens = match self.opts.hydrogens {
+                Hydrogens::Atoms => 0,
+                _ => self.mol.hydrogen_count(&atom_current)?,
+            };
+            seq += "[";
+            if self.opts.isotopes && current.isotope() >= 0 {
+                seq += current.isotope().to_string().as_str();
+            }
+            seq += self.symbol(&atom_current)?.as_str();
+            seq += top.as_str();
+            if hydrogens > 0 {
+                seq += "H";
+                if hydrogens > 1 {
+                    seq += hydrogens.to_string().as_str();
+                }
+            }
+            if current.charge() < 0 {
+                seq += "-";
+                if current.charge().lt(&-1) {
+                    seq += (-current.charge()).to_string().as_str();
+                }
+            } else if current.charge() > 0 {
+                seq += "+";
+                if current.charge() > 1 {
+                    seq += current.charge().to_string().as_str();
+                }
+            }
+            if self.opts.atom_classes && current.atom_class() > 0 {
+                seq += ":";
+                seq += current.atom_class().to_string().as_str();
+            }
+            seq += "]";
+        } else {
+            seq += self.symbol(&atom_current)?.as_str();
+        }
+
+        let mut closes = vec![];
+        self.dp
+            .sort_close_and_delete(&atom_current, &mut |one, two| {
+                closes.push((one, two));
+                Ok(())
+            })?;
+        for (one, two) in closes {
+            seq += self.bond_token(atom_current, one)?;
+            if two > 9 {
+                seq += "%";
+            }
+            seq += &two.to_string();
+        }
+        self.dp.update_open(&atom_current, &mut |d: u8| {
+            if d > 9 {
+                seq += "%";
+            }
+            seq += &d.to_string();
+        });
+
+        Ok(seq)
+    }
+}
+
+/// Marks for the stereo double bonds in rank order. An end with a bond
+/// already marked for a conjugated double bond keeps it, otherwise its first
+/// substituent is marked, up from the lower ranked atom when neither end is.
+fn directions(mol: &Molecule, ranks: &[usize]) -> Result<HashMap<[AtomIdx; 2], bool>> {
+    let rank = |at: &AtomIdx| ranks[*at as usize - 1];
+    let substituents = |u: AtomIdx, v: AtomIdx| -> Result<Vec<AtomIdx>> {
+        let mut subs: Vec<AtomIdx> = mol
+            .graph()
+            .neighbors(&u)?
+            .copied()
+            .filter(|a| *a != v)
+            .collect();
+        subs.sort_by_key(rank);
+        Ok(subs)
+    };
+    let mut stereo = double_bond_stereo(mol)?;
+    stereo.sort_by_key(|db| rank(&db.u).min(rank(&db.v)));
+    let mut up: HashMap<[AtomIdx; 2], bool> = HashMap::new();
+    for db in stereo.into_iter() {
+        let marked = |u: AtomIdx, v: AtomIdx| -> Result<Option<AtomIdx>> {
+            Ok(substituents(u, v)?
+                .into_iter()
+                .find(|x| up.contains_key(&[*x, u])))
+        };
+        let (u, v) = match (marked(db.u, db.v)?, marked(db.v, db.u)?) {
+            (None, Some(_)) => (db.v, db.u),
+            (None, None) if rank(&db.v) < rank(&db.u) => (db.v, db.u),
+            _ => (db.u, db.v),
+        };
+        let x = match marked(u, v)? {
+            Some(x) => x,
+            None => {
+                let x = substituents(u, v)?[0];
+                up.insert([x, u], rank(&x) < rank(&u));
+                up.insert([u, x], rank(&x) >= rank(&u));
+                x
+            }
+        };
+        let dx = up[&[x, u]];
+        // `/` from `x` to `u` and from `v` to `y` when trans
+        let wanted = |y: AtomIdx| {
+            let trans = match u == db.u {
+                true => db.trans_of(x, y),
+                false => db.trans_of(y, x),
+            };
+            trans == dx
+        };
+        let subs = substituents(v, u)?;
+        if subs.iter().any(|y| up.get(&[v, *y]) == Some(&wanted(*y))) {
+            continue;
+        }
+        if let Some(y) = subs.iter().find(|y| !up.contains_key(&[v, **y])) {
+            up.insert([v, *y], wanted(*y));
+            up.insert([*y, v], !wanted(*y));
+        }
+    }
+    Ok(up)
+}
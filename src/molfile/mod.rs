@@ -0,0 +1,676 @@
+//! MDL molfile connection tables.
+//!
+//! Stereo is read from wedge and hash bonds of 2D blocks, from the geometry
+//! of 3D blocks, and from the atom parity column when all coordinates are
+//! zero. Written blocks carry the stored coordinates, wedges for 2D
+//! coordinates and atom parities; a molecule without coordinates keeps its
+//! tetrahedral centres through the parities only.
+//!
+//! V3000 blocks also carry enhanced stereo collections, which end up as the
+//! [`StereoGroup`] of each configuration, and keep SGroups and collections
+//! they can't interpret as [`Molecule::opaque_blocks`].
+
+mod v2000;
+mod v3000;
+
+use crate::error::{Result, RuatomError};
+use crate::molecule::{
+    atom::Atom,
+    bond::{DOUBLE, DOWN, IMPLICT, TRIPLE, UP},
+    create,
+    element::{Element, Specification, ANY, H},
+    topology::permutation_parity,
+    AtomIdx, Configuration, Molecule, StereoGroup, HAS_ATM_STRO, HAS_BND_STRO, TH1, TH2,
+};
+use std::collections::HashMap;
+
+/// Reads a V2000 or V3000 molfile block into a [`Molecule`].
+pub fn read_molfile(block: &str) -> Result<Molecule> {
+    let lines: Vec<&str> = block.lines().collect();
+    let counts = lines
+        .get(3)
+        .ok_or(RuatomError::IllegalMolfile(4, "missing counts line"))?;
+    let ctab = if counts.get(33..39).map(str::trim) == Some("V3000") {
+        v3000::parse(&lines)?
+    } else {
+        v2000::parse(&lines)?
+    };
+    build(&ctab)
+}
+
+/// Writes `mol` as a molfile block ending with `M  END`.
+///
+/// The block is V2000 unless the molecule needs V3000: more than 999 atoms
+/// or bonds, enhanced stereo groups or opaque blocks.
+pub fn write_molfile(mol: &Molecule) -> Result<String> {
+    let ctab = tabulate(mol)?;
+    if ctab.atoms.len() > 999
+        || ctab.bonds.len() > 999
+        || !ctab.blocks.is_empty()
+        || ctab.groups.iter().any(|g| g.0 != StereoGroup::Absolute)
+    {
+        return Ok(v3000::format(&ctab));
+    }
+    v2000::format(&ctab)
+}
+
+/// Writes `mol` as a V3000 molfile block ending with `M  END`.
+pub fn write_molfile_v3000(mol: &Molecule) -> Result<String> {
+    Ok(v3000::format(&tabulate(mol)?))
+}
+
+/// Atom block entry.
+#[derive(Debug, Clone)]
+pub(crate) struct AtomRecord {
+    pub symbol: String,
+    pub xyz: [f64; 3],
+    pub charge: i8,
+    /// Mass number, -1 for the natural abundance.
+    pub isotope: i16,
+    pub radical: u8,
+    /// Total valence, `Some(0)` forbids implicit hydrogens.
+    pub valence: Option<u8>,
+    pub parity: u8,
+}
+
+impl AtomRecord {
+    pub(crate) fn new(symbol: &str, xyz: [f64; 3]) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            xyz,
+            charge: 0,
+            isotope: -1,
+            radical: 0,
+            valence: None,
+            parity: 0,
+        }
+    }
+}
+
+/// Bond block entry, `order` 4 is aromatic and `stereo` keeps the V2000
+/// codes: 1 wedge, 4 either, 6 hash on single bonds, 3 either on double
+/// bonds.
+#[derive(Debug, Clone)]
+pub(crate) struct BondRecord {
+    pub u: AtomIdx,
+    pub v: AtomIdx,
+    pub order: u8,
+    pub stereo: u8,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Ctab {
+    pub atoms: Vec<AtomRecord>,
+    pub bonds: Vec<BondRecord>,
+    pub chiral: bool,
+    /// Enhanced stereo collections.
+    pub groups: Vec<(StereoGroup, Vec<AtomIdx>)>,
+    /// Blocks kept verbatim as `(name, lines)`.
+    pub blocks: Vec<(String, Vec<String>)>,
+}
+
+impl Ctab {
+    fn is_3d(&self) -> bool {
+        self.atoms.iter().any(|a| a.xyz[2] != 0.0)
+    }
+
+    fn has_coordinates(&self) -> bool {
+        self.atoms.iter().any(|a| a.xyz != [0.0; 3])
+    }
+}
+
+pub(crate) fn build(ctab: &Ctab) -> Result<Molecule> {
+    let n = ctab.atoms.len();
+    let mut sums = vec![0u8; n];
+    let mut degrees = vec![0u8; n];
+    let mut aromatic = vec![false; n];
+    for b in ctab.bonds.iter() {
+        if b.u == b.v || b.u == 0 || b.v == 0 || b.u as usize > n || b.v as usize > n {
+            return Err(RuatomError::IllegalMolecule("bond to a missing atom"));
+        }
+        if !(1..=4).contains(&b.order) {
+            return Err(RuatomError::IllegalMolecule("unsupported bond order"));
+        }
+        for w in [b.u, b.v] {
+            count_bond(&mut sums, &mut degrees, w, b.order)?;
+            aromatic[w as usize - 1] |= b.order == 4;
+        }
+    }
+
+    let mut mol = Molecule::new();
+    for (ix, rec) in ctab.atoms.iter().enumerate() {
+        let sum = bond_sum(sums[ix], degrees[ix], aromatic[ix]);
+        let at = mol.add_atom(new_atom(rec, aromatic[ix], sum)?)?;
+        mol.set_coordinates(at, rec.xyz);
+    }
+    for b in ctab.bonds.iter() {
+        let bond = match b.order {
+            2 => DOUBLE,
+            3 => TRIPLE,
+            _ => IMPLICT,
+        };
+        mol.add_bond(b.u, b.v, bond)?;
+    }
+    mol.refresh()?;
+    if assign_stereo(&mut mol, ctab)? {
+        mol.refresh()?;
+    }
+    for (group, atoms) in ctab.groups.iter() {
+        for at in atoms.iter() {
+            if mol.topology_at(at).is_some() {
+                mol.set_stereo_group(at, *group)?;
+            }
+        }
+    }
+    for (name, lines) in ctab.blocks.iter() {
+        mol.add_opaque_block(name, lines.clone());
+    }
+    Ok(mol)
+}
+
+pub(crate) fn tabulate(mol: &Molecule) -> Result<Ctab> {
+    let has_xyz =
+        !mol.atoms().is_empty() && mol.atoms().iter().all(|a| mol.coordinates(a).is_some());
+    let mut ctab = Ctab::default();
+
+    for [u, v] in mol.bonds().iter() {
+        let bond = mol.edge_at(*u, *v)?;
+        let order = match bond.electron() {
+            1 if !bond.is("-")
+                && !bond.direction()
+                && bond.ring_membership() > 0
+                && mol.atom_at(u)?.is_aromatic()
+                && mol.atom_at(v)?.is_aromatic() =>
+            {
+                4
+            }
+            1 => 1,
+            2 => 2,
+            3 => 3,
+            _ => return Err(RuatomError::IllegalMolecule("bond order not representable")),
+        };
+        ctab.bonds.push(BondRecord {
+            u: *u,
+            v: *v,
+            order,
+            stereo: 0,
+        });
+    }
+
+    let n = mol.atoms().len();
+    let mut sums = vec![0u8; n];
+    let mut degrees = vec![0u8; n];
+    let mut aromatic = vec![false; n];
+    for b in ctab.bonds.iter() {
+        for w in [b.u, b.v] {
+            count_bond(&mut sums, &mut degrees, w, b.order)?;
+            aromatic[w as usize - 1] |= b.order == 4;
+        }
+    }
+
+    for at in mol.atoms().iter() {
+        let ix = *at as usize - 1;
+        let atom = mol.atom_at(at)?;
+        let symbol = if atom.ele_is_any() {
+            "A".to_string()
+        } else {
+            atom.element().symbol().to_string()
+        };
+        let xyz = if has_xyz {
+            mol.coordinates(at).unwrap()
+        } else {
+            [0.0; 3]
+        };
+        let mut rec = AtomRecord::new(&symbol, xyz);
+        rec.charge = atom.charge();
+        rec.isotope = if atom.isotope() > 0 {
+            atom.isotope()
+        } else {
+            -1
+        };
+        rec.radical = atom.radical();
+
+        let sum = bond_sum(sums[ix], degrees[ix], aromatic[ix]);
+        let inferred = new_atom(&rec, aromatic[ix], sum)?;
+        let hydrogens = mol.hydrogen_count(at)?;
+        if inferred.implict_hydrogen_amount(sum) + inferred.explicit_hydrogens() != hydrogens {
+            rec.valence = Some(sum + hydrogens);
+        }
+        if let Some((carriers, conf)) = tetrahedral_of(mol, *at)? {
+            let mut sorted: Vec<i64> = carriers.clone();
+            sorted.sort_by_key(|c| if *c == *at as i64 { i64::MAX } else { *c });
+            let conf = match permutation_parity(&carriers, &sorted) {
+                Some(-1) => conf.inverse(),
+                _ => conf,
+            };
+            rec.parity = if conf == TH1 { 2 } else { 1 };
+            ctab.chiral = true;
+        }
+        ctab.atoms.push(rec);
+    }
+
+    let mut groups: Vec<(StereoGroup, Vec<AtomIdx>)> = vec![];
+    for at in mol.atoms().iter() {
+        if let Some(t) = mol.topology_at(at) {
+            match groups.iter_mut().find(|g| g.0 == t.group()) {
+                Some(g) => g.1.push(*at),
+                None => groups.push((t.group(), vec![*at])),
+            }
+        }
+    }
+    ctab.groups = groups;
+    ctab.blocks = mol.opaque_blocks().to_vec();
+
+    if has_xyz && !ctab.is_3d() {
+        assign_wedges(mol, &mut ctab)?;
+    }
+    Ok(ctab)
+}
+
+/// Adds a bond of `order` to the bond order sum and degree of `at`.
+fn count_bond(sums: &mut [u8], degrees: &mut [u8], at: AtomIdx, order: u8) -> Result<()> {
+    let ix = at as usize - 1;
+    let order = if order == 4 { 1 } else { order };
+    match (sums[ix].checked_add(order), degrees[ix].checked_add(1)) {
+        (Some(sum), Some(degree)) => {
+            sums[ix] = sum;
+            degrees[ix] = degree;
+            Ok(())
+        }
+        _ => Err(RuatomError::IllegalMolecule("too many bonds to one atom")),
+    }
+}
+
+/// Bond order sum used for implicit hydrogens, aromatic atoms with only
+/// single electron bonds count one more as [`Molecule::valence`] does.
+fn bond_sum(sum: u8, degree: u8, aromatic: bool) -> u8 {
+    if aromatic && sum == degree {
+        sum + 1
+    } else {
+        sum
+    }
+}
+
+fn new_atom(rec: &AtomRecord, aromatic: bool, sum: u8) -> Result<Atom> {
+    let (element, isotope) = match rec.symbol.as_str() {
+        "D" => (H, 2),
+        "T" => (H, 3),
+        "A" | "Q" | "*" => return Ok(Atom::new_any(ANY, true)),
+        s => (
+            Element::read(s).ok_or_else(|| RuatomError::NotFoundSymbolError(s.to_string()))?,
+            rec.isotope,
+        ),
+    };
+    let aromatic = aromatic && element.is_aromatic(Specification::OpenSMILES);
+    if rec.charge == 0
+        && isotope < 0
+        && rec.radical == 0
+        && rec.valence.is_none()
+        && element.is_subset_symbol()
+    {
+        if aromatic {
+            return Ok(Atom::new_aromatic(element, true));
+        }
+        return Ok(Atom::new_aliphatic(element, true));
+    }
+    let hydrogens = match rec.valence {
+        Some(v) => v.saturating_sub(sum),
+        None => default_hydrogens(&element, rec.charge, rec.radical, sum),
+    };
+    let organogen = element.is_organogen();
+    let mut atom = Atom::new_bracket(element, isotope, hydrogens, rec.charge, aromatic, organogen);
+    atom.set_radical(rec.radical);
+    Ok(atom)
+}
+
+/// Implicit hydrogens of an atom without an explicit valence, following the
+/// MDL valence model for the non-metals. Metals get none.
+fn default_hydrogens(e: &Element, charge: i8, radical: u8, sum: u8) -> u8 {
+    let ve: i16 = match e.atomic_number() {
+        1 => 1,
+        5 => 3,
+        6 | 14 | 32 => 4,
+        7 | 15 | 33 | 51 => 5,
+        8 | 16 | 34 | 52 => 6,
+        9 | 17 | 35 | 53 | 85 => 7,
+        _ => return 0,
+    };
+    let effective = ve - charge as i16;
+    if effective < 0 {
+        return 0;
+    }
+    let unpaired = match radical {
+        2 => 1,
+        1 | 3 => 2,
+        _ => 0,
+    };
+    let base = if e.atomic_number() == 1 {
+        if effective == 1 {
+            1
+        } else {
+            0
+        }
+    } else if effective <= 4 {
+        effective
+    } else {
+        8 - effective
+    };
+    let mut valence = base;
+    loop {
+        let v = valence - unpaired;
+        if v >= sum as i16 {
+            return (v - sum as i16) as u8;
+        }
+        valence += 2;
+        if e.atomic_number() <= 10 || valence > effective {
+            return 0;
+        }
+    }
+}
+
+/// Tetrahedral topology at `at` as sorted carriers and configuration.
+fn tetrahedral_of(mol: &Molecule, at: AtomIdx) -> Result<Option<(Vec<i64>, Configuration)>> {
+    match mol.topology_at(&at) {
+        Some(t) if t.carriers().len() == 4 => {
+            let conf = t.configuration()?;
+            if !conf.is_tetrahedral() {
+                return Ok(None);
+            }
+            Ok(Some((t.carriers().to_vec(), conf)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Neighbours of a possible tetrahedral centre, `None` when `at` can't carry
+/// one.
+fn tetrahedral_neighbors(mol: &Molecule, at: AtomIdx) -> Result<Option<Vec<AtomIdx>>> {
+    let mut nbors: Vec<AtomIdx> = mol.graph().neighbors(&at)?.copied().collect();
+    nbors.sort();
+    let hydrogens = mol.hydrogen_count(&at)?;
+    match nbors.len() {
+        4 if hydrogens == 0 => Ok(Some(nbors)),
+        3 if hydrogens <= 1 => Ok(Some(nbors)),
+        _ => Ok(None),
+    }
+}
+
+/// Configuration of `nbors` around `at` from their directions, the implicit
+/// hydrogen or lone pair pointing away from the others.
+fn tetrahedral_from(
+    at: AtomIdx,
+    nbors: &[AtomIdx],
+    dirs: &[[f64; 3]],
+) -> Option<(Vec<i64>, Configuration)> {
+    let mut carriers: Vec<i64> = nbors.iter().map(|n| *n as i64).collect();
+    let mut points: Vec<[f64; 3]> = dirs.iter().map(unit).collect();
+    if points.iter().any(|p| p.iter().all(|c| c.is_nan())) {
+        return None;
+    }
+    if carriers.len() == 3 {
+        let mut h = [0.0; 3];
+        for p in points.iter() {
+            for k in 0..3 {
+                h[k] -= p[k];
+            }
+        }
+        carriers.push(at as i64);
+        points.push(unit(&h));
+    }
+    let [a, b, c, d] = [points[0], points[1], points[2], points[3]];
+    let volume = dot(&sub(&b, &a), &cross(&sub(&c, &a), &sub(&d, &a)));
+    if volume.is_nan() || volume.abs() < 1e-3 {
+        return None;
+    }
+    Some((carriers, if volume < 0.0 { TH1 } else { TH2 }))
+}
+
+fn sub(a: &[f64; 3], b: &[f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: &[f64; 3], b: &[f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn unit(a: &[f64; 3]) -> [f64; 3] {
+    let norm = dot(a, a).sqrt();
+    [a[0] / norm, a[1] / norm, a[2] / norm]
+}
+
+/// Perceives tetrahedral centres and double bond configurations, returns
+/// whether any were found.
+fn assign_stereo(mol: &mut Molecule, ctab: &Ctab) -> Result<bool> {
+    let pos = |at: AtomIdx| ctab.atoms[at as usize - 1].xyz;
+    let mut centres = vec![];
+    let atoms = mol.atoms().clone();
+    if !ctab.has_coordinates() {
+        for at in atoms.iter() {
+            let parity = ctab.atoms[*at as usize - 1].parity;
+            if parity != 1 && parity != 2 {
+                continue;
+            }
+            if let Some(nbors) = tetrahedral_neighbors(mol, *at)? {
+                let mut carriers: Vec<i64> = nbors.iter().map(|n| *n as i64).collect();
+                if carriers.len() == 3 {
+                    carriers.push(*at as i64);
+                }
+                centres.push((*at, carriers, if parity == 1 { TH2 } else { TH1 }));
+            }
+        }
+    } else if ctab.is_3d() {
+        for at in atoms.iter() {
+            if !mol.atom_at(at)?.is_stereocenter() {
+                continue;
+            }
+            if let Some(nbors) = tetrahedral_neighbors(mol, *at)? {
+                let dirs: Vec<[f64; 3]> = nbors.iter().map(|n| sub(&pos(*n), &pos(*at))).collect();
+                if let Some((carriers, conf)) = tetrahedral_from(*at, &nbors, &dirs) {
+                    centres.push((*at, carriers, conf));
+                }
+            }
+        }
+    } else {
+        let mut wedges: HashMap<AtomIdx, Vec<(AtomIdx, f64)>> = HashMap::new();
+        for b in ctab.bonds.iter() {
+            let dz = match b.stereo {
+                1 => 1.0,
+                6 => -1.0,
+                _ => continue,
+            };
+            if b.order == 1 {
+                wedges.entry(b.u).or_default().push((b.v, dz));
+            }
+        }
+        let mut keys: Vec<AtomIdx> = wedges.keys().copied().collect();
+        keys.sort();
+        for at in keys {
+            if let Some(nbors) = tetrahedral_neighbors(mol, at)? {
+                let dirs: Vec<[f64; 3]> = nbors
+                    .iter()
+                    .map(|n| {
+                        let d = sub(&pos(*n), &pos(at));
+                        let mut d = unit(&[d[0], d[1], 0.0]);
+                        if let Some((_, dz)) = wedges[&at].iter().find(|w| w.0 == *n) {
+                            d[2] = *dz;
+                        }
+                        d
+                    })
+                    .collect();
+                if let Some((carriers, conf)) = tetrahedral_from(at, &nbors, &dirs) {
+                    centres.push((at, carriers, conf));
+                }
+            }
+        }
+    }
+
+    let found = !centres.is_empty();
+    for (at, carriers, conf) in centres {
+        let hydrogens = mol.hydrogen_count(&at)?;
+        mol.add_topology(create(at, conf, carriers)?);
+        let atom = mol.atom_mut(&at)?;
+        if let Some(mut bracket) = atom.to_bracket() {
+            bracket.set_explicit_hydrogens(hydrogens);
+            *atom = bracket;
+        }
+    }
+    if found {
+        mol.set_flags(HAS_ATM_STRO);
+    }
+    if !ctab.has_coordinates() {
+        return Ok(found);
+    }
+
+    let mut directional = false;
+    for b in ctab.bonds.iter() {
+        if b.order != 2 || b.stereo == 3 || mol.edge_at(b.u, b.v)?.ring_membership() > 0 {
+            continue;
+        }
+        let (u, v) = (b.u, b.v);
+        let (Some(us), Some(vs)) = (substituents(mol, u, v)?, substituents(mol, v, u)?) else {
+            continue;
+        };
+        let axis = sub(&pos(v), &pos(u));
+        let perp = |d: [f64; 3]| {
+            let k = dot(&d, &axis) / dot(&axis, &axis);
+            [d[0] - k * axis[0], d[1] - k * axis[1], d[2] - k * axis[2]]
+        };
+        let a = us
+            .iter()
+            .copied()
+            .find(|a| direction_of(mol, *a, u).is_some())
+            .unwrap_or(us[0]);
+        let b = vs
+            .iter()
+            .copied()
+            .find(|b| direction_of(mol, v, *b).is_none())
+            .unwrap_or(vs[0]);
+        let cos = dot(&perp(sub(&pos(a), &pos(u))), &perp(sub(&pos(b), &pos(v))));
+        if cos.abs() < 1e-6 {
+            continue;
+        }
+        let su = match direction_of(mol, a, u) {
+            Some(s) => s,
+            None => {
+                set_direction(mol, a, u, 1)?;
+                1
+            }
+        };
+        let sv = if cos < 0.0 { su } else { -su };
+        match direction_of(mol, v, b) {
+            Some(s) if s != sv => continue,
+            Some(_) => {}
+            None => set_direction(mol, v, b, sv)?,
+        }
+        directional = true;
+    }
+    if directional {
+        mol.set_flags(HAS_BND_STRO);
+    }
+    Ok(found || directional)
+}
+
+/// Single bonded neighbours of `u` other than `v`, `None` when the end of
+/// the double bond `u=v` can't carry a configuration.
+fn substituents(mol: &Molecule, u: AtomIdx, v: AtomIdx) -> Result<Option<Vec<AtomIdx>>> {
+    let mut subs = vec![];
+    for n in mol.graph().neighbors(&u)? {
+        if *n == v {
+            continue;
+        }
+        let bond = mol.edge_at(u, *n)?;
+        if bond.electron() != 1 || bond.is_aromatic() {
+            return Ok(None);
+        }
+        subs.push(*n);
+    }
+    subs.sort();
+    if subs.is_empty() || subs.len() > 2 || mol.hydrogen_count(&u)? + subs.len() as u8 > 2 {
+        return Ok(None);
+    }
+    if subs.len() == 2
+        && mol.atom_at(&subs[0])?.symmetry_class() == mol.atom_at(&subs[1])?.symmetry_class()
+    {
+        return Ok(None);
+    }
+    Ok(Some(subs))
+}
+
+/// `1` when the bond reads `a/b`, `-1` for `a\b`.
+fn direction_of(mol: &Molecule, a: AtomIdx, b: AtomIdx) -> Option<i8> {
+    match mol.edge_at(a, b) {
+        Ok(bond) if bond.is("/") => Some(1),
+        Ok(bond) if bond.is("\\") => Some(-1),
+        _ => None,
+    }
+}
+
+fn set_direction(mol: &mut Molecule, a: AtomIdx, b: AtomIdx, s: i8) -> Result<()> {
+    let bond = if s > 0 { UP } else { DOWN };
+    *mol.edge_mut(a, b)? = bond;
+    *mol.edge_mut(b, a)? = bond.inverse();
+    Ok(())
+}
+
+/// Marks one single bond of every tetrahedral centre as wedge or hash so
+/// the 2D coordinates reproduce its configuration.
+fn assign_wedges(mol: &Molecule, ctab: &mut Ctab) -> Result<()> {
+    let pos = |at: AtomIdx| ctab.atoms[at as usize - 1].xyz;
+    let mut flags: Vec<(usize, AtomIdx, u8)> = vec![];
+    for at in mol.atoms().iter() {
+        let Some((_, conf)) = tetrahedral_of(mol, *at)? else {
+            continue;
+        };
+        let Some(nbors) = tetrahedral_neighbors(mol, *at)? else {
+            continue;
+        };
+        let mut candidates: Vec<(usize, AtomIdx)> = ctab
+            .bonds
+            .iter()
+            .enumerate()
+            .filter(|(ix, b)| {
+                b.order == 1 && (b.u == *at || b.v == *at) && !flags.iter().any(|f| f.0 == *ix)
+            })
+            .map(|(ix, b)| (ix, if b.u == *at { b.v } else { b.u }))
+            .collect();
+        candidates.sort_by_key(|(_, n)| (tetrahedral_of(mol, *n).ok().flatten().is_some(), *n));
+        for (ix, n) in candidates {
+            let dirs: Vec<[f64; 3]> = nbors
+                .iter()
+                .map(|w| {
+                    let d = sub(&pos(*w), &pos(*at));
+                    let mut d = unit(&[d[0], d[1], 0.0]);
+                    if *w == n {
+                        d[2] = 1.0;
+                    }
+                    d
+                })
+                .collect();
+            if let Some((carriers, wedged)) = tetrahedral_from(*at, &nbors, &dirs) {
+                let stored = mol.topology_at(at).unwrap();
+                let wedged = match permutation_parity(&carriers, stored.carriers()) {
+                    Some(-1) => wedged.inverse(),
+                    _ => wedged,
+                };
+                flags.push((ix, *at, if wedged == conf { 1 } else { 6 }));
+                break;
+            }
+        }
+    }
+    for (ix, at, stereo) in flags {
+        let b = &mut ctab.bonds[ix];
+        if b.v == at {
+            std::mem::swap(&mut b.u, &mut b.v);
+        }
+        b.stereo = stereo;
+    }
+    Ok(())
+}
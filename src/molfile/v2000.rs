@@ -0,0 +1,229 @@
+use super::{AtomRecord, BondRecord, Ctab};
+use crate::error::{Result, RuatomError};
+use crate::molecule::element::Element;
+use std::str::FromStr;
+
+/// Trimmed columns `start..end` of `line`, empty when the line is shorter.
+fn field(line: &str, start: usize, end: usize) -> &str {
+    line.get(start..end.min(line.len())).unwrap_or("").trim()
+}
+
+fn number<T: FromStr + Default>(
+    line: &str,
+    start: usize,
+    end: usize,
+    ln: usize,
+    what: &'static str,
+) -> Result<T> {
+    let f = field(line, start, end);
+    if f.is_empty() {
+        return Ok(T::default());
+    }
+    f.parse().map_err(|_| RuatomError::IllegalMolfile(ln, what))
+}
+
+pub(crate) fn parse(lines: &[&str]) -> Result<Ctab> {
+    let counts = lines
+        .get(3)
+        .ok_or(RuatomError::IllegalMolfile(4, "missing counts line"))?;
+    let na: usize = number(counts, 0, 3, 4, "invalid atom count")?;
+    let nb: usize = number(counts, 3, 6, 4, "invalid bond count")?;
+    let chiral: u8 = number(counts, 12, 15, 4, "invalid chiral flag")?;
+    let mut ctab = Ctab {
+        chiral: chiral == 1,
+        ..Default::default()
+    };
+
+    for ix in 4..4 + na {
+        let ln = ix + 1;
+        let line = lines
+            .get(ix)
+            .ok_or(RuatomError::IllegalMolfile(ln, "missing atom line"))?;
+        let mut xyz = [0.0; 3];
+        for (k, c) in xyz.iter_mut().enumerate() {
+            *c = field(line, k * 10, k * 10 + 10)
+                .parse()
+                .map_err(|_| RuatomError::IllegalMolfile(ln, "invalid coordinate"))?;
+        }
+        let symbol = field(line, 31, 34);
+        if symbol.is_empty() {
+            return Err(RuatomError::IllegalMolfile(ln, "missing atom symbol"));
+        }
+        let mut rec = AtomRecord::new(symbol, xyz);
+        let dd: i16 = number(line, 34, 36, ln, "invalid mass difference")?;
+        if dd != 0 {
+            let mass = Element::read(symbol)
+                .ok_or(RuatomError::IllegalMolfile(
+                    ln,
+                    "mass difference on a pseudo atom",
+                ))?
+                .get_mass();
+            rec.isotope = mass.round() as i16 + dd;
+        }
+        match number::<u8>(line, 36, 39, ln, "invalid charge")? {
+            0 => {}
+            4 => rec.radical = 2,
+            c @ 1..=7 => rec.charge = 4 - c as i8,
+            _ => return Err(RuatomError::IllegalMolfile(ln, "invalid charge")),
+        }
+        rec.parity = number(line, 39, 42, ln, "invalid stereo parity")?;
+        rec.valence = match number::<u8>(line, 48, 51, ln, "invalid valence")? {
+            0 => None,
+            15 => Some(0),
+            v => Some(v),
+        };
+        ctab.atoms.push(rec);
+    }
+
+    for ix in 4 + na..4 + na + nb {
+        let ln = ix + 1;
+        let line = lines
+            .get(ix)
+            .ok_or(RuatomError::IllegalMolfile(ln, "missing bond line"))?;
+        let u = number(line, 0, 3, ln, "invalid bond atom")?;
+        let v = number(line, 3, 6, ln, "invalid bond atom")?;
+        if u == 0 || v == 0 || u as usize > na || v as usize > na {
+            return Err(RuatomError::IllegalMolfile(ln, "bond to a missing atom"));
+        }
+        let order = number(line, 6, 9, ln, "invalid bond type")?;
+        if !(1..=4).contains(&order) {
+            return Err(RuatomError::IllegalMolfile(ln, "unsupported bond type"));
+        }
+        let stereo = number(line, 9, 12, ln, "invalid bond stereo")?;
+        ctab.bonds.push(BondRecord {
+            u,
+            v,
+            order,
+            stereo,
+        });
+    }
+
+    let props = &lines[(4 + na + nb).min(lines.len())..];
+    if props
+        .iter()
+        .take_while(|l| !l.starts_with("M  END"))
+        .any(|l| l.starts_with("M  CHG") || l.starts_with("M  RAD"))
+    {
+        for rec in ctab.atoms.iter_mut() {
+            rec.charge = 0;
+            rec.radical = 0;
+        }
+    }
+    let mut ix = 0;
+    while ix < props.len() {
+        let ln = 4 + na + nb + ix + 1;
+        let line = props[ix];
+        ix += 1;
+        if line.starts_with("M  END") {
+            return Ok(ctab);
+        }
+        if line.starts_with("A  ") || line.starts_with("G  ") {
+            ix += 1;
+            continue;
+        }
+        let tag = field(line, 3, 6);
+        if !line.starts_with("M  ") || !matches!(tag, "CHG" | "ISO" | "RAD") {
+            continue;
+        }
+        let entries: Vec<&str> = line[6..].split_whitespace().collect();
+        let count: usize = entries
+            .first()
+            .and_then(|c| c.parse().ok())
+            .ok_or(RuatomError::IllegalMolfile(ln, "invalid property count"))?;
+        if entries.len() != 1 + 2 * count {
+            return Err(RuatomError::IllegalMolfile(ln, "invalid property entries"));
+        }
+        for pair in entries[1..].chunks(2) {
+            let at: usize = pair[0]
+                .parse()
+                .map_err(|_| RuatomError::IllegalMolfile(ln, "invalid property atom"))?;
+            let rec = match at {
+                0 => None,
+                _ => ctab.atoms.get_mut(at - 1),
+            }
+            .ok_or(RuatomError::IllegalMolfile(
+                ln,
+                "property on a missing atom",
+            ))?;
+            let value: i16 = pair[1]
+                .parse()
+                .map_err(|_| RuatomError::IllegalMolfile(ln, "invalid property value"))?;
+            match tag {
+                "CHG" => {
+                    rec.charge = i8::try_from(value)
+                        .map_err(|_| RuatomError::IllegalMolfile(ln, "invalid charge"))?
+                }
+                "ISO" => rec.isotope = value,
+                _ => {
+                    rec.radical = u8::try_from(value)
+                        .ok()
+                        .filter(|r| *r <= 3)
+                        .ok_or(RuatomError::IllegalMolfile(ln, "invalid radical"))?
+                }
+            }
+        }
+    }
+    Err(RuatomError::IllegalMolfile(
+        lines.len(),
+        "missing `M  END` line",
+    ))
+}
+
+pub(crate) fn format(ctab: &Ctab) -> Result<String> {
+    if ctab.atoms.len() > 999 || ctab.bonds.len() > 999 {
+        return Err(RuatomError::IllegalMolecule("too many atoms for V2000"));
+    }
+    let dim = if ctab.is_3d() { "3D" } else { "2D" };
+    let mut out = String::new();
+    out += "\n";
+    out += &format!("{:2}{:8}{:10}{}\n", "", "ruatom", "", dim);
+    out += "\n";
+    out += &format!(
+        "{:3}{:3}  0  0{:3}  0  0  0  0  0999 V2000\n",
+        ctab.atoms.len(),
+        ctab.bonds.len(),
+        ctab.chiral as u8
+    );
+
+    let mut chg = vec![];
+    let mut iso = vec![];
+    let mut rad = vec![];
+    for (ix, a) in ctab.atoms.iter().enumerate() {
+        let ccc = match a.charge {
+            -3..=3 if a.charge != 0 => 4 - a.charge,
+            _ => 0,
+        };
+        let vvv = match a.valence {
+            None => 0,
+            Some(0) => 15,
+            Some(v) => v,
+        };
+        out += &format!(
+            "{:10.4}{:10.4}{:10.4} {:<3} 0{:3}{:3}  0  0{:3}  0  0  0  0  0  0\n",
+            a.xyz[0], a.xyz[1], a.xyz[2], a.symbol, ccc, a.parity, vvv
+        );
+        if a.charge != 0 {
+            chg.push((ix + 1, a.charge as i16));
+        }
+        if a.isotope > 0 {
+            iso.push((ix + 1, a.isotope));
+        }
+        if a.radical != 0 {
+            rad.push((ix + 1, a.radical as i16));
+        }
+    }
+    for b in ctab.bonds.iter() {
+        out += &format!("{:3}{:3}{:3}{:3}\n", b.u, b.v, b.order, b.stereo);
+    }
+    for (tag, entries) in [("CHG", chg), ("ISO", iso), ("RAD", rad)] {
+        for chunk in entries.chunks(8) {
+            out += &format!("M  {}{:3}", tag, chunk.len());
+            for (at, value) in chunk {
+                out += &format!(" {:3} {:3}", at, value);
+            }
+            out += "\n";
+        }
+    }
+    out += "M  END\n";
+    Ok(out)
+}
@@ -0,0 +1,381 @@
+use super::{AtomRecord, BondRecord, Ctab};
+use crate::error::{Result, RuatomError};
+use crate::molecule::{AtomIdx, StereoGroup};
+use std::collections::HashMap;
+
+/// Longest line a V3000 block may hold.
+const LINE_WIDTH: usize = 80;
+
+/// Splits at whitespace outside of parentheses and quotes.
+fn tokens(line: &str) -> Vec<&str> {
+    let mut out = vec![];
+    let mut depth = 0;
+    let mut quoted = false;
+    let mut start = None;
+    for (ix, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted => depth -= 1,
+            _ => {}
+        }
+        if c.is_whitespace() && depth == 0 && !quoted {
+            if let Some(s) = start.take() {
+                out.push(&line[s..ix]);
+            }
+        } else if start.is_none() {
+            start = Some(ix);
+        }
+    }
+    if let Some(s) = start {
+        out.push(&line[s..]);
+    }
+    out
+}
+
+/// Items of a `(n a b ...)` list.
+fn list(value: &str, ln: usize) -> Result<Vec<usize>> {
+    let inner = value
+        .strip_prefix('(')
+        .and_then(|v| v.strip_suffix(')'))
+        .ok_or(RuatomError::IllegalMolfile(ln, "invalid list"))?;
+    let items: Vec<usize> = inner
+        .split_whitespace()
+        .map(|i| i.parse())
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|_| RuatomError::IllegalMolfile(ln, "invalid list"))?;
+    match items.split_first() {
+        Some((n, rest)) if *n == rest.len() => Ok(rest.to_vec()),
+        _ => Err(RuatomError::IllegalMolfile(ln, "invalid list")),
+    }
+}
+
+fn number<T: std::str::FromStr>(value: &str, ln: usize, what: &'static str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| RuatomError::IllegalMolfile(ln, what))
+}
+
+/// `MDLV30/STEABS`, `MDLV30/STERACn` or `MDLV30/STERELn`.
+fn stereo_group(name: &str) -> Option<StereoGroup> {
+    let name = name.strip_prefix("MDLV30/STE")?;
+    if name == "ABS" {
+        return Some(StereoGroup::Absolute);
+    }
+    if let Some(n) = name.strip_prefix("RAC") {
+        return n.parse().ok().map(StereoGroup::And);
+    }
+    name.strip_prefix("REL")?.parse().ok().map(StereoGroup::Or)
+}
+
+/// Joins continued `M  V30` lines, keeping the line number each starts at.
+fn logical_lines(lines: &[&str]) -> Result<Vec<(usize, String)>> {
+    let mut out = vec![];
+    let mut pending: Option<(usize, String)> = None;
+    for (ix, line) in lines.iter().enumerate().skip(4) {
+        let ln = ix + 1;
+        if line.starts_with("M  END") {
+            if pending.is_some() {
+                return Err(RuatomError::IllegalMolfile(ln, "unfinished continuation"));
+            }
+            return Ok(out);
+        }
+        let Some(body) = line.trim_end().strip_prefix("M  V30 ") else {
+            continue;
+        };
+        let (body, continued) = match body.strip_suffix('-') {
+            Some(b) => (b, true),
+            None => (body, false),
+        };
+        match pending.as_mut() {
+            Some((_, s)) => s.push_str(body),
+            None => pending = Some((ln, body.to_string())),
+        }
+        if !continued {
+            out.push(pending.take().unwrap());
+        }
+    }
+    Err(RuatomError::IllegalMolfile(
+        lines.len(),
+        "missing `M  END` line",
+    ))
+}
+
+pub(crate) fn parse(lines: &[&str]) -> Result<Ctab> {
+    let logical = logical_lines(lines)?;
+    let mut ctab = Ctab::default();
+    let mut index: HashMap<usize, AtomIdx> = HashMap::new();
+    let mut block: Option<(String, Vec<String>)> = None;
+    let mut collection: Vec<String> = vec![];
+
+    for (ln, line) in logical.iter() {
+        let ln = *ln;
+        let toks = tokens(line);
+        let Some(head) = toks.first() else {
+            continue;
+        };
+        if let Some((name, body)) = block.as_mut() {
+            if *head == "END" && toks.get(1) == Some(&name.as_str()) {
+                let (name, body) = block.take().unwrap();
+                match name.as_str() {
+                    "ATOM" | "BOND" => {}
+                    "COLLECTION" => {
+                        if !collection.is_empty() {
+                            ctab.blocks.push((name, std::mem::take(&mut collection)));
+                        }
+                    }
+                    _ => ctab.blocks.push((name, body)),
+                }
+                continue;
+            }
+            match name.as_str() {
+                "ATOM" => {
+                    if toks.len() < 6 {
+                        return Err(RuatomError::IllegalMolfile(ln, "incomplete atom line"));
+                    }
+                    let symbol = toks[1];
+                    if symbol.starts_with('[') || symbol.starts_with("NOT") {
+                        return Err(RuatomError::IllegalMolfile(
+                            ln,
+                            "atom lists are not supported",
+                        ));
+                    }
+                    let mut xyz = [0.0; 3];
+                    for (k, c) in xyz.iter_mut().enumerate() {
+                        *c = number(toks[2 + k], ln, "invalid coordinate")?;
+                    }
+                    let mut rec = AtomRecord::new(symbol, xyz);
+                    for kv in toks[6..].iter() {
+                        let Some((key, value)) = kv.split_once('=') else {
+                            continue;
+                        };
+                        match key {
+                            "CHG" => rec.charge = number(value, ln, "invalid charge")?,
+                            "RAD" => rec.radical = number(value, ln, "invalid radical")?,
+                            "MASS" => rec.isotope = number(value, ln, "invalid mass")?,
+                            "CFG" => rec.parity = number(value, ln, "invalid stereo parity")?,
+                            "VAL" => {
+                                rec.valence = match number::<i8>(value, ln, "invalid valence")? {
+                                    -1 => Some(0),
+                                    v => Some(v as u8),
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    ctab.atoms.push(rec);
+                    let id = number(toks[0], ln, "invalid atom index")?;
+                    index.insert(id, ctab.atoms.len() as AtomIdx);
+                }
+                "BOND" => {
+                    if toks.len() < 4 {
+                        return Err(RuatomError::IllegalMolfile(ln, "incomplete bond line"));
+                    }
+                    let order: u8 = number(toks[1], ln, "invalid bond type")?;
+                    if !(1..=4).contains(&order) {
+                        return Err(RuatomError::IllegalMolfile(ln, "unsupported bond type"));
+                    }
+                    let mut ends = [0; 2];
+                    for (k, end) in ends.iter_mut().enumerate() {
+                        let id: usize = number(toks[2 + k], ln, "invalid bond atom")?;
+                        *end = *index
+                            .get(&id)
+                            .ok_or(RuatomError::IllegalMolfile(ln, "bond to a missing atom"))?;
+                    }
+                    let mut stereo = 0;
+                    for kv in toks[4..].iter() {
+                        if let Some(value) = kv.strip_prefix("CFG=") {
+                            stereo = match (number(value, ln, "invalid bond stereo")?, order) {
+                                (1, _) => 1,
+                                (2, 2) => 3,
+                                (2, _) => 4,
+                                (3, _) => 6,
+                                _ => 0,
+                            };
+                        }
+                    }
+                    ctab.bonds.push(BondRecord {
+                        u: ends[0],
+                        v: ends[1],
+                        order,
+                        stereo,
+                    });
+                }
+                "COLLECTION" => match stereo_group(head) {
+                    Some(group) => {
+                        let mut atoms = vec![];
+                        for kv in toks[1..].iter() {
+                            if let Some(value) = kv.strip_prefix("ATOMS=") {
+                                for id in list(value, ln)? {
+                                    atoms.push(*index.get(&id).ok_or(
+                                        RuatomError::IllegalMolfile(
+                                            ln,
+                                            "collection of a missing atom",
+                                        ),
+                                    )?);
+                                }
+                            }
+                        }
+                        ctab.groups.push((group, atoms));
+                    }
+                    None => collection.push(line.clone()),
+                },
+                _ => body.push(line.clone()),
+            }
+            continue;
+        }
+        match *head {
+            "BEGIN" if toks.get(1) == Some(&"CTAB") => {}
+            "END" if toks.get(1) == Some(&"CTAB") => {}
+            "BEGIN" => {
+                let name = toks
+                    .get(1)
+                    .ok_or(RuatomError::IllegalMolfile(ln, "unnamed block"))?;
+                block = Some((name.to_string(), vec![]));
+            }
+            "COUNTS" => {
+                ctab.chiral = toks.get(5) == Some(&"1");
+            }
+            _ => {}
+        }
+    }
+    if block.is_some() {
+        return Err(RuatomError::IllegalMolfile(
+            lines.len(),
+            "unterminated block",
+        ));
+    }
+    Ok(ctab)
+}
+
+/// Appends `body` as one or more `M  V30` lines.
+fn push_line(out: &mut String, body: &str) {
+    let width = LINE_WIDTH - "M  V30 ".len() - 1;
+    let mut rest = body;
+    while rest.len() > width + 1 {
+        let mut cut = width;
+        while !rest.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        out.push_str("M  V30 ");
+        out.push_str(&rest[..cut]);
+        out.push_str("-\n");
+        rest = &rest[cut..];
+    }
+    out.push_str("M  V30 ");
+    out.push_str(rest);
+    out.push('\n');
+}
+
+fn format_list(atoms: &[AtomIdx]) -> String {
+    let mut s = format!("({}", atoms.len());
+    for at in atoms.iter() {
+        s += &format!(" {}", at);
+    }
+    s + ")"
+}
+
+pub(crate) fn format(ctab: &Ctab) -> String {
+    let dim = if ctab.is_3d() { "3D" } else { "2D" };
+    let sgroups = ctab
+        .blocks
+        .iter()
+        .filter(|b| b.0 == "SGROUP")
+        .map(|b| b.1.len())
+        .sum::<usize>();
+    let mut out = String::new();
+    out += "\n";
+    out += &format!("{:2}{:8}{:10}{}\n", "", "ruatom", "", dim);
+    out += "\n";
+    out += "  0  0  0     0  0            999 V3000\n";
+    push_line(&mut out, "BEGIN CTAB");
+    push_line(
+        &mut out,
+        &format!(
+            "COUNTS {} {} {} 0 {}",
+            ctab.atoms.len(),
+            ctab.bonds.len(),
+            sgroups,
+            ctab.chiral as u8
+        ),
+    );
+
+    push_line(&mut out, "BEGIN ATOM");
+    for (ix, a) in ctab.atoms.iter().enumerate() {
+        let mut line = format!(
+            "{} {} {:.4} {:.4} {:.4} 0",
+            ix + 1,
+            a.symbol,
+            a.xyz[0],
+            a.xyz[1],
+            a.xyz[2]
+        );
+        if a.charge != 0 {
+            line += &format!(" CHG={}", a.charge);
+        }
+        if a.radical != 0 {
+            line += &format!(" RAD={}", a.radical);
+        }
+        if a.isotope > 0 {
+            line += &format!(" MASS={}", a.isotope);
+        }
+        if a.parity != 0 {
+            line += &format!(" CFG={}", a.parity);
+        }
+        match a.valence {
+            Some(0) => line += " VAL=-1",
+            Some(v) => line += &format!(" VAL={}", v),
+            None => {}
+        }
+        push_line(&mut out, &line);
+    }
+    push_line(&mut out, "END ATOM");
+
+    if !ctab.bonds.is_empty() {
+        push_line(&mut out, "BEGIN BOND");
+        for (ix, b) in ctab.bonds.iter().enumerate() {
+            let mut line = format!("{} {} {} {}", ix + 1, b.order, b.u, b.v);
+            match b.stereo {
+                1 => line += " CFG=1",
+                3 | 4 => line += " CFG=2",
+                6 => line += " CFG=3",
+                _ => {}
+            }
+            push_line(&mut out, &line);
+        }
+        push_line(&mut out, "END BOND");
+    }
+
+    let mut collection = vec![];
+    if ctab.groups.iter().any(|g| g.0 != StereoGroup::Absolute) {
+        for (group, atoms) in ctab.groups.iter() {
+            let name = match group {
+                StereoGroup::Absolute => "MDLV30/STEABS".to_string(),
+                StereoGroup::And(n) => format!("MDLV30/STERAC{}", n),
+                StereoGroup::Or(n) => format!("MDLV30/STEREL{}", n),
+            };
+            collection.push(format!("{} ATOMS={}", name, format_list(atoms)));
+        }
+    }
+    for (name, lines) in ctab.blocks.iter() {
+        if name == "COLLECTION" {
+            collection.extend(lines.iter().cloned());
+            continue;
+        }
+        push_line(&mut out, &format!("BEGIN {}", name));
+        for line in lines.iter() {
+            push_line(&mut out, line);
+        }
+        push_line(&mut out, &format!("END {}", name));
+    }
+    if !collection.is_empty() {
+        push_line(&mut out, "BEGIN COLLECTION");
+        for line in collection.iter() {
+            push_line(&mut out, line);
+        }
+        push_line(&mut out, "END COLLECTION");
+    }
+    push_line(&mut out, "END CTAB");
+    out += "M  END\n";
+    out
+}
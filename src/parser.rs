@@ -3,27 +3,33 @@ use crate::molecule::configuration::{
     TH1, TH2, UNKNOWN,
 };
 use crate::molecule::{
-    atom::*, bond::*, create, element::*, Molecule, HAS_AROM, HAS_ATM_STRO, HAS_BND_STRO,
-    HAS_EXT_STRO, HAS_STRO,
+    atom::*, bond::*, create, element::*, AromaticityModel, AtomIdx, Molecule, HAS_AROM,
+    HAS_ATM_STRO, HAS_BND_STRO, HAS_EXT_STRO, HAS_STRO,
 };
 use crate::{
     char_buff::CharBuffer,
-    error::{Result, RuatomError},
+    error::{Result, RuatomError, SmilesError},
 };
 use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 pub struct Parser {
     buf: CharBuffer,
     molecule: Molecule,
     current_bond: Bond,
-    stack: Vec<u8>,
-    adjacent_map: HashMap<u8, Vec<i8>>,
-    last_bond_pos: Option<u8>,
-    start: HashSet<u8>,
+    stack: Vec<AtomIdx>,
+    adjacent_map: HashMap<AtomIdx, Vec<i64>>,
+    last_bond_pos: Option<usize>,
+    start: HashSet<AtomIdx>,
     configuration: Configuration,
-    configurations: HashMap<u8, Configuration>,
+    configurations: HashMap<AtomIdx, Configuration>,
     hastrix: bool,
-    directional_bonds: HashMap<u8, bool>,
+    directional_bonds: HashMap<AtomIdx, bool>,
+    token_start: usize,
+    atom_spans: Vec<(usize, usize)>,
+    ring_spans: HashMap<u8, (usize, usize)>,
+    branch_spans: Vec<(usize, usize)>,
+    opened: Option<usize>,
 }
 
 impl Parser {
@@ -40,21 +46,47 @@ impl Parser {
             configurations: HashMap::new(),
             hastrix: false,
             directional_bonds: HashMap::new(),
+            token_start: 0,
+            atom_spans: Vec::new(),
+            ring_spans: HashMap::new(),
+            branch_spans: Vec::new(),
+            opened: None,
         }
     }
 
+    /// Perceives aromaticity with `model` instead of
+    /// [`Basic`](crate::molecule::Basic).
+    ///
+    /// ```
+    /// use ruatom::molecule::Huckel;
+    /// use ruatom::Parser;
+    ///
+    /// let m = Parser::new("C1=CC=CC=CC=C1").aromaticity(Huckel::default()).parse().unwrap();
+    /// assert_eq!(m.to_smiles().unwrap(), "C1=CC=CC=CC=C1");
+    /// let m = Parser::new("C1=CC=C2C=CC=CC2=C1").aromaticity(Huckel::default()).parse().unwrap();
+    /// assert_eq!(m.to_smiles().unwrap(), "c1cccc2ccccc12");
+    /// ```
+    pub fn aromaticity(mut self, model: impl AromaticityModel + 'static) -> Self {
+        self.molecule.set_aromaticity_model(Arc::new(model));
+        self
+    }
+
     fn add_atom(&mut self, atom: Atom) -> Result<()> {
         let v = self.molecule.add_atom(atom)?;
+        self.atom_spans
+            .push((self.token_start, self.buf.position()));
         if !self.stack.is_empty() {
             let u = self.stack.pop().unwrap();
-            if self.current_bond != DOT {
+            if self.current_bond == DOT {
+                self.start.insert(v);
+            } else {
                 if self.current_bond.direction() {
                     self.directional_bonds.insert(u, true);
                     self.directional_bonds.insert(v, true);
                 }
-                self.molecule.add_bond(u as u8, v, self.current_bond)?;
-                self.set_adjacent(u, v as i8);
-                self.set_adjacent(v, u as i8);
+                self.molecule.add_bond(u, v, self.current_bond)?;
+                self.set_adjacent(u, v as i64);
+                self.set_adjacent(v, u as i64);
             }
         } else {
             self.start.insert(v);
@@ -69,43 +101,89 @@ impl Parser {
         Ok(())
     }
 
+    /// Parses the SMILES, failures are [`RuatomError::SmilesSyntax`] located
+    /// in the input.
     pub fn parse(mut self) -> Result<Molecule> {
-        self.read_smiles()?;
-        self.molecule.rings_detection()?;
-        self.molecule.aromaticity_detection()?;
-        self.molecule.symmetry_detection()?;
-        if self.molecule.ring_num() > 0 {
-            return Err(RuatomError::IllegalSMILES("unclosed ring"));
+        if let Err(e) = self.read_smiles() {
+            let span = (self.token_start, self.buf.position());
+            return Err(self.located(e, span, self.opened));
+        }
+        let end = (self.buf.length(), self.buf.length());
+        if let Some(span) = self.ring_spans.values().min().copied() {
+            let e = RuatomError::IllegalSMILES("unclosed ring");
+            return Err(self.located_unclosed(e, span));
         }
-        if self.stack.len() > 1 {
-            return Err(RuatomError::IllegalSMILES("unclosed branch"));
+        if let Some(span) = self.branch_spans.first().copied() {
+            let e = RuatomError::IllegalSMILES("unclosed branch");
+            return Err(self.located_unclosed(e, span));
         }
+        self.perceive().map_err(|e| self.located(e, end, None))?;
         if self.molecule.get_flag(HAS_STRO) != 0 {
             self.build_topologies()?;
         }
         if self.hastrix {
-            self.molecule.trans_astrix_atom()?;
-        }
-        self.molecule.stereocenter_detection()?;
-        if self.molecule.chiralatoms_count() >= 2 {
-            self.molecule.rerank()?;
+            self.molecule
+                .trans_astrix_atom()
+                .map_err(|e| self.located(e, end, None))?;
         }
+        self.molecule
+            .stereocenter_detection()
+            .map_err(|e| self.located(e, end, None))?;
         Ok(self.molecule)
     }
 
+    fn perceive(&mut self) -> Result<()> {
+        self.molecule.rings_detection()?;
+        self.molecule.aromaticity_detection()?;
+        self.molecule.symmetry_detection()
+    }
+
+    /// Wraps `reason` with the text of `span`, a pair of character offsets.
+    fn located(
+        &self,
+        reason: RuatomError,
+        span: (usize, usize),
+        opened: Option<usize>,
+    ) -> RuatomError {
+        RuatomError::SmilesSyntax(SmilesError {
+            smiles: self.buf.to_string(),
+            position: span.0,
+            token: self.buf.substr(span.0, span.1),
+            opened,
+            reason: Box::new(reason),
+        })
+    }
+
+    /// Unclosed rings and branches are found at the end of the input.
+    fn located_unclosed(&self, reason: RuatomError, span: (usize, usize)) -> RuatomError {
+        match self.located(reason, span, Some(span.0)) {
+            RuatomError::SmilesSyntax(mut e) => {
+                e.position = self.buf.length();
+                RuatomError::SmilesSyntax(e)
+            }
+            e => e,
+        }
+    }
+
     fn build_topologies(&mut self) -> Result<()> {
         let configurations = self.configurations.clone();
         for (k, c) in configurations.iter() {
-            let conf = self.molecule.to_explict_configuration(*k, c)?;
-            self.add_topology(*k, conf)?;
+            let span = self.atom_spans[*k as usize - 1];
+            self.molecule
+                .to_explict_configuration(*k, c)
+                .and_then(|conf| self.add_topology(*k, conf))
+                .map_err(|e| self.located(e, span, None))?;
         }
         let directional_bonds = self.directional_bonds.clone();
-        self.molecule.validate_up_down(directional_bonds)?;
+        let end = (self.buf.length(), self.buf.length());
+        self.molecule
+            .validate_up_down(directional_bonds)
+            .map_err(|e| self.located(e, end, None))?;
         Ok(())
     }
 
-    fn add_topology(&mut self, u: u8, conf: Configuration) -> Result<()> {
-        return match self.adjacent_map.get(&u) {
+    fn add_topology(&mut self, u: AtomIdx, conf: Configuration) -> Result<()> {
+        match self.adjacent_map.get(&u) {
             None => Err(RuatomError::IllegalSMILES("no such atom in adjacent_map")),
             Some(arr) => {
                 let mut us = arr.clone().to_owned();
@@ -130,15 +208,17 @@ impl Parser {
                     ));
                 }
                 self.molecule.add_topology(create(u, conf, us)?);
-                return Ok(());
+                Ok(())
             }
-        };
+        }
     }
 
     fn read_smiles(&mut self) -> Result<()> {
         loop {
+            self.token_start = self.buf.position();
+            self.opened = None;
             let c = self.buf.next_with_progress();
-            if let None = c {
+            if c.is_none() {
                 return Ok(());
             }
             match c.unwrap() {
@@ -209,8 +289,8 @@ impl Parser {
                     let btom = self.read_bracket_atoms()?;
                     self.add_atom(btom)?;
                 }
-                ch if ch.is_digit(10) => {
-                    self.build_ring((ch.to_digit(10).unwrap() - 0) as u8)?;
+                ch if ch.is_ascii_digit() => {
+                    self.build_ring(ch.to_digit(10).unwrap() as u8)?;
                 }
                 '%' => {
                     match self.buf.to_sub_number(&mut 2) {
@@ -222,7 +302,7 @@ impl Parser {
                                 ));
                             };
                             self.build_ring(n as u8)?;
-                            self.last_bond_pos = Some(self.buf.position() as u8);
+                            self.last_bond_pos = Some(self.buf.position());
                         }
                     };
                 }
@@ -231,28 +311,28 @@ impl Parser {
                         return Err(RuatomError::IllegalSMILES("bond conflict"));
                     }
                     self.current_bond = SINGLE;
-                    self.last_bond_pos = Some(self.buf.position() as u8);
+                    self.last_bond_pos = Some(self.buf.position());
                 }
                 '=' => {
                     if self.current_bond != IMPLICT {
                         return Err(RuatomError::IllegalSMILES("bond conflict"));
                     }
                     self.current_bond = DOUBLE;
-                    self.last_bond_pos = Some(self.buf.position() as u8);
+                    self.last_bond_pos = Some(self.buf.position());
                 }
                 '#' => {
                     if self.current_bond != IMPLICT {
                         return Err(RuatomError::IllegalSMILES("bond conflict"));
                     }
                     self.current_bond = TRIPLE;
-                    self.last_bond_pos = Some(self.buf.position() as u8);
+                    self.last_bond_pos = Some(self.buf.position());
                 }
                 '$' => {
                     if self.current_bond != IMPLICT {
                         return Err(RuatomError::IllegalSMILES("bond conflict"));
                     }
                     self.current_bond = QUADRUPLE;
-                    self.last_bond_pos = Some(self.buf.position() as u8);
+                    self.last_bond_pos = Some(self.buf.position());
                 }
                 ':' => {
                     if self.current_bond != IMPLICT {
@@ -260,7 +340,7 @@ impl Parser {
                     }
                     self.current_bond = AROMATIC;
                     self.molecule.set_flags(HAS_AROM);
-                    self.last_bond_pos = Some(self.buf.position() as u8);
+                    self.last_bond_pos = Some(self.buf.position());
                 }
                 '/' => {
                     if self.current_bond != IMPLICT {
@@ -268,7 +348,7 @@ impl Parser {
                     }
                     self.current_bond = UP;
                     self.molecule.set_flags(HAS_BND_STRO);
-                    self.last_bond_pos = Some(self.buf.position() as u8);
+                    self.last_bond_pos = Some(self.buf.position());
                 }
                 '\\' => {
                     if self.current_bond != IMPLICT {
@@ -276,7 +356,7 @@ impl Parser {
                     }
                     self.current_bond = DOWN;
                     self.molecule.set_flags(HAS_BND_STRO);
-                    self.last_bond_pos = Some(self.buf.position() as u8);
+                    self.last_bond_pos = Some(self.buf.position());
                 }
                 '.' => {
                     if self.current_bond != IMPLICT {
@@ -291,6 +371,8 @@ impl Parser {
                         ));
                     }
                     self.stack.push(self.stack[self.stack.len() - 1]);
+                    self.branch_spans
+                        .push((self.token_start, self.buf.position()));
                 }
                 ')' => {
                     if self.stack.len() < 2 {
@@ -299,6 +381,7 @@ impl Parser {
                         ));
                     }
                     self.stack.pop();
+                    self.branch_spans.pop();
                 }
                 _ => {
                     return Err(RuatomError::IllegalSMILES("unexpected character"));
@@ -307,7 +390,7 @@ impl Parser {
         }
     }
 
-    fn set_adjacent(&mut self, key: u8, val: i8) {
+    fn set_adjacent(&mut self, key: AtomIdx, val: i64) {
         match self.adjacent_map.get_mut(&key) {
             None => {
                 self.adjacent_map.insert(key, vec![val]);
@@ -319,7 +402,7 @@ impl Parser {
     }
 
     fn read_element(&mut self) -> Option<Element> {
-        self.buf.next_with_progress().map_or(None, |c| {
+        self.buf.next_with_progress().and_then(|c| {
             let nxt = self.buf.next();
             let mut ele = c.to_string();
             if self.buf.is_remain() && nxt.unwrap().is_lowercase() {
@@ -327,7 +410,7 @@ impl Parser {
                 ele.push(nxt.unwrap());
                 return Element::read(&ele);
             };
-            return Element::read(&ele);
+            Element::read(&ele)
         })
     }
 
@@ -335,7 +418,7 @@ impl Parser {
         if self.buf.is_tar_with_progress('H') {
             return self.buf.to_number().map_or(1, |count| count as u8);
         };
-        return 0;
+        0
     }
 
     fn read_charge(&mut self, acc: i8) -> i8 {
@@ -352,7 +435,7 @@ impl Parser {
                 return self.read_charge(acc - 1);
             }
         }
-        return acc;
+        acc
     }
 
     fn read_configuration(&mut self) -> Result<Configuration> {
@@ -380,7 +463,7 @@ impl Parser {
                                 .ok_or(RuatomError::IllegalSMILES(
                                     "digit after '@TB' must be in range [1, 20]",
                                 ))
-                                .and_then(|conf| Ok(conf.to_owned()));
+                                .map(|conf| conf.to_owned());
                         }
                     };
                 }
@@ -425,7 +508,7 @@ impl Parser {
                                 .ok_or(RuatomError::IllegalSMILES(
                                     "digit after '@OH' must be in range [1, 30]",
                                 ))
-                                .and_then(|conf| Ok(conf.to_owned()));
+                                .map(|conf| conf.to_owned());
                         }
                     };
                 }
@@ -434,12 +517,12 @@ impl Parser {
                 return Ok(ANTICLOCKWISE);
             }
         }
-        return Ok(UNKNOWN);
+        Ok(UNKNOWN)
     }
 
     fn read_bracket_atoms(&mut self) -> Result<Atom> {
         let isotope = self.buf.to_number();
-        let is_aromatic = self.buf.next().map_or(false, |c| c.is_lowercase());
+        let is_aromatic = self.buf.next().is_some_and(|c| c.is_lowercase());
         let ele = self.read_element();
         if ele.is_none() {
             return Err(RuatomError::IllegalSMILES("need an element in bracket"));
@@ -459,12 +542,19 @@ impl Parser {
         self.configuration = self.read_configuration()?;
         let hydrogens = self.read_hydrogens();
         let charge = self.read_charge(0);
+        let class = match self.buf.is_tar_with_progress(':') {
+            true => self
+                .buf
+                .to_number()
+                .ok_or(RuatomError::IllegalSMILES("need a digit after ':'"))?,
+            false => 0,
+        };
         if !self.buf.is_tar_with_progress(']') {
             return Err(RuatomError::IllegalSMILES(
                 "failed to close bracket, invalid bracket atom",
             ));
         }
-        let b_atom = Atom::new_bracket(
+        let mut b_atom = Atom::new_bracket(
             ele,
             isotope.map_or(-1, |n| n as i16),
             hydrogens,
@@ -472,33 +562,41 @@ impl Parser {
             is_aromatic,
             isorganogen,
         );
-        return Ok(b_atom);
+        b_atom.set_atom_class(class as u32);
+        Ok(b_atom)
     }
 
     fn open_ring(&mut self, rloc: u8) {
         let u = self.stack[self.stack.len() - 1];
+        self.ring_spans
+            .insert(rloc, (self.token_start, self.buf.position()));
         self.molecule
-            .open_ring(rloc, self.current_bond.clone(), self.last_bond_pos, u);
-        self.set_adjacent(u, -(rloc as i8));
+            .open_ring(rloc, self.current_bond, self.last_bond_pos, u);
+        self.set_adjacent(u, -(rloc as i64));
         self.current_bond = IMPLICT;
     }
 
     fn close_ring(&mut self, rloc: u8) -> Result<()> {
         let u = self.stack[self.stack.len() - 1];
-        let v = self
-            .molecule
-            .close_ring(rloc, u, self.current_bond.clone())?;
+        self.opened = self.ring_spans.remove(&rloc).map(|span| span.0);
+        let v = self.molecule.close_ring(rloc, u, self.current_bond)?;
         self.adjacent_map
             .get_mut(&v)
-            .and_then(|l| replace(l, -(rloc as i8), u as i8));
-        self.set_adjacent(u, v as i8);
+            .and_then(|l| replace(l, -(rloc as i64), u as i64));
+        self.set_adjacent(u, v as i64);
         self.current_bond = IMPLICT;
         Ok(())
     }
 
     fn build_ring(&mut self, rloc: u8) -> Result<()> {
-        if rloc > 99 || self.current_bond.is(".") || self.stack.is_empty() {
-            return Err(RuatomError::IllegalSMILES(""));
+        if rloc > 99 {
+            return Err(RuatomError::IllegalSMILES("ring bond number over 99"));
+        }
+        if self.current_bond.is(".") {
+            return Err(RuatomError::IllegalSMILES("ring bond after '.'"));
+        }
+        if self.stack.is_empty() {
+            return Err(RuatomError::IllegalSMILES("ring bond without an atom"));
         }
         if self.molecule.enable_open(rloc) {
             self.open_ring(rloc);
@@ -508,7 +606,7 @@ impl Parser {
         Ok(())
     }
 
-    fn modify_th_arrangement_order(&self, u: u8, arrangement: Vec<i8>) -> Result<Vec<i8>> {
+    fn modify_th_arrangement_order(&self, u: AtomIdx, arrangement: Vec<i64>) -> Result<Vec<i64>> {
         if arrangement.len() == 4 {
             return Ok(arrangement);
         }
@@ -518,23 +616,23 @@ impl Parser {
             ));
         }
         if self.start.contains(&u) {
-            return Ok(vec![
-                u as i8,
+            Ok(vec![
+                u as i64,
                 arrangement[0],
                 arrangement[1],
                 arrangement[2],
-            ]);
+            ])
         } else {
-            return Ok(vec![
+            Ok(vec![
                 arrangement[0],
-                u as i8,
+                u as i64,
                 arrangement[1],
                 arrangement[2],
-            ]);
+            ])
         }
     }
 
-    fn modify_db_arrangement_order(&self, u: u8, arrangement: Vec<i8>) -> Result<Vec<i8>> {
+    fn modify_db_arrangement_order(&self, u: AtomIdx, arrangement: Vec<i64>) -> Result<Vec<i64>> {
         if arrangement.len() == 3 {
             return Ok(arrangement);
         }
@@ -544,14 +642,14 @@ impl Parser {
             ));
         }
         if self.start.contains(&u) {
-            return Ok(vec![u as i8, arrangement[0], arrangement[1]]);
+            Ok(vec![u as i64, arrangement[0], arrangement[1]])
         } else {
-            return Ok(vec![arrangement[0], u as i8, arrangement[1]]);
+            Ok(vec![arrangement[0], u as i64, arrangement[1]])
         }
     }
 
-    fn get_allene_carriers(&self, u: u8) -> Result<Vec<i8>> {
-        let mut carriers: Vec<i8> = Vec::with_capacity(4);
+    fn get_allene_carriers(&self, u: AtomIdx) -> Result<Vec<i64>> {
+        let mut carriers: Vec<i64> = Vec::with_capacity(4);
         let mut index = 0;
         let ends = self.molecule.find_extend_tetrahedral_ends(u)?;
         let beg = ends[0];
@@ -568,21 +666,21 @@ impl Parser {
         }
         for bv in beg_vertex.iter() {
             if bv == &-1 {
-                carriers.insert(index, beg as i8);
+                carriers.insert(index, beg as i64);
                 index += 1;
                 continue;
             }
 
-            if self.molecule.edge_at(beg, *bv as u8)?.is("=") {
+            if self.molecule.edge_at(beg, *bv as AtomIdx)?.is("=") {
                 let mut end_vertex = self.adjacent_map.get(&end).unwrap().clone().to_owned();
                 if endh {
                     end_vertex.insert(1, -1);
                 }
                 for ev in end_vertex.iter() {
                     if ev == &-1 {
-                        carriers.insert(index, end as i8);
+                        carriers.insert(index, end as i64);
                         index += 1;
-                    } else if !self.molecule.edge_at(end, *ev as u8)?.is("=") {
+                    } else if !self.molecule.edge_at(end, *ev as AtomIdx)?.is("=") {
                         carriers.insert(index, *ev);
                         index += 1;
                     }
@@ -600,13 +698,13 @@ impl Parser {
     }
 }
 
-fn replace<T: PartialEq + Eq>(l: &mut Vec<T>, old: T, new: T) -> Option<T> {
-    for i in 0..l.len() {
-        if l[i] == old {
-            return Some(std::mem::replace(&mut l[i], new));
+fn replace<T: PartialEq + Eq>(l: &mut [T], old: T, new: T) -> Option<T> {
+    for item in l.iter_mut() {
+        if *item == old {
+            return Some(std::mem::replace(item, new));
         }
     }
-    return None;
+    None
 }
 
 mod test {
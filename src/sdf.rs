@@ -0,0 +1,172 @@
+//! SD files: molfile blocks followed by `> <TAG>` data items, records end
+//! with `$$$$`.
+//!
+//! [`SdfReader`] streams records from any [`BufRead`], a bad record yields
+//! an error carrying its number and the stream goes on with the next one.
+//! To parse in parallel, take the raw records with [`SdfReader::blocks`] and
+//! hand them to rayon with `par_bridge`, each [`SdfBlock`] parses on its own.
+
+use crate::error::{Result, RuatomError};
+use crate::molecule::Molecule;
+use crate::molfile::{read_molfile, write_molfile};
+use std::io::{BufRead, Write};
+
+/// Data items of a record in file order.
+pub type Properties = Vec<(String, String)>;
+
+/// Raw text of one record, `record` counts from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SdfBlock {
+    pub record: usize,
+    pub text: String,
+}
+
+impl SdfBlock {
+    pub fn parse(&self) -> Result<(Molecule, Properties)> {
+        parse_record(&self.text)
+            .map_err(|e| RuatomError::IllegalSdfRecord(self.record, Box::new(e)))
+    }
+}
+
+fn parse_record(text: &str) -> Result<(Molecule, Properties)> {
+    let lines: Vec<&str> = text.lines().collect();
+    let end = lines
+        .iter()
+        .position(|l| l.starts_with("M  END"))
+        .map_or(lines.len(), |ix| ix + 1);
+    let mol = read_molfile(&lines[..end].join("\n"))?;
+
+    let mut props = Properties::new();
+    let mut ix = end;
+    while ix < lines.len() {
+        let header = lines[ix];
+        ix += 1;
+        if !header.starts_with('>') {
+            continue;
+        }
+        let tag = match (header.find('<'), header.rfind('>')) {
+            (Some(s), Some(e)) if s < e => &header[s + 1..e],
+            _ => header[1..].trim(),
+        };
+        let mut value: Vec<&str> = vec![];
+        while ix < lines.len() && !lines[ix].is_empty() {
+            value.push(lines[ix]);
+            ix += 1;
+        }
+        props.push((tag.to_string(), value.join("\n")));
+    }
+    Ok((mol, props))
+}
+
+/// Iterator over the raw records of an SD file.
+pub struct SdfBlocks<R> {
+    reader: R,
+    record: usize,
+    done: bool,
+}
+
+impl<R: BufRead> Iterator for SdfBlocks<R> {
+    type Item = Result<SdfBlock>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut text = String::new();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => {
+                    self.done = true;
+                    if text.trim().is_empty() {
+                        return None;
+                    }
+                    break;
+                }
+                Ok(_) => {
+                    if line.trim_end() == "$$$$" {
+                        break;
+                    }
+                    text.push_str(&line);
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(RuatomError::StdError(e.to_string())));
+                }
+            }
+        }
+        self.record += 1;
+        Some(Ok(SdfBlock {
+            record: self.record,
+            text,
+        }))
+    }
+}
+
+/// Reads `(Molecule, Properties)` records one at a time.
+pub struct SdfReader<R> {
+    blocks: SdfBlocks<R>,
+}
+
+impl<R: BufRead> SdfReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            blocks: SdfBlocks {
+                reader,
+                record: 0,
+                done: false,
+            },
+        }
+    }
+
+    /// The records as unparsed text.
+    pub fn blocks(self) -> SdfBlocks<R> {
+        self.blocks
+    }
+}
+
+impl<R: BufRead> Iterator for SdfReader<R> {
+    type Item = Result<(Molecule, Properties)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.blocks.next().map(|b| b?.parse())
+    }
+}
+
+/// Text of one record including the closing `$$$$` line.
+pub fn write_record(mol: &Molecule, props: &[(String, String)]) -> Result<String> {
+    let mut out = write_molfile(mol)?;
+    for (tag, value) in props.iter() {
+        out += &format!("> <{}>\n", tag);
+        for line in value.lines() {
+            out += line;
+            out += "\n";
+        }
+        out += "\n";
+    }
+    out += "$$$$\n";
+    Ok(out)
+}
+
+/// Writes records to any [`Write`].
+pub struct SdfWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> SdfWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn write(&mut self, mol: &Molecule, props: &[(String, String)]) -> Result<()> {
+        let record = write_record(mol, props)?;
+        self.writer
+            .write_all(record.as_bytes())
+            .map_err(|e| RuatomError::StdError(e.to_string()))
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
@@ -0,0 +1,770 @@
+//! SMARTS queries.
+//!
+//! [`SmartsParser`] reads a SMARTS into a [`QueryMol`], a graph of atom and
+//! bond expressions matched with the same search as
+//! [`SubstructureMatcher`](crate::molecule::SubstructureMatcher) and written
+//! back with [`QueryMol::to_smarts`].
+
+use crate::char_buff::CharBuffer;
+use crate::error::{Result, RuatomError};
+use crate::molecule::{
+    element::{Element, Specification},
+    substructure::{bond_order, matches_at, search, Collect, Query},
+    AtomIdx, Molecule,
+};
+use std::collections::HashMap;
+use std::fmt;
+
+/// A logical expression over primitives, `!` binding tightest, then `&`,
+/// `,` and `;`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr<P> {
+    Primitive(P),
+    Not(Box<Expr<P>>),
+    /// `&`, or primitives written next to each other.
+    And(Box<Expr<P>>, Box<Expr<P>>),
+    Or(Box<Expr<P>>, Box<Expr<P>>),
+    /// `;`
+    LowAnd(Box<Expr<P>>, Box<Expr<P>>),
+}
+
+impl<P> Expr<P> {
+    fn eval(&self, f: &dyn Fn(&P) -> Result<bool>) -> Result<bool> {
+        match self {
+            Expr::Primitive(p) => f(p),
+            Expr::Not(e) => Ok(!e.eval(f)?),
+            Expr::And(a, b) | Expr::LowAnd(a, b) => Ok(a.eval(f)? && b.eval(f)?),
+            Expr::Or(a, b) => Ok(a.eval(f)? || b.eval(f)?),
+        }
+    }
+}
+
+impl<P: fmt::Display> fmt::Display for Expr<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Primitive(p) => write!(f, "{}", p),
+            Expr::Not(e) => write!(f, "!{}", e),
+            Expr::And(a, b) => write!(f, "{}&{}", a, b),
+            Expr::Or(a, b) => write!(f, "{},{}", a, b),
+            Expr::LowAnd(a, b) => write!(f, "{};{}", a, b),
+        }
+    }
+}
+
+pub type AtomExpr = Expr<AtomPrimitive>;
+pub type BondExpr = Expr<BondPrimitive>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AtomPrimitive {
+    /// `*`
+    Any,
+    /// `a`
+    Aromatic,
+    /// `A`
+    Aliphatic,
+    /// An element symbol by atomic number, `c` or `se` being aromatic.
+    Symbol(u8, bool),
+    /// `#n`
+    AtomicNumber(u8),
+    /// `Dn`, explicit connections.
+    Degree(u8),
+    /// `Xn`, connections including implicit hydrogens.
+    Connectivity(u8),
+    /// `Hn`, attached hydrogens.
+    TotalH(u8),
+    /// `hn`, implicit hydrogens.
+    ImplicitH(u8),
+    /// `R` in any ring, `Rn` in `n` SSSR rings.
+    RingCount(Option<u8>),
+    /// `r` in any ring, `rn` with `n` atoms in the smallest ring.
+    RingSize(Option<u8>),
+    /// `vn`, bond orders plus hydrogens.
+    Valence(u8),
+    /// `+n` or `-n`.
+    Charge(i8),
+    /// Leading digits.
+    Isotope(u16),
+    /// `$(...)`, the first atom of the query sits on the atom.
+    Recursive(Box<QueryMol>),
+}
+
+impl fmt::Display for AtomPrimitive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AtomPrimitive::Any => write!(f, "*"),
+            AtomPrimitive::Aromatic => write!(f, "a"),
+            AtomPrimitive::Aliphatic => write!(f, "A"),
+            // in brackets `H` is a hydrogen count
+            AtomPrimitive::Symbol(1, _) => write!(f, "#1"),
+            AtomPrimitive::Symbol(n, aromatic) => {
+                let e = Element::by_atomic_number(*n).ok_or(fmt::Error)?;
+                match aromatic {
+                    true => write!(f, "{}", e.symbol().to_lowercase()),
+                    false => write!(f, "{}", e.symbol()),
+                }
+            }
+            AtomPrimitive::AtomicNumber(n) => write!(f, "#{}", n),
+            AtomPrimitive::Degree(n) => write!(f, "D{}", n),
+            AtomPrimitive::Connectivity(n) => write!(f, "X{}", n),
+            AtomPrimitive::TotalH(n) => write!(f, "H{}", n),
+            AtomPrimitive::ImplicitH(n) => write!(f, "h{}", n),
+            AtomPrimitive::RingCount(None) => write!(f, "R"),
+            AtomPrimitive::RingCount(Some(n)) => write!(f, "R{}", n),
+            AtomPrimitive::RingSize(None) => write!(f, "r"),
+            AtomPrimitive::RingSize(Some(n)) => write!(f, "r{}", n),
+            AtomPrimitive::Valence(n) => write!(f, "v{}", n),
+            AtomPrimitive::Charge(1) => write!(f, "+"),
+            AtomPrimitive::Charge(-1) => write!(f, "-"),
+            AtomPrimitive::Charge(n) if *n < 0 => write!(f, "-{}", -n),
+            AtomPrimitive::Charge(n) => write!(f, "+{}", n),
+            AtomPrimitive::Isotope(n) => write!(f, "{}", n),
+            AtomPrimitive::Recursive(q) => write!(f, "$({})", q.to_smarts()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BondPrimitive {
+    /// No bond written, single or aromatic.
+    Implicit,
+    /// `-`
+    Single,
+    /// `=`
+    Double,
+    /// `#`
+    Triple,
+    /// `$`
+    Quadruple,
+    /// `:`
+    Aromatic,
+    /// `~`
+    Any,
+    /// `@`
+    Ring,
+    /// `/`, matched as single.
+    Up,
+    /// `\`, matched as single.
+    Down,
+}
+
+impl fmt::Display for BondPrimitive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let token = match self {
+            BondPrimitive::Implicit => "",
+            BondPrimitive::Single => "-",
+            BondPrimitive::Double => "=",
+            BondPrimitive::Triple => "#",
+            BondPrimitive::Quadruple => "$",
+            BondPrimitive::Aromatic => ":",
+            BondPrimitive::Any => "~",
+            BondPrimitive::Ring => "@",
+            BondPrimitive::Up => "/",
+            BondPrimitive::Down => "\\",
+        };
+        write!(f, "{}", token)
+    }
+}
+
+/// A parsed SMARTS, atoms numbered from 1 in input order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct QueryMol {
+    atoms: Vec<AtomExpr>,
+    bonds: Vec<([AtomIdx; 2], BondExpr)>,
+}
+
+impl QueryMol {
+    pub fn atoms(&self) -> &[AtomExpr] {
+        &self.atoms
+    }
+
+    /// Bonds in input order, ring closures where they close.
+    pub fn bonds(&self) -> &[([AtomIdx; 2], BondExpr)] {
+        &self.bonds
+    }
+
+    pub fn bond(&self, u: AtomIdx, v: AtomIdx) -> Option<&BondExpr> {
+        self.bonds
+            .iter()
+            .find(|(b, _)| *b == [u, v] || *b == [v, u])
+            .map(|(_, e)| e)
+    }
+
+    pub fn is_match(&self, target: &Molecule) -> Result<bool> {
+        Ok(!search(self, target, Collect::First)?.is_empty())
+    }
+
+    /// The first mapping found, target atoms in query atom order.
+    pub fn first(&self, target: &Molecule) -> Result<Option<Vec<AtomIdx>>> {
+        Ok(search(self, target, Collect::First)?.pop())
+    }
+
+    pub fn matches(&self, target: &Molecule) -> Result<Vec<Vec<AtomIdx>>> {
+        search(self, target, Collect::All)
+    }
+
+    /// One mapping for each distinct set of target atoms.
+    pub fn unique_matches(&self, target: &Molecule) -> Result<Vec<Vec<AtomIdx>>> {
+        search(self, target, Collect::Unique)
+    }
+
+    fn add_atom(&mut self, atom: AtomExpr) -> AtomIdx {
+        self.atoms.push(atom);
+        self.atoms.len() as AtomIdx
+    }
+
+    fn adjacency(&self) -> Vec<Vec<(AtomIdx, usize)>> {
+        let mut adj = vec![vec![]; self.atoms.len() + 1];
+        for (i, ([u, v], _)) in self.bonds.iter().enumerate() {
+            adj[*u as usize].push((*v, i));
+            adj[*v as usize].push((*u, i));
+        }
+        adj
+    }
+
+    /// Writes the query back as SMARTS, depth first from the lowest atom of
+    /// each component, expressions as parsed.
+    pub fn to_smarts(&self) -> String {
+        let adj = self.adjacency();
+        let mut visited = vec![false; self.atoms.len() + 1];
+        let mut tree = vec![false; self.bonds.len()];
+        let mut roots = vec![];
+        for root in 1..=self.atoms.len() as AtomIdx {
+            if !visited[root as usize] {
+                spanning_tree(&adj, root, &mut visited, &mut tree);
+                roots.push(root);
+            }
+        }
+        let mut writer = SmartsWriter {
+            query: self,
+            adj: &adj,
+            tree: &tree,
+            written: vec![false; self.atoms.len() + 1],
+            open: HashMap::new(),
+            out: String::new(),
+        };
+        for (i, root) in roots.into_iter().enumerate() {
+            if i > 0 {
+                writer.out.push('.');
+            }
+            writer.write(root);
+        }
+        writer.out
+    }
+}
+
+impl fmt::Display for QueryMol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_smarts())
+    }
+}
+
+impl Query for QueryMol {
+    fn order(&self) -> usize {
+        self.atoms.len()
+    }
+
+    fn neighbors(&self, u: AtomIdx) -> Result<Vec<AtomIdx>> {
+        Ok(self
+            .bonds
+            .iter()
+            .filter_map(|([a, b], _)| match u {
+                _ if *a == u => Some(*b),
+                _ if *b == u => Some(*a),
+                _ => None,
+            })
+            .collect())
+    }
+
+    fn match_atom(&self, u: AtomIdx, target: &Molecule, v: AtomIdx) -> Result<bool> {
+        self.atoms[u as usize - 1].eval(&|p| match_atom(p, target, v))
+    }
+
+    fn match_bond(&self, [u, w]: [AtomIdx; 2], target: &Molecule, v: [AtomIdx; 2]) -> Result<bool> {
+        match self.bond(u, w) {
+            Some(e) => e.eval(&|p| match_bond(p, target, v)),
+            None => Ok(false),
+        }
+    }
+}
+
+fn match_atom(p: &AtomPrimitive, mol: &Molecule, v: AtomIdx) -> Result<bool> {
+    let atom = mol.atom_at(&v)?;
+    let hydrogens = mol.hydrogen_count(&v)?;
+    let degree = mol.graph().bound_count(&v)?;
+    Ok(match p {
+        AtomPrimitive::Any => true,
+        AtomPrimitive::Aromatic => atom.is_aromatic(),
+        AtomPrimitive::Aliphatic => !atom.is_aromatic(),
+        AtomPrimitive::Symbol(n, aromatic) => {
+            atom.element().atomic_number() == *n && atom.is_aromatic() == *aromatic
+        }
+        AtomPrimitive::AtomicNumber(n) => atom.element().atomic_number() == *n,
+        AtomPrimitive::Degree(n) => degree == *n as usize,
+        AtomPrimitive::Connectivity(n) => degree + hydrogens as usize == *n as usize,
+        AtomPrimitive::TotalH(n) => {
+            let mut explicit = 0;
+            for w in mol.graph().neighbors(&v)? {
+                explicit += mol.atom_at(w)?.is("H") as u8;
+            }
+            hydrogens + explicit == *n
+        }
+        AtomPrimitive::ImplicitH(n) => hydrogens == *n,
+        AtomPrimitive::RingCount(n) => {
+            let count = mol.atom_rings(&v).len();
+            n.map_or(count > 0, |n| count == n as usize)
+        }
+        AtomPrimitive::RingSize(n) => {
+            let smallest = mol
+                .relevant_cycles()
+                .iter()
+                .filter(|r| r.contains(&v))
+                .map(|r| r.len())
+                .min();
+            match n {
+                None => smallest.is_some(),
+                Some(0) => smallest.is_none(),
+                Some(n) => smallest == Some(*n as usize),
+            }
+        }
+        AtomPrimitive::Valence(n) => {
+            let implicit = hydrogens - atom.explicit_hydrogens();
+            mol.valence(&v)? + implicit == *n
+        }
+        AtomPrimitive::Charge(n) => atom.charge() == *n,
+        AtomPrimitive::Isotope(n) => atom.isotope() == *n as i16,
+        AtomPrimitive::Recursive(q) => matches_at(q.as_ref(), mol, v)?,
+    })
+}
+
+fn match_bond(p: &BondPrimitive, mol: &Molecule, [u, v]: [AtomIdx; 2]) -> Result<bool> {
+    let order = bond_order(mol, [u, v])?;
+    Ok(match p {
+        BondPrimitive::Implicit => matches!(order, Some(1) | None),
+        BondPrimitive::Single | BondPrimitive::Up | BondPrimitive::Down => order == Some(1),
+        BondPrimitive::Double => order == Some(2),
+        BondPrimitive::Triple => order == Some(3),
+        BondPrimitive::Quadruple => order == Some(4),
+        BondPrimitive::Aromatic => order.is_none(),
+        BondPrimitive::Any => true,
+        BondPrimitive::Ring => mol.edge_at(u, v)?.ring_membership() == 1,
+    })
+}
+
+/// Marks the depth first tree from `root`, the other bonds close rings.
+fn spanning_tree(
+    adj: &[Vec<(AtomIdx, usize)>],
+    root: AtomIdx,
+    visited: &mut [bool],
+    tree: &mut [bool],
+) {
+    visited[root as usize] = true;
+    for (v, b) in adj[root as usize].iter() {
+        if !visited[*v as usize] {
+            tree[*b] = true;
+            spanning_tree(adj, *v, visited, tree);
+        }
+    }
+}
+
+struct SmartsWriter<'a> {
+    query: &'a QueryMol,
+    adj: &'a [Vec<(AtomIdx, usize)>],
+    tree: &'a [bool],
+    written: Vec<bool>,
+    /// Ring numbers of rings opened but not closed yet, by bond.
+    open: HashMap<usize, usize>,
+    out: String,
+}
+
+impl SmartsWriter<'_> {
+    fn write(&mut self, u: AtomIdx) {
+        self.written[u as usize] = true;
+        let atom = &self.query.atoms[u as usize - 1];
+        match bare(atom) {
+            true => self.out.push_str(&atom.to_string()),
+            false => self.out.push_str(&format!("[{}]", atom)),
+        }
+        let mut children = vec![];
+        for (v, b) in self.adj[u as usize].iter() {
+            if self.tree[*b] {
+                if !self.written[*v as usize] {
+                    children.push((*v, *b));
+                }
+                continue;
+            }
+            let number = match self.open.remove(b) {
+                Some(n) => n,
+                None => {
+                    let n = (1..).find(|n| !self.open.values().any(|o| o == n)).unwrap();
+                    self.out.push_str(&self.query.bonds[*b].1.to_string());
+                    self.open.insert(*b, n);
+                    n
+                }
+            };
+            match number < 10 {
+                true => self.out.push_str(&number.to_string()),
+                false => self.out.push_str(&format!("%{}", number)),
+            }
+        }
+        let last = children.len().saturating_sub(1);
+        for (i, (v, b)) in children.into_iter().enumerate() {
+            if i < last {
+                self.out.push('(');
+            }
+            self.out.push_str(&self.query.bonds[b].1.to_string());
+            self.write(v);
+            if i < last {
+                self.out.push(')');
+            }
+        }
+    }
+}
+
+/// Whether the atom is written without brackets.
+fn bare(atom: &AtomExpr) -> bool {
+    match atom {
+        Expr::Primitive(
+            AtomPrimitive::Any | AtomPrimitive::Aromatic | AtomPrimitive::Aliphatic,
+        ) => true,
+        Expr::Primitive(AtomPrimitive::Symbol(n, aromatic)) => match aromatic {
+            true => matches!(n, 5 | 6 | 7 | 8 | 15 | 16),
+            false => matches!(n, 5 | 6 | 7 | 8 | 9 | 15 | 16 | 17 | 35 | 53),
+        },
+        _ => false,
+    }
+}
+
+fn starts_bond(c: char) -> bool {
+    matches!(c, '-' | '=' | '#' | '$' | ':' | '~' | '@' | '/' | '\\')
+}
+
+fn starts_atom_primitive(c: char) -> bool {
+    !matches!(c, ']' | ',' | ';' | '&' | ')')
+}
+
+pub struct SmartsParser {
+    buf: CharBuffer,
+    bracket: usize,
+}
+
+impl SmartsParser {
+    pub fn new(smarts: &str) -> Self {
+        SmartsParser {
+            buf: CharBuffer::from_str(smarts),
+            bracket: 0,
+        }
+    }
+
+    /// Parses the SMARTS, failures are [`RuatomError::IllegalSMARTS`] with
+    /// the character offset they were found at.
+    ///
+    /// ```
+    /// use ruatom::{Parser, SmartsParser};
+    ///
+    /// let amide = SmartsParser::new("[NX3][CX3](=[OX1])[#6]").parse().unwrap();
+    /// assert!(amide.is_match(&Parser::new("CC(=O)NC").parse().unwrap()).unwrap());
+    /// assert_eq!(amide.to_smarts(), "[N&X3][C&X3](=[O&X1])[#6]");
+    /// ```
+    pub fn parse(mut self) -> Result<QueryMol> {
+        let query = self.read_query(false)?;
+        if self.buf.is_remain() {
+            return Err(self.error("unexpected character"));
+        }
+        Ok(query)
+    }
+
+    fn error(&self, reason: &'static str) -> RuatomError {
+        RuatomError::IllegalSMARTS(reason, self.buf.position())
+    }
+
+    /// Reads atoms and bonds up to the end, or up to the `)` closing a
+    /// recursive SMARTS when `nested`.
+    fn read_query(&mut self, nested: bool) -> Result<QueryMol> {
+        let mut query = QueryMol::default();
+        let mut prev: Option<AtomIdx> = None;
+        let mut branches: Vec<AtomIdx> = vec![];
+        let mut bond: Option<BondExpr> = None;
+        let mut rings: HashMap<usize, (AtomIdx, Option<BondExpr>)> = HashMap::new();
+        while let Some(c) = self.buf.next() {
+            match c {
+                ')' if branches.is_empty() && nested => break,
+                '(' | ')' | '.' if bond.is_some() => {
+                    return Err(self.error("bond without an atom"));
+                }
+                '(' => {
+                    let u = prev.ok_or(self.error("branch without an atom"))?;
+                    self.buf.next_with_progress();
+                    branches.push(u);
+                }
+                ')' => {
+                    prev = Some(branches.pop().ok_or(self.error("unopened branch"))?);
+                    self.buf.next_with_progress();
+                }
+                '.' => {
+                    prev.ok_or(self.error("'.' without an atom"))?;
+                    self.buf.next_with_progress();
+                    prev = None;
+                }
+                '%' | '0'..='9' => {
+                    let u = prev.ok_or(self.error("ring bond without an atom"))?;
+                    let n = self.read_ring_number()?;
+                    match rings.remove(&n) {
+                        None => {
+                            rings.insert(n, (u, bond.take()));
+                        }
+                        Some((v, opened)) => {
+                            let e = match (opened, bond.take()) {
+                                (Some(a), Some(b)) if a != b => {
+                                    return Err(self.error("conflicting ring bonds"));
+                                }
+                                (a, b) => a.or(b),
+                            };
+                            let e = e.unwrap_or(Expr::Primitive(BondPrimitive::Implicit));
+                            query.bonds.push(([v, u], e));
+                        }
+                    }
+                }
+                c if starts_bond(c) || c == '!' => {
+                    if bond.is_some() {
+                        return Err(self.error("two bonds in a row"));
+                    }
+                    bond = Some(self.read_expr(SmartsParser::read_bond, starts_bond)?);
+                }
+                _ => {
+                    let atom = self.read_atom()?;
+                    let v = query.add_atom(atom);
+                    match (prev, bond.take()) {
+                        (Some(u), e) => {
+                            let e = e.unwrap_or(Expr::Primitive(BondPrimitive::Implicit));
+                            query.bonds.push(([u, v], e));
+                        }
+                        (None, Some(_)) => return Err(self.error("bond without an atom")),
+                        (None, None) => (),
+                    }
+                    prev = Some(v);
+                }
+            }
+        }
+        if bond.is_some() {
+            return Err(self.error("bond without an atom"));
+        }
+        if !branches.is_empty() {
+            return Err(self.error("unclosed branch"));
+        }
+        if !rings.is_empty() {
+            return Err(self.error("unclosed ring"));
+        }
+        if query.atoms.is_empty() {
+            return Err(self.error("no atom"));
+        }
+        Ok(query)
+    }
+
+    fn read_ring_number(&mut self) -> Result<usize> {
+        if self.buf.is_tar_with_progress('%') {
+            let mut digits = 2;
+            return match self.buf.to_sub_number(&mut digits) {
+                Some(n) if digits == 0 => Ok(n),
+                _ => Err(self.error("need two digits after '%'")),
+            };
+        }
+        Ok(self.buf.next_with_digit_and_progress().unwrap())
+    }
+
+    fn read_atom(&mut self) -> Result<AtomExpr> {
+        let start = self.buf.position();
+        let c = self.buf.next_with_progress().unwrap();
+        let primitive = match c {
+            '[' => {
+                let outer = self.bracket;
+                self.bracket = self.buf.position();
+                let e = self.read_expr(SmartsParser::read_atom_primitive, starts_atom_primitive)?;
+                self.bracket = outer;
+                if !self.buf.is_tar_with_progress(']') {
+                    return Err(self.error("unclosed bracket atom"));
+                }
+                return Ok(e);
+            }
+            '*' => AtomPrimitive::Any,
+            'a' => AtomPrimitive::Aromatic,
+            'A' => AtomPrimitive::Aliphatic,
+            'C' if self.buf.is_tar_with_progress('l') => AtomPrimitive::Symbol(17, false),
+            'B' if self.buf.is_tar_with_progress('r') => AtomPrimitive::Symbol(35, false),
+            'B' | 'C' | 'N' | 'O' | 'P' | 'S' | 'F' | 'I' | 'b' | 'c' | 'n' | 'o' | 'p' | 's' => {
+                let e = Element::read(&c.to_ascii_uppercase().to_string()).unwrap();
+                AtomPrimitive::Symbol(e.atomic_number(), c.is_ascii_lowercase())
+            }
+            _ => return Err(RuatomError::IllegalSMARTS("invalid atom", start)),
+        };
+        Ok(Expr::Primitive(primitive))
+    }
+
+    fn read_expr<P>(
+        &mut self,
+        read: fn(&mut Self) -> Result<P>,
+        starts: fn(char) -> bool,
+    ) -> Result<Expr<P>> {
+        let mut e = self.read_or(read, starts)?;
+        while self.buf.is_tar_with_progress(';') {
+            e = Expr::LowAnd(Box::new(e), Box::new(self.read_or(read, starts)?));
+        }
+        Ok(e)
+    }
+
+    fn read_or<P>(
+        &mut self,
+        read: fn(&mut Self) -> Result<P>,
+        starts: fn(char) -> bool,
+    ) -> Result<Expr<P>> {
+        let mut e = self.read_and(read, starts)?;
+        while self.buf.is_tar_with_progress(',') {
+            e = Expr::Or(Box::new(e), Box::new(self.read_and(read, starts)?));
+        }
+        Ok(e)
+    }
+
+    fn read_and<P>(
+        &mut self,
+        read: fn(&mut Self) -> Result<P>,
+        starts: fn(char) -> bool,
+    ) -> Result<Expr<P>> {
+        let mut e = self.read_not(read)?;
+        loop {
+            let explicit = self.buf.is_tar_with_progress('&');
+            if !explicit && !self.buf.next().is_some_and(|c| c == '!' || starts(c)) {
+                return Ok(e);
+            }
+            e = Expr::And(Box::new(e), Box::new(self.read_not(read)?));
+        }
+    }
+
+    fn read_not<P>(&mut self, read: fn(&mut Self) -> Result<P>) -> Result<Expr<P>> {
+        if self.buf.is_tar_with_progress('!') {
+            return Ok(Expr::Not(Box::new(self.read_not(read)?)));
+        }
+        Ok(Expr::Primitive(read(self)?))
+    }
+
+    fn read_bond(&mut self) -> Result<BondPrimitive> {
+        let primitive = match self.buf.next_with_progress() {
+            Some('-') => BondPrimitive::Single,
+            Some('=') => BondPrimitive::Double,
+            Some('#') => BondPrimitive::Triple,
+            Some('$') => BondPrimitive::Quadruple,
+            Some(':') => BondPrimitive::Aromatic,
+            Some('~') => BondPrimitive::Any,
+            Some('@') => BondPrimitive::Ring,
+            Some('/') => BondPrimitive::Up,
+            Some('\\') => BondPrimitive::Down,
+            _ => return Err(self.error("invalid bond")),
+        };
+        Ok(primitive)
+    }
+
+    fn count(&mut self, default: u8) -> Result<u8> {
+        match self.buf.to_number() {
+            None => Ok(default),
+            Some(n) => u8::try_from(n).map_err(|_| self.error("number too large")),
+        }
+    }
+
+    fn optional_count(&mut self) -> Result<Option<u8>> {
+        match self.buf.next_is_digit() {
+            true => Ok(Some(self.count(0)?)),
+            false => Ok(None),
+        }
+    }
+
+    fn read_charge(&mut self, sign: char) -> Result<i8> {
+        let mut n = match self.buf.to_number() {
+            Some(n) => i8::try_from(n).map_err(|_| self.error("charge too large"))?,
+            None => {
+                let mut n = 1;
+                while self.buf.is_tar_with_progress(sign) {
+                    n += 1;
+                }
+                n
+            }
+        };
+        if sign == '-' {
+            n = -n;
+        }
+        Ok(n)
+    }
+
+    /// Two letter elements take precedence over a primitive and a symbol.
+    fn read_two_letter(&mut self, c: char) -> Option<AtomPrimitive> {
+        let next = self.buf.next().filter(|n| n.is_ascii_lowercase())?;
+        let symbol: String = [c.to_ascii_uppercase(), next].iter().collect();
+        let e = Element::read(&symbol)?;
+        let aromatic = c.is_ascii_lowercase();
+        if aromatic && !e.is_aromatic(Specification::OpenSMILES) {
+            return None;
+        }
+        self.buf.next_with_progress();
+        Some(AtomPrimitive::Symbol(e.atomic_number(), aromatic))
+    }
+
+    fn read_atom_primitive(&mut self) -> Result<AtomPrimitive> {
+        let start = self.buf.position();
+        if let Some(n) = self.buf.to_number() {
+            let n = u16::try_from(n).map_err(|_| self.error("isotope too large"))?;
+            return Ok(AtomPrimitive::Isotope(n));
+        }
+        let c = self
+            .buf
+            .next_with_progress()
+            .ok_or(self.error("unclosed bracket atom"))?;
+        if c.is_ascii_alphabetic() {
+            if let Some(p) = self.read_two_letter(c) {
+                return Ok(p);
+            }
+        }
+        // `[H]`, `[2H]` and `[H+]` are hydrogen atoms, not counts
+        let leading = self
+            .buf
+            .substr(self.bracket, start)
+            .chars()
+            .all(|c| c.is_ascii_digit());
+        let primitive = match c {
+            'H' if leading && !self.buf.next_is_digit() => AtomPrimitive::Symbol(1, false),
+            '*' => AtomPrimitive::Any,
+            'a' => AtomPrimitive::Aromatic,
+            'A' => AtomPrimitive::Aliphatic,
+            '#' => match self.buf.to_number() {
+                Some(n) if n <= u8::MAX as usize => AtomPrimitive::AtomicNumber(n as u8),
+                _ => return Err(self.error("need an atomic number after '#'")),
+            },
+            'D' => AtomPrimitive::Degree(self.count(1)?),
+            'X' => AtomPrimitive::Connectivity(self.count(1)?),
+            'H' => AtomPrimitive::TotalH(self.count(1)?),
+            'h' => AtomPrimitive::ImplicitH(self.count(1)?),
+            'v' => AtomPrimitive::Valence(self.count(1)?),
+            'R' => AtomPrimitive::RingCount(self.optional_count()?),
+            'r' => AtomPrimitive::RingSize(self.optional_count()?),
+            '+' | '-' => AtomPrimitive::Charge(self.read_charge(c)?),
+            '$' => {
+                if !self.buf.is_tar_with_progress('(') {
+                    return Err(self.error("need '(' after '$'"));
+                }
+                let outer = self.bracket;
+                let query = self.read_query(true)?;
+                self.bracket = outer;
+                if !self.buf.is_tar_with_progress(')') {
+                    return Err(self.error("unclosed recursive SMARTS"));
+                }
+                AtomPrimitive::Recursive(Box::new(query))
+            }
+            'b' | 'c' | 'n' | 'o' | 'p' | 's' => {
+                let e = Element::read(&c.to_ascii_uppercase().to_string()).unwrap();
+                AtomPrimitive::Symbol(e.atomic_number(), true)
+            }
+            c if c.is_ascii_uppercase() => match Element::read(&c.to_string()) {
+                Some(e) => AtomPrimitive::Symbol(e.atomic_number(), false),
+                None => return Err(RuatomError::IllegalSMARTS("unknown element", start)),
+            },
+            _ => return Err(RuatomError::IllegalSMARTS("invalid atom primitive", start)),
+        };
+        Ok(primitive)
+    }
+}
@@ -0,0 +1,209 @@
+//! SMILES files, one record per line.
+//!
+//! Plain `.smi` lines hold the SMILES followed by an optional title, `.csv`
+//! style files have a header and delimited columns. Blank lines and lines
+//! starting with `#` are skipped. Every record yields its own `Result`, a bad
+//! line reports its number and text and the stream goes on. [`SmiReader::lines`]
+//! gives the split but unparsed lines for rayon's `par_bridge`, and
+//! [`SmiReader::par_read`] parses a whole file in parallel keeping file order.
+
+use crate::error::{Result, RuatomError};
+use crate::molecule::Molecule;
+use crate::parser::Parser;
+use rayon::prelude::*;
+use std::io::BufRead;
+
+/// One line split into the SMILES and the remaining columns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmiLine {
+    pub line: usize,
+    pub text: String,
+    pub smiles: String,
+    pub fields: Vec<String>,
+}
+
+impl SmiLine {
+    pub fn parse(self) -> Result<SmiRecord> {
+        let parsed = match self.smiles.is_empty() {
+            true => Err(RuatomError::IllegalSMILES("empty SMILES")),
+            false => Parser::new(&self.smiles).parse(),
+        };
+        match parsed {
+            Ok(molecule) => Ok(SmiRecord {
+                line: self.line,
+                fields: self.fields,
+                molecule,
+            }),
+            Err(e) => Err(RuatomError::IllegalSmilesRecord(
+                self.line,
+                self.text,
+                Box::new(e),
+            )),
+        }
+    }
+}
+
+/// A parsed line, `fields` are the columns other than the SMILES.
+pub struct SmiRecord {
+    pub line: usize,
+    pub fields: Vec<String>,
+    pub molecule: Molecule,
+}
+
+impl SmiRecord {
+    /// The first column after the SMILES.
+    pub fn title(&self) -> Option<&str> {
+        self.fields.first().map(|f| f.as_str())
+    }
+}
+
+/// Iterator over the split lines of a SMILES file.
+pub struct SmiLines<R> {
+    reader: R,
+    line: usize,
+    delimiter: Option<char>,
+    smiles_column: usize,
+    done: bool,
+}
+
+impl<R: BufRead> SmiLines<R> {
+    fn read_line(&mut self) -> Option<Result<(usize, String)>> {
+        let mut text = String::new();
+        while !self.done {
+            text.clear();
+            match self.reader.read_line(&mut text) {
+                Ok(0) => self.done = true,
+                Ok(_) => {
+                    self.line += 1;
+                    let text = text.trim_end_matches(['\n', '\r']);
+                    if !text.trim().is_empty() && !text.starts_with('#') {
+                        return Some(Ok((self.line, text.to_string())));
+                    }
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(RuatomError::StdError(e.to_string())));
+                }
+            }
+        }
+        None
+    }
+
+    fn split(&self, text: &str) -> Vec<String> {
+        match self.delimiter {
+            None => {
+                let text = text.trim();
+                match text.split_once(char::is_whitespace) {
+                    Some((smi, title)) => vec![smi.to_string(), title.trim().to_string()],
+                    None => vec![text.to_string()],
+                }
+            }
+            Some(d) => split_delimited(text, d),
+        }
+    }
+}
+
+/// Splits on `delim` outside of double quotes, `""` inside quotes is a quote.
+fn split_delimited(text: &str, delim: char) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delim && !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields.into_iter().map(|f| f.trim().to_string()).collect()
+}
+
+impl<R: BufRead> Iterator for SmiLines<R> {
+    type Item = Result<SmiLine>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (line, text) = match self.read_line()? {
+            Ok(l) => l,
+            Err(e) => return Some(Err(e)),
+        };
+        let mut fields = self.split(&text);
+        let smiles = match self.smiles_column < fields.len() {
+            true => fields.remove(self.smiles_column),
+            false => String::new(),
+        };
+        Some(Ok(SmiLine {
+            line,
+            text,
+            smiles,
+            fields,
+        }))
+    }
+}
+
+/// Reads [`SmiRecord`]s one line at a time.
+pub struct SmiReader<R> {
+    lines: SmiLines<R>,
+    columns: Vec<String>,
+}
+
+impl<R: BufRead> SmiReader<R> {
+    /// A `.smi` reader: SMILES, whitespace, then the rest of the line as title.
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: SmiLines {
+                reader,
+                line: 0,
+                delimiter: None,
+                smiles_column: 0,
+                done: false,
+            },
+            columns: vec![],
+        }
+    }
+
+    /// A delimited reader, the first line is the header. The SMILES column is
+    /// the one named `smiles` (any case), the first column otherwise.
+    pub fn csv(reader: R, delimiter: char) -> Result<Self> {
+        let mut reader = Self::new(reader);
+        reader.lines.delimiter = Some(delimiter);
+        if let Some(header) = reader.lines.read_line() {
+            let columns = reader.lines.split(&header?.1);
+            reader.lines.smiles_column = columns
+                .iter()
+                .position(|c| c.eq_ignore_ascii_case("smiles"))
+                .unwrap_or(0);
+            reader.columns = columns;
+        }
+        Ok(reader)
+    }
+
+    /// Header names of a delimited file, SMILES column included.
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// The lines split but not parsed.
+    pub fn lines(self) -> SmiLines<R> {
+        self.lines
+    }
+
+    /// Parses all remaining lines in parallel, results are in file order.
+    pub fn par_read(self) -> Vec<Result<SmiRecord>> {
+        let lines: Vec<Result<SmiLine>> = self.lines.collect();
+        lines.into_par_iter().map(|l| l?.parse()).collect()
+    }
+}
+
+impl<R: BufRead> Iterator for SmiReader<R> {
+    type Item = Result<SmiRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.lines.next().map(|l| l?.parse())
+    }
+}
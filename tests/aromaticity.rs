@@ -0,0 +1,125 @@
+#[cfg(test)]
+mod test {
+    use ruatom::molecule::{element::Specification, Basic, Huckel, Mdl};
+    use ruatom::parser::Parser;
+
+    fn huckel(smiles: &str) -> String {
+        let m = Parser::new(smiles)
+            .aromaticity(Huckel::default())
+            .parse()
+            .unwrap();
+        m.to_smiles().unwrap()
+    }
+
+    fn mdl(smiles: &str) -> String {
+        let m = Parser::new(smiles).aromaticity(Mdl).parse().unwrap();
+        m.to_smiles().unwrap()
+    }
+
+    #[test]
+    fn test_huckel() {
+        assert_eq!(huckel("C1=CC=CC=C1"), "c1ccccc1");
+        assert_eq!(huckel("C1=CNC=C1"), "c1cc[nH]c1");
+        assert_eq!(huckel("O=C1C=CNC=C1"), "O=c1cc[nH]cc1");
+        assert_eq!(huckel("C1=CC=C[CH-]1"), "c1cc[cH-]c1");
+        assert_eq!(huckel("[CH+]1C=C1"), "c1c[cH+]1");
+        // 4n electrons
+        assert_eq!(huckel("C1=CC=CC=CC=C1"), "C1=CC=CC=CC=C1");
+        assert_eq!(huckel("B1C=CC=C1"), "B1C=CC=C1");
+        assert_eq!(huckel("O=C1C=CC(=O)C=C1"), "O=C1C=CC(=O)C=C1");
+    }
+
+    #[test]
+    fn test_huckel_fused() {
+        // azulene, only the 10 electron periphery counts
+        let m = Parser::new("C1=CC2=CC=CC=CC2=C1")
+            .aromaticity(Huckel::default())
+            .parse()
+            .unwrap();
+        assert!(m
+            .atoms()
+            .iter()
+            .all(|a| m.atom_at(a).unwrap().is_aromatic()));
+        assert_eq!(huckel("C1=CC=C2C=CC=CC2=C1"), "c1cccc2ccccc12");
+
+        let porphine = "C1=CC2=CC3=CC=C(N3)C=C4C=CC(=N4)C=C5C=CC(N5)=CC1=N2";
+        let m = Parser::new(porphine)
+            .aromaticity(Huckel::default())
+            .parse()
+            .unwrap();
+        assert!(m
+            .atoms()
+            .iter()
+            .all(|a| m.atom_at(a).unwrap().is_aromatic()));
+    }
+
+    #[test]
+    fn test_huckel_specification() {
+        let m = Parser::new("c1cc[se]c1")
+            .aromaticity(Huckel::new(Specification::DayLight))
+            .parse()
+            .unwrap();
+        assert!(m
+            .atoms()
+            .iter()
+            .all(|a| m.atom_at(a).unwrap().is_aromatic()));
+        let m = Parser::new("C1=CC=C[Te]1")
+            .aromaticity(Huckel::new(Specification::DayLight))
+            .parse()
+            .unwrap();
+        assert!(m
+            .atoms()
+            .iter()
+            .all(|a| !m.atom_at(a).unwrap().is_aromatic()));
+    }
+
+    #[test]
+    fn test_mdl() {
+        assert_eq!(mdl("C1=CC=CC=C1"), "c1ccccc1");
+        assert_eq!(mdl("c1ccncc1"), "c1ccncc1");
+        assert_eq!(mdl("C1=CC=C2C=CC=CC2=C1"), "c1cccc2ccccc12");
+        // lone pairs and exocyclic double bonds don't count
+        assert_eq!(mdl("c1cc[nH]c1"), "C1C=CNC=1");
+        assert_eq!(mdl("o1cccc1"), "C1C=COC=1");
+        assert_eq!(mdl("O=c1cc[nH]cc1"), "O=C1C=CNC=C1");
+        let m = Parser::new("c1cc2cccccc2c1")
+            .aromaticity(Mdl)
+            .parse()
+            .unwrap();
+        assert!(m
+            .atoms()
+            .iter()
+            .all(|a| !m.atom_at(a).unwrap().is_aromatic()));
+    }
+
+    #[test]
+    fn test_input_form() {
+        let pairs = [
+            ("c1ccccc1", "C1=CC=CC=C1"),
+            ("c1cc[nH]c1", "C1=CNC=C1"),
+            ("o1cccc1", "C1=COC=C1"),
+            ("O=c1cc[nH]cc1", "O=C1C=CNC=C1"),
+            ("Cn1ccnc1", "CN1C=CN=C1"),
+        ];
+        for (aromatic, kekule) in pairs {
+            assert_eq!(huckel(aromatic), huckel(kekule), "{}", aromatic);
+            assert_eq!(mdl(aromatic), mdl(kekule), "{}", aromatic);
+        }
+        // basic leaves Kekulé input alone, forms only meet where it drops
+        // the aromaticity of the input
+        let basic = |s| Parser::new(s).aromaticity(Basic).parse().unwrap();
+        let a = basic("O=c1cc[nH]cc1").to_smiles().unwrap();
+        assert_eq!(a, basic("O=C1C=CNC=C1").to_smiles().unwrap());
+    }
+
+    #[test]
+    fn test_perceive_again() {
+        let mut m = Parser::new("c1cc[nH]c1").parse().unwrap();
+        m.perceive_aromaticity(Mdl).unwrap();
+        assert_eq!(m.to_smiles().unwrap(), "C1C=CNC=1");
+        m.perceive_aromaticity(Huckel::default()).unwrap();
+        assert_eq!(m.to_smiles().unwrap(), "c1cc[nH]c1");
+        m.perceive_aromaticity(Basic).unwrap();
+        assert_eq!(m.to_smiles().unwrap(), "c1cc[nH]c1");
+    }
+}
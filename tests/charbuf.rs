@@ -147,6 +147,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::zero_prefixed_literal)]
     fn test_to_number() {
         assert_eq!(CharBuffer::from_str("1").to_number(), Some(1));
 
@@ -173,6 +174,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::zero_prefixed_literal)]
     fn test_to_sub_number() {
         let mut cb_beta = CharBuffer::from_str("002C");
         assert_eq!(Some(0), cb_beta.to_sub_number(&mut 0));
@@ -0,0 +1,402 @@
+#[cfg(test)]
+mod test {
+    use ruatom::fingerprint::{
+        maccs_keys, AtomPairs, BitVector, CountVector, Fingerprint, Metric, Morgan, Paths,
+        SimilaritySearch, Torsions, MACCS_LENGTH,
+    };
+    use ruatom::molecule::Molecule;
+    use ruatom::Parser;
+
+    fn mol(smiles: &str) -> Molecule {
+        Parser::new(smiles).parse().unwrap()
+    }
+
+    #[test]
+    fn test_morgan_environments() {
+        // three atoms, three bond sets at radius 1, nothing new at radius 2
+        let envs = Morgan::new(2).environments(&mol("CCO")).unwrap();
+        assert_eq!(envs.len(), 6);
+        assert_eq!(envs.iter().filter(|e| e.radius == 1).count(), 3);
+
+        // both carbons of ethane cover the same bond
+        let counts = Morgan::new(2).counts(&mol("CC")).unwrap();
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts.values().sum::<u32>(), 3);
+
+        let counts = Morgan::new(2).counts(&mol("c1ccccc1")).unwrap();
+        assert_eq!(counts.len(), 3);
+        assert!(counts.values().all(|c| *c == 6));
+
+        assert!(Morgan::new(0)
+            .environments(&mol("CCO"))
+            .unwrap()
+            .iter()
+            .all(|e| e.radius == 0));
+    }
+
+    #[test]
+    fn test_morgan_atom_order() {
+        for (a, b) in [
+            ("CCO", "OCC"),
+            ("c1ccccc1C(=O)O", "OC(=O)c1ccccc1"),
+            ("CC(C)Cc1ccc(cc1)C(C)C(O)=O", "OC(=O)C(C)c1ccc(CC(C)C)cc1"),
+        ] {
+            for morgan in [Morgan::new(3), Morgan::new(2).features(true)] {
+                assert_eq!(
+                    morgan.counts(&mol(a)).unwrap(),
+                    morgan.counts(&mol(b)).unwrap(),
+                    "{} {}",
+                    a,
+                    b
+                );
+            }
+        }
+        assert_ne!(
+            Morgan::new(2).counts(&mol("CCO")).unwrap(),
+            Morgan::new(2).counts(&mol("COC")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_morgan_bits() {
+        let m = mol("CC(=O)Oc1ccccc1C(=O)O");
+        let morgan = Morgan::new(2).length(64);
+        let bits = morgan.bits(&m).unwrap();
+        assert!(bits.windows(2).all(|w| w[0] < w[1]));
+        assert!(bits.iter().all(|b| *b < 64));
+
+        let info = morgan.bit_info(&m).unwrap();
+        assert_eq!(info.keys().copied().collect::<Vec<_>>(), bits);
+        for (bit, envs) in info.iter() {
+            for (centre, radius) in envs {
+                assert!(*radius <= 2);
+                let env = Morgan::new(2)
+                    .environments(&m)
+                    .unwrap()
+                    .into_iter()
+                    .find(|e| e.centre == *centre && e.radius == *radius)
+                    .unwrap();
+                assert_eq!(env.id as usize % 64, *bit);
+            }
+        }
+    }
+
+    #[test]
+    fn test_morgan_chirality() {
+        let l = mol("N[C@@H](C)C(=O)O");
+        let d = mol("N[C@H](C)C(=O)O");
+        let flat = Morgan::new(2);
+        assert_eq!(flat.counts(&l).unwrap(), flat.counts(&d).unwrap());
+        let chiral = Morgan::new(2).chirality(true);
+        assert_ne!(chiral.counts(&l).unwrap(), chiral.counts(&d).unwrap());
+        assert_eq!(
+            chiral.counts(&l).unwrap(),
+            chiral.counts(&mol("C[C@H](N)C(=O)O")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_morgan_features() {
+        // halogens are one feature
+        let fcfp = Morgan::new(2).features(true);
+        assert_eq!(
+            fcfp.counts(&mol("Clc1ccccc1")).unwrap(),
+            fcfp.counts(&mol("Brc1ccccc1")).unwrap()
+        );
+        let ecfp = Morgan::new(2);
+        assert_ne!(
+            ecfp.counts(&mol("Clc1ccccc1")).unwrap(),
+            ecfp.counts(&mol("Brc1ccccc1")).unwrap()
+        );
+        // an amine is basic, an amide is not
+        assert_ne!(
+            fcfp.counts(&mol("CCN")).unwrap(),
+            fcfp.counts(&mol("CC(N)=O")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_paths_counts() {
+        // three atoms, two bonds and one path of two bonds, all carbon
+        let counts = Paths::default().counts(&mol("CCC")).unwrap();
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts.values().sum::<u32>(), 6);
+        assert_eq!(
+            Paths::new(1)
+                .counts(&mol("CCC"))
+                .unwrap()
+                .values()
+                .sum::<u32>(),
+            5
+        );
+        // a six-ring has six paths of each length from one to five bonds
+        let counts = Paths::new(5).counts(&mol("C1CCCCC1")).unwrap();
+        assert_eq!(counts.len(), 6);
+        assert!(counts.values().all(|c| *c == 6));
+        assert_ne!(
+            Paths::default().counts(&mol("CCO")).unwrap(),
+            Paths::default().counts(&mol("COC")).unwrap()
+        );
+        assert_eq!(
+            Paths::default().counts(&mol("OC(=O)c1ccccc1")).unwrap(),
+            Paths::default().counts(&mol("c1ccccc1C(O)=O")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_paths_bits() {
+        let m = mol("CC(=O)Oc1ccccc1C(=O)O");
+        let paths = Paths::default().length(128).bits_per_path(3);
+        let bits = paths.bits(&m).unwrap();
+        assert!(bits.windows(2).all(|w| w[0] < w[1]));
+        assert!(bits.iter().all(|b| *b < 128));
+        assert!(
+            bits.len()
+                > Paths::default()
+                    .length(128)
+                    .bits_per_path(1)
+                    .bits(&m)
+                    .unwrap()
+                    .len()
+        );
+    }
+
+    #[test]
+    fn test_paths_screen() {
+        let targets = [
+            "CC(=O)Oc1ccccc1C(=O)O",
+            "CN1C=NC2=C1C(=O)N(C(=O)N2C)C",
+            "CC(C)Cc1ccc(cc1)C(C)C(O)=O",
+            "N[C@@H](Cc1ccc(O)cc1)C(=O)O",
+            "C1CCC2CCCCC2C1",
+            "c1ccc2ccccc2c1",
+            "OCC(O)CO",
+            "C/C=C/C(=O)OCC",
+            "ClC(Cl)(Cl)Cl",
+            "c1ccncc1CC#N",
+        ];
+        let queries = [
+            "C", "O", "CC", "C=O", "C(=O)O", "c1ccccc1", "cO", "CCC", "C1CCCCC1", "ccc", "CC(C)C",
+            "OCCO", "C=CC", "CCl", "c1ccncc1", "C#N", "cC", "N", "CN", "C(=O)N",
+        ];
+        let paths = Paths::default().length(512);
+        let mut checked = 0;
+        for t in targets {
+            let target = mol(t);
+            let tbits = paths.bits(&target).unwrap();
+            for q in queries {
+                let query = mol(q);
+                if !target.has_substructure(&query).unwrap() {
+                    continue;
+                }
+                checked += 1;
+                for bit in paths.bits(&query).unwrap() {
+                    assert!(tbits.binary_search(&bit).is_ok(), "{} in {}", q, t);
+                }
+            }
+        }
+        assert!(checked > 50, "{}", checked);
+        // the screen rules out what is not there
+        let qbits = paths.bits(&mol("C#N")).unwrap();
+        let tbits = paths.bits(&mol("OCC(O)CO")).unwrap();
+        assert!(qbits.iter().any(|b| tbits.binary_search(b).is_err()));
+    }
+
+    #[test]
+    fn test_maccs_keys() {
+        for (smiles, keys) in [
+            ("CCO", vec![82, 109, 114, 139, 153, 155, 157, 160, 164]),
+            ("c1ccccc1", vec![162, 163, 165]),
+            ("CC(=O)O", vec![123, 139, 154, 157, 159, 160, 164]),
+            (
+                "CC(=O)Oc1ccccc1C(=O)O",
+                vec![
+                    89, 113, 123, 126, 127, 136, 139, 140, 143, 144, 146, 150, 152, 154, 157, 159,
+                    160, 162, 163, 164, 165,
+                ],
+            ),
+            ("CC(C)(C)C", vec![66, 74, 112, 141, 149, 160]),
+            ("c1ccc2ccccc2c1", vec![101, 105, 125, 145, 162, 163, 165]),
+            ("C1CCCCCCC1", vec![101, 118, 128, 129, 147, 165]),
+            ("[Na+].[Cl-]", vec![35, 44, 49, 103, 134, 166]),
+            ("[13CH4]", vec![1, 160]),
+        ] {
+            assert_eq!(maccs_keys(&mol(smiles)).unwrap().bits(), keys, "{}", smiles);
+        }
+        let keys = maccs_keys(&mol("Cn1cnc2c1c(=O)n(C)c(=O)n2C")).unwrap();
+        assert_eq!(keys.len(), MACCS_LENGTH);
+        assert!(!keys.get(0));
+        // five membered ring, aromatic, C=O, more than one N
+        for key in [96, 142, 154, 162] {
+            assert!(keys.get(key), "{}", key);
+        }
+        assert_eq!(maccs_keys(&mol("C")).unwrap().bits(), [160]);
+        assert!(!maccs_keys(&mol("*CC")).unwrap().get(44));
+    }
+
+    #[test]
+    fn test_atom_pairs() {
+        let pairs = AtomPairs::default();
+        for smiles in ["CCO", "c1ccccc1O", "CC(=O)Oc1ccccc1C(=O)O"] {
+            let m = mol(smiles);
+            let n = m.atoms().len() as u32;
+            let total: u32 = pairs.counts(&m).unwrap().values().sum();
+            assert_eq!(total, n * (n - 1) / 2, "{}", smiles);
+        }
+        // symmetric pairs collapse onto one hash
+        assert_eq!(pairs.counts(&mol("CC")).unwrap().len(), 1);
+        assert_eq!(pairs.counts(&mol("OCCO")).unwrap().len(), 4);
+        // hydrogens and disconnected pairs are left out
+        assert_eq!(
+            pairs.counts(&mol("[H]OC.C")).unwrap().values().sum::<u32>(),
+            1
+        );
+        let far = AtomPairs::new().max_distance(2);
+        assert_eq!(far.counts(&mol("CCCC")).unwrap().values().sum::<u32>(), 5);
+        let near = AtomPairs::new().min_distance(2);
+        assert_eq!(near.counts(&mol("CCCC")).unwrap().values().sum::<u32>(), 3);
+        // double bonds change the pi electron count
+        assert_ne!(
+            pairs.counts(&mol("CC=C")).unwrap(),
+            pairs.counts(&mol("CCC")).unwrap()
+        );
+        assert_eq!(
+            pairs.counts(&mol("OCC(N)C")).unwrap(),
+            pairs.counts(&mol("CC(N)CO")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_torsions() {
+        let total = |smiles: &str| {
+            Torsions::counts(&mol(smiles))
+                .unwrap()
+                .values()
+                .sum::<u32>()
+        };
+        assert_eq!(total("CCC"), 0);
+        assert_eq!(total("CCCC"), 1);
+        assert_eq!(total("CC(C)CC"), 2);
+        assert_eq!(total("C1CCCCC1"), 6);
+        assert_eq!(total("[H]CCC[H]"), 0);
+        assert_eq!(Torsions::counts(&mol("C1CCCCC1")).unwrap().len(), 1);
+        assert_eq!(
+            Torsions::counts(&mol("CC(O)CCN")).unwrap(),
+            Torsions::counts(&mol("NCCC(O)C")).unwrap()
+        );
+        assert_ne!(
+            Torsions::counts(&mol("CCCC")).unwrap(),
+            Torsions::counts(&mol("CC=CC")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_bit_vector() {
+        let mut a = BitVector::new(130);
+        assert_eq!(a.popcount(), 0);
+        a.set(0, true);
+        a.set(64, true);
+        a.set(129, true);
+        a.set(130, true);
+        assert_eq!(a.bits(), vec![0, 64, 129]);
+        a.set(64, false);
+        assert!(!a.get(64) && a.get(129) && !a.get(500));
+        let b = BitVector::from_bits(130, &[0, 1, 129]);
+        assert_eq!(a.common(&b), 2);
+        assert_eq!(a.tanimoto(&b), 2.0 / 3.0);
+        assert_eq!(a.dice(&b), 0.8);
+        assert_eq!(a.tversky(&b, 1.0, 0.0), 1.0);
+        assert_eq!(a.tversky(&b, 0.0, 1.0), 2.0 / 3.0);
+        assert_eq!(a.cosine(&b), 2.0 / 6f64.sqrt());
+        assert_eq!(a.hamming(&b), 1);
+        let empty = BitVector::new(130);
+        assert_eq!(empty.tanimoto(&empty), 0.0);
+        assert_eq!(empty.cosine(&a), 0.0);
+
+        let morgan = Morgan::default();
+        let (m, n) = (mol("CCO"), mol("OCC"));
+        let v = morgan.bit_vector(&m).unwrap();
+        assert_eq!(v.len(), 2048);
+        assert_eq!(v.bits(), morgan.bits(&m).unwrap());
+        assert_eq!(v.tanimoto(&morgan.bit_vector(&n).unwrap()), 1.0);
+        assert_eq!(maccs_keys(&m).unwrap().popcount(), 9);
+    }
+
+    #[test]
+    #[should_panic(expected = "bit vectors differ in length")]
+    fn test_bit_vector_lengths() {
+        let a = BitVector::from_bits(1024, &[1, 2]);
+        let b = BitVector::from_bits(2048, &[1, 2, 1500]);
+        a.tanimoto(&b);
+    }
+
+    #[test]
+    fn test_count_vector() {
+        let a = CountVector::from_iter([(1, 2), (2, 1), (3, 0), (1, 1)]);
+        assert_eq!(a.len(), 2);
+        assert_eq!(a.get(1), 3);
+        assert_eq!(a.popcount(), 4);
+        let mut b = CountVector::new();
+        b.set(1, 1);
+        b.set(4, 3);
+        b.set(4, 0);
+        b.set(5, 1);
+        assert_eq!(b.counts().keys().copied().collect::<Vec<_>>(), vec![1, 5]);
+        assert_eq!(a.common(&b), 1);
+        assert_eq!(a.tanimoto(&b), 0.2);
+        assert_eq!(a.dice(&b), 1.0 / 3.0);
+        assert_eq!(a.cosine(&b), 3.0 / (10f64.sqrt() * 2f64.sqrt()));
+        assert_eq!(a.hamming(&b), 4);
+        assert_eq!(a.hamming(&a), 0);
+
+        let pairs = AtomPairs::default();
+        let x = CountVector::from(pairs.counts(&mol("CCCO")).unwrap());
+        let y = CountVector::from(pairs.counts(&mol("OCCC")).unwrap());
+        assert_eq!(x.tanimoto(&y), 1.0);
+        let z = CountVector::from(pairs.counts(&mol("CCCN")).unwrap());
+        assert!(x.tanimoto(&z) > 0.0 && x.tanimoto(&z) < 1.0);
+    }
+
+    #[test]
+    fn test_similarity_search() {
+        let morgan = Morgan::default();
+        let smiles = ["c1ccccc1O", "CCO", "c1ccccc1N", "Oc1ccccc1", "CCCCCC"];
+        let database: Vec<BitVector> = smiles
+            .iter()
+            .map(|s| morgan.bit_vector(&mol(s)).unwrap())
+            .collect();
+        let query = morgan.bit_vector(&mol("Oc1ccccc1")).unwrap();
+        let hits = SimilaritySearch::new(Metric::Tanimoto).run(&query, &database);
+        assert_eq!(hits.len(), 5);
+        assert_eq!(hits[0], (0, 1.0));
+        assert_eq!(hits[1], (3, 1.0));
+        assert_eq!(hits[2].0, 2);
+        assert!(hits.windows(2).all(|w| w[0].1 >= w[1].1));
+        let top = SimilaritySearch::new(Metric::Dice)
+            .top(2)
+            .run(&query, &database);
+        assert_eq!(top.iter().map(|h| h.0).collect::<Vec<_>>(), vec![0, 3]);
+        let close = SimilaritySearch::new(Metric::Cosine)
+            .threshold(0.99)
+            .run(&query, &database);
+        assert_eq!(close.len(), 2);
+        let none = SimilaritySearch::default()
+            .threshold(1.1)
+            .run(&query, &database);
+        assert!(none.is_empty());
+        // only what the query lacks counts against a candidate
+        let tversky = Metric::Tversky(1.0, 0.0);
+        let phenol = &database[0];
+        assert_eq!(tversky.similarity(phenol, &query), 1.0);
+        assert!(tversky.similarity(&database[1], &query) < 1.0);
+
+        let pairs = AtomPairs::default();
+        let counts: Vec<CountVector> = smiles
+            .iter()
+            .map(|s| pairs.counts(&mol(s)).unwrap().into())
+            .collect();
+        let query: CountVector = pairs.counts(&mol("OCC")).unwrap().into();
+        let hits = SimilaritySearch::default().top(1).run(&query, &counts);
+        assert_eq!(hits, vec![(1, 1.0)]);
+    }
+}
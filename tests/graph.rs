@@ -63,12 +63,12 @@ mod test {
     fn test_neighbors() {
         let mut g = create_graph();
         let nei = g.neighbors(&0).unwrap();
-        assert_eq!(vec![&1, &2], nei.collect::<Vec<&u8>>());
+        assert_eq!(vec![&1, &2], nei.collect::<Vec<&u32>>());
         g.add_edge(3, 0, "Double").unwrap();
         let nei = g.neighbors(&0).unwrap();
-        assert_eq!(vec![&1, &2, &3], nei.collect::<Vec<&u8>>());
+        assert_eq!(vec![&1, &2, &3], nei.collect::<Vec<&u32>>());
         let nei = g.neighbors(&2).unwrap();
-        assert_eq!(vec![&0, &3], nei.collect::<Vec<&u8>>());
+        assert_eq!(vec![&0, &3], nei.collect::<Vec<&u32>>());
         if let Err(e) = g.neighbors(&4) {
             assert!(e == RuatomError::NoSuchVertex(4));
         };
@@ -185,4 +185,49 @@ mod test {
         g.replace(e, "Si");
         assert_eq!(g.edge_with_vertex(0, 2).unwrap(), &"Si");
     }
+    #[test]
+    fn test_map_edges() {
+        let mut g = Graph::new();
+        g.add_vertex(1, "C").unwrap();
+        g.add_vertex(5, "O").unwrap();
+        g.add_vertex(9, "N").unwrap();
+        g.add_direction_edge(1, 5, "/", "\\").unwrap();
+        g.add_edge(9, 1, "-").unwrap();
+        assert_eq!(g.order(), 3);
+        assert_eq!(g.size(), 2);
+        let mut edges = vec![];
+        g.map_edges(|e, desc| edges.push((*e.outbound(), *e.inbound(), *desc)))
+            .unwrap();
+        assert_eq!(
+            edges,
+            vec![(1, 5, "/"), (5, 1, "\\"), (9, 1, "-"), (1, 9, "-")]
+        );
+        assert_eq!(g.edge_with_vertex(5, 1), Ok(&"\\"));
+    }
+    #[test]
+    fn test_remove_edge() {
+        let mut g = create_graph();
+        assert_eq!(g.remove_edge(2, 0), Ok("Double"));
+        assert!(!g.adjancent(0, 2));
+        assert_eq!(g.size(), 3);
+        assert_eq!(g.bound_count(&0), Ok(1));
+        assert_eq!(g.remove_edge(2, 0), Err(RuatomError::NoSuchEdge(0, 2)));
+        assert!(g.adjancent(2, 3));
+        assert!(g.adjancent(1, 3));
+        assert_eq!(g.edge_with_vertex(3, 1), Ok(&"Double"));
+    }
+
+    #[test]
+    fn test_remove_vertex() {
+        let mut g = create_graph();
+        assert_eq!(g.remove_vertex(1), Ok("H"));
+        assert_eq!(g.order(), 3);
+        assert_eq!(g.size(), 2);
+        assert_eq!(g.vertex(&1), Ok(&"O"));
+        assert_eq!(g.vertex(&2), Ok(&"N"));
+        assert_eq!(g.vertex(&3), Err(RuatomError::NoSuchVertex(3)));
+        let nei = g.neighbors(&1).unwrap();
+        assert_eq!(vec![&0, &2], nei.collect::<Vec<&u32>>());
+        assert_eq!(g.remove_vertex(3), Err(RuatomError::NoSuchVertex(3)));
+    }
 }
@@ -0,0 +1,115 @@
+#[cfg(test)]
+mod test {
+    use ruatom::error::RuatomError;
+    use ruatom::molecule::{MolKey, MolKeyBuilder};
+    use ruatom::Parser;
+    use std::collections::HashMap;
+
+    fn key(smiles: &str) -> MolKey {
+        Parser::new(smiles).parse().unwrap().key().unwrap()
+    }
+
+    #[test]
+    fn test_same_molecule() {
+        for group in [
+            vec!["CCO", "OCC", "C(O)C", "[CH3][CH2][OH]"],
+            vec!["c1ccccc1", "C1=CC=CC=C1", "C1C=CC=CC=1"],
+            vec!["c1cc[nH]c1", "C1=CNC=C1", "[nH]1cccc1"],
+            vec!["Cc1ccccc1", "CC1=CC=CC=C1", "c1ccc(C)cc1"],
+            vec!["c1ccccc1-c1ccccc1", "C1=CC=CC=C1C1=CC=CC=C1"],
+            vec!["C-C", "CC"],
+            vec!["N[C@@H](C)C(=O)O", "C[C@H](N)C(=O)O", "OC(=O)[C@H](C)N"],
+            vec!["C/C=C/C", "C\\C=C\\C"],
+        ] {
+            for smiles in group.iter() {
+                assert_eq!(key(smiles), key(group[0]), "{} {}", smiles, group[0]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_different_molecules() {
+        let all = [
+            "CCO",
+            "COC",
+            "c1ccccc1",
+            "C1=CC=CC=CC=C1",
+            "c1ccncc1",
+            "CC(=O)O",
+            "CC(=O)[O-]",
+            "C1CC1",
+            "C=CC",
+            "N[C@@H](C)C(=O)O",
+            "N[C@H](C)C(=O)O",
+            "C/C=C/C",
+            "C/C=C\\C",
+        ];
+        let mut seen = HashMap::new();
+        for smiles in all {
+            assert_eq!(seen.insert(key(smiles), smiles), None, "{}", smiles);
+        }
+    }
+
+    #[test]
+    fn test_layers() {
+        assert_eq!(key("CCO").layers(), 0);
+        assert_eq!(
+            key("N[C@@H](C)C(=O)O").connectivity(),
+            key("NC(C)C(=O)O").connectivity()
+        );
+        assert_ne!(key("N[C@@H](C)C(=O)O").layers(), 0);
+        assert_eq!(key("[13CH3]CO").connectivity(), key("CCO").connectivity());
+        assert_ne!(key("[13CH3]CO").layers(), 0);
+        assert_eq!(key("[Fe+2]").connectivity(), key("[Fe]").connectivity());
+        assert_ne!(key("[Fe+2]"), key("[Fe]"));
+        assert_eq!(key("CC=CC").layers(), 0);
+        assert_ne!(key("C/C=C/C").layers(), key("C/C=C\\C").layers());
+
+        let m = |s: &str| Parser::new(s).parse().unwrap();
+        let flat = MolKeyBuilder::new().stereo(false);
+        assert_eq!(
+            flat.build(&m("N[C@@H](C)C(=O)O")).unwrap(),
+            flat.build(&m("N[C@H](C)C(=O)O")).unwrap()
+        );
+        assert_eq!(flat.build(&m("C/C=C/C")).unwrap().layers(), 0);
+        let b = MolKeyBuilder::new().charges(false).isotopes(false);
+        assert_eq!(b.build(&m("[Fe+2]")).unwrap(), b.build(&m("[Fe]")).unwrap());
+        assert_eq!(b.build(&m("[13CH3]CO")).unwrap(), key("CCO"));
+    }
+
+    #[test]
+    fn test_stable() {
+        // changing any of these breaks every stored key
+        assert_eq!(
+            key("c1ccccc1").to_string(),
+            "C377B172FE062215-0000000000000000"
+        );
+        assert_eq!(key("CCO").to_string(), "A78789632A851DD9-0000000000000000");
+        assert_eq!(
+            key("N[C@@H](C)C(=O)O").to_string(),
+            "7E275C626EDE05D4-A1363033C1C06DC4"
+        );
+        assert_eq!(
+            key("[Fe+2]").to_string(),
+            "05A4332F6DBFCA0D-81B17DC1965B5700"
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let k = key("CC(=O)[O-]");
+        assert_eq!(k.to_string().parse::<MolKey>().unwrap(), k);
+        assert_eq!(k.to_string().to_lowercase().parse::<MolKey>().unwrap(), k);
+        for s in [
+            "",
+            "C377B172FE062215",
+            "C377B172FE062215-00",
+            "C377B172FE06221G-0000000000000000",
+        ] {
+            assert_eq!(
+                s.parse::<MolKey>(),
+                Err(RuatomError::IllegalMolKey(s.to_string()))
+            );
+        }
+    }
+}
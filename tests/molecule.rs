@@ -1,6 +1,10 @@
 #[cfg(test)]
 mod test {
-    use ruatom::molecule::{atom::Atom, bond::*, element::*, Molecule};
+    use ruatom::molecule::{
+        atom::Atom, bond::*, element::*, FormulaBuilder, Hydrogens, Molecule, RingSystemKind,
+        SmilesWriter,
+    };
+    use ruatom::parser::Parser;
     use std::collections::HashMap;
 
     #[test]
@@ -99,6 +103,98 @@ mod test {
         assert_eq!(m.ring_num(), 0);
     }
 
+    #[test]
+    fn test_rings() {
+        let m = Parser::new("c1ccc2ccccc2c1").parse().unwrap();
+        assert_eq!(m.n_ssr(), 2);
+        for ring in m.rings() {
+            assert_eq!(ring.len(), 6);
+            assert_eq!(ring.bonds().len(), 6);
+            for (i, b) in ring.bonds().iter().enumerate() {
+                let [u, v] = m.bonds()[*b];
+                let (a, c) = (ring.atoms()[i], ring.atoms()[(i + 1) % 6]);
+                assert!([u, v] == [a, c] || [u, v] == [c, a]);
+            }
+        }
+        assert_eq!(m.atom_rings(&4).len(), 2);
+        assert_eq!(m.atom_rings(&1).len(), 1);
+
+        // spiro
+        let m = Parser::new("C1CCC2(CC1)CC2").parse().unwrap();
+        let sizes: Vec<usize> = m.rings().iter().map(|r| r.len()).collect();
+        assert_eq!(sizes, [3, 6]);
+        assert_eq!(m.atom_rings(&4).len(), 2);
+        assert!(m.is_in_ring_of_size(&4, 3));
+        assert!(!m.is_in_ring_of_size(&1, 3));
+
+        let m = Parser::new("CCO").parse().unwrap();
+        assert!(m.rings().is_empty());
+        assert_eq!(m.n_ssr(), 0);
+    }
+
+    #[test]
+    fn test_relevant_cycles() {
+        // norbornane, the 6 ring is the sum of the two 5 rings
+        let m = Parser::new("C1CC2CCC1C2").parse().unwrap();
+        assert_eq!(m.n_ssr(), 2);
+        assert_eq!(m.relevant_cycles().len(), 2);
+        assert!(!m.is_in_ring_of_size(&1, 6));
+
+        // bicyclo[2.2.2]octane, any two of three 6 rings
+        let m = Parser::new("C1CC2CCC1CC2").parse().unwrap();
+        assert_eq!(m.n_ssr(), 2);
+        assert_eq!(m.relevant_cycles().len(), 3);
+        assert!(m.atoms().iter().all(|a| m.is_in_ring_of_size(a, 6)));
+
+        let m = Parser::new("C12C3C4C1C5C2C3C45").parse().unwrap();
+        assert_eq!(m.n_ssr(), 5);
+        assert_eq!(m.relevant_cycles().len(), 6);
+        assert!(m.relevant_cycles().iter().all(|r| r.len() == 4));
+    }
+
+    #[test]
+    fn test_ring_systems() {
+        let m = Parser::new("c1ccccc1CCC1CC1").parse().unwrap();
+        let systems = m.ring_systems().unwrap();
+        assert_eq!(systems.len(), 2);
+        assert!(systems.iter().all(|s| s.kind() == RingSystemKind::Isolated));
+        assert_eq!(systems[0].aromatic_rings() + systems[1].aromatic_rings(), 1);
+        assert_eq!(
+            systems[0].aliphatic_rings() + systems[1].aliphatic_rings(),
+            1
+        );
+
+        let m = Parser::new("c1ccc2[nH]ccc2c1").parse().unwrap();
+        let systems = m.ring_systems().unwrap();
+        assert_eq!(systems.len(), 1);
+        assert_eq!(systems[0].kind(), RingSystemKind::Fused);
+        assert_eq!(systems[0].ring_count(), 2);
+        assert_eq!(systems[0].aromatic_rings(), 2);
+        assert_eq!(systems[0].hetero_rings(), 1);
+        assert_eq!(systems[0].atoms().len(), 9);
+
+        let m = Parser::new("C1CCC2(CC1)CC2").parse().unwrap();
+        let systems = m.ring_systems().unwrap();
+        assert_eq!(systems[0].kind(), RingSystemKind::Spiro);
+        assert_eq!(systems[0].spiro_atoms(), [4]);
+
+        let m = Parser::new("C1CC2CCC1C2").parse().unwrap();
+        let systems = m.ring_systems().unwrap();
+        assert_eq!(systems[0].kind(), RingSystemKind::Bridged);
+        assert!(systems[0].spiro_atoms().is_empty());
+
+        let m = Parser::new("CCO").parse().unwrap();
+        assert!(m.ring_systems().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rings_after_edit() {
+        let mut m = Parser::new("C1CC2CCC1C2").parse().unwrap();
+        m.remove_bond(3, 7).unwrap();
+        assert_eq!(m.n_ssr(), 1);
+        assert_eq!(m.rings()[0].len(), 6);
+    }
+
     #[test]
     fn test_validate_up_down() {
         let mut m = Molecule::new();
@@ -169,4 +265,346 @@ mod test {
         assert_eq!(3, m.bond_degree_of(&6).unwrap());
         assert_eq!(1, m.bond_degree_of(&7).unwrap());
     }
+
+    #[test]
+    fn test_formula() {
+        for (smiles, formula) in [
+            ("CCO", "C2H6O"),
+            ("[H]OCC", "C2H6O"),
+            ("c1ccccc1", "C6H6"),
+            ("O", "H2O"),
+            ("[H][H]", "H2"),
+            ("ClC(Cl)Cl", "CHCl3"),
+            ("[Na+].[Cl-]", "ClNa"),
+            ("C[N+](C)(C)C", "C4H12N+"),
+            ("[O-]S(=O)(=O)[O-]", "O4S-2"),
+            ("[Fe+2]", "Fe+2"),
+            ("[NH4+].[NH4+].[O-]S(=O)(=O)[O-]", "H8N2O4S"),
+            ("[2H]O[2H]", "H2O"),
+            ("[13CH4]", "CH4"),
+        ] {
+            assert_eq!(
+                Parser::new(smiles).parse().unwrap().formula().unwrap(),
+                formula
+            );
+        }
+        let labelled = FormulaBuilder::new().isotopes(true);
+        for (smiles, formula) in [
+            ("[2H]O[2H]", "D2O"),
+            ("[13CH4]", "[13C]H4"),
+            ("[3H]C([2H])([1H])C", "C2H3[1H]DT"),
+            ("[13CH3][13CH2]C", "C[13C]2H8"),
+            ("[18OH2]", "H2[18O]"),
+            ("[15NH4+]", "H4[15N]+"),
+        ] {
+            assert_eq!(
+                labelled
+                    .build(&Parser::new(smiles).parse().unwrap())
+                    .unwrap(),
+                formula
+            );
+        }
+        let m = Parser::new("CC(=O)[O-].[Na+].O.O").parse().unwrap();
+        assert_eq!(m.formula().unwrap(), "C2H7NaO4");
+        assert_eq!(
+            m.fragment_formulas().unwrap(),
+            vec!["C2H3O2-", "Na+", "H2O", "H2O"]
+        );
+        let m = Parser::new("[2H]OC.[13CH4]").parse().unwrap();
+        assert_eq!(labelled.fragments(&m).unwrap(), vec!["CH3DO", "[13C]H4"]);
+    }
+
+    #[test]
+    fn test_distance_matrix() {
+        let m = Parser::new("CCO").parse().unwrap();
+        assert_eq!(
+            m.distance_matrix().unwrap(),
+            vec![
+                vec![Some(0), Some(1), Some(2)],
+                vec![Some(1), Some(0), Some(1)],
+                vec![Some(2), Some(1), Some(0)],
+            ]
+        );
+        let m = Parser::new("C1CCCCC1C").parse().unwrap();
+        let d = m.distance_matrix().unwrap();
+        assert_eq!(d[0][3], Some(3));
+        assert_eq!(d[0][5], Some(1));
+        assert_eq!(d[3][6], Some(3));
+        let m = Parser::new("CC.O").parse().unwrap();
+        let d = m.distance_matrix().unwrap();
+        assert_eq!(d[0][1], Some(1));
+        assert_eq!(d[0][2], None);
+        assert_eq!(d[2][2], Some(0));
+    }
+
+    #[test]
+    fn test_remove_bond() {
+        let p = Parser::new("C1CCCCC1");
+        let mut m = p.parse().unwrap();
+        assert_eq!(1, m.n_ssr());
+        assert_eq!("", m.remove_bond(1, 6).unwrap().token());
+        assert_eq!(5, m.size());
+        assert_eq!(0, m.n_ssr());
+        assert_eq!(m.hydrogen_count(&1).unwrap(), 3);
+        assert_eq!(m.hydrogen_count(&6).unwrap(), 3);
+        assert_eq!(m.bond_degree_of(&1).unwrap(), 1);
+        assert_eq!("CCCCCC", m.to_smiles().unwrap());
+        assert!(m.remove_bond(1, 6).is_err());
+    }
+
+    #[test]
+    fn test_remove_atom() {
+        let p = Parser::new("c1ccccc1CN");
+        let mut m = p.parse().unwrap();
+        let mapping = m.remove_atom(&7).unwrap();
+        assert_eq!(mapping.len(), 7);
+        assert_eq!(mapping[&6], 6);
+        assert_eq!(mapping[&8], 7);
+        assert!(!mapping.contains_key(&7));
+        assert_eq!(7, m.order());
+        assert_eq!(6, m.size());
+        assert_eq!(m.atoms(), &vec![1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(m.hydrogen_count(&6).unwrap(), 1);
+        assert_eq!(m.hydrogen_count(&7).unwrap(), 3);
+        assert_eq!(m.bond_degree_of(&7).unwrap(), 0);
+
+        m.remove_atom(&7).unwrap();
+        m.remove_atom(&1).unwrap();
+        assert_eq!(5, m.order());
+        assert_eq!(0, m.n_ssr());
+        for i in 1..6 {
+            assert!(!m.atom_at(&i).unwrap().is_aromatic());
+        }
+    }
+
+    #[test]
+    fn test_remove_atom_with_stereo() {
+        let p = Parser::new("OCC[C@@H](N)CO");
+        let mut m = p.parse().unwrap();
+        let t = m.topology_at(&4).unwrap();
+        let conf = t.configuration().unwrap();
+        let carriers: Vec<i64> = t
+            .carriers()
+            .iter()
+            .map(|v| if *v > 1 { v - 1 } else { *v })
+            .collect();
+        m.remove_atom(&1).unwrap();
+        let t = m.topology_at(&3).unwrap();
+        assert_eq!(t.atom(), 3);
+        assert_eq!(t.configuration().unwrap(), conf);
+        assert_eq!(t.carriers(), carriers.as_slice());
+        assert!(m.topology_at(&4).is_none());
+
+        m.remove_atom(&4).unwrap();
+        assert!(m.topology_at(&3).is_none());
+    }
+
+    #[test]
+    fn test_change_bond_order() {
+        let p = Parser::new("CCC");
+        let mut m = p.parse().unwrap();
+        assert_eq!("", m.change_bond_order(1, 2, DOUBLE).unwrap().token());
+        assert_eq!(m.hydrogen_count(&1).unwrap(), 2);
+        assert_eq!(m.hydrogen_count(&2).unwrap(), 1);
+        assert_eq!(m.bond_degree_of(&2).unwrap(), 3);
+        assert!(m.edge_at(2, 1).unwrap().is("="));
+        assert!(m.change_bond_order(1, 3, SINGLE).is_err());
+        assert!(m.change_bond_order(1, 2, DOT).is_err());
+    }
+
+    const ROUND_TRIP: [&str; 24] = [
+        "[13CH4]",
+        "[2H]O[2H]",
+        "[0CH4]",
+        "OC[C@@H](N)C(=O)O",
+        "[NH4+].[Cl-]",
+        "C.[C@H](F)(Cl)Br",
+        "c1cc[nH]c1",
+        "[Na+].[O-]C(=O)C",
+        "CC[Fe]CC",
+        "F/C=C/F",
+        "C[S](C)(C)(C)(C)C",
+        "[CH2:1]=[CH:2][C@](F)(Cl)[13CH3]",
+        "[O-][n+]1ccccc1",
+        "[*:1]CC[*:2]",
+        "C1CC2CCC1C2",
+        "[Cu+2].[O-]S(=O)(=O)[O-]",
+        "[CH3]",
+        "[CH2]",
+        "[BH3-]",
+        "[U]",
+        "[18OH2]",
+        "C[C@H]1CC[C@@H](C)CC1",
+        "c1ccc2[nH]ccc2c1",
+        "[Fe+3].[Fe+2].[C-]#N",
+    ];
+
+    /// Order independent summary of the atoms and stereo of `m`.
+    fn atom_summary(m: &Molecule) -> Vec<(String, i16, i8, u8, u32, bool, u8)> {
+        let mut atoms: Vec<_> = m
+            .atoms()
+            .iter()
+            .map(|i| {
+                let a = m.atom_at(i).unwrap();
+                (
+                    m.symbol(i).unwrap(),
+                    a.isotope(),
+                    a.charge(),
+                    m.hydrogen_count(i).unwrap(),
+                    a.atom_class(),
+                    m.topology_at(i).is_some(),
+                    m.bond_degree_of(i).unwrap(),
+                )
+            })
+            .collect();
+        atoms.sort();
+        atoms
+    }
+
+    #[test]
+    fn test_smiles_round_trip() {
+        for smi in ROUND_TRIP.iter() {
+            let m = Parser::new(smi).parse().unwrap();
+            let written = m.to_smiles().unwrap();
+            let back = Parser::new(&written).parse().unwrap();
+            assert_eq!(atom_summary(&m), atom_summary(&back), "{}", smi);
+            assert_eq!(m.size(), back.size(), "{}", smi);
+            assert_eq!(back.to_smiles().unwrap(), written, "{}", smi);
+        }
+    }
+
+    #[test]
+    fn test_smiles_promotes_subset_atoms() {
+        let m = Parser::new("C(F)(F)(F)(F)F").parse().unwrap();
+        let smiles = m.to_smiles().unwrap();
+        assert!(smiles.contains("[C]"), "{}", smiles);
+
+        let mut m = Parser::new("CCO").parse().unwrap();
+        m.atom_mut(&3).unwrap().set_atom_class(2);
+        assert_eq!(m.to_smiles().unwrap(), "CC[OH:2]");
+        assert_eq!(Molecule::new().to_smiles().unwrap(), "");
+    }
+    #[test]
+    fn test_writer_options() {
+        let m = Parser::new("[13CH3:1]c1ccc[nH]1").parse().unwrap();
+        let w = SmilesWriter::new().canonical(false);
+        assert_eq!(w.write(&m).unwrap(), "[13CH3:1]c1ccc[nH]1");
+        assert_eq!(
+            w.clone()
+                .isotopes(false)
+                .atom_classes(false)
+                .write(&m)
+                .unwrap(),
+            "Cc1ccc[nH]1"
+        );
+        assert_eq!(
+            w.clone().aromatic(false).isotopes(false).write(&m).unwrap(),
+            "[CH3:1]C1=CC=CN1"
+        );
+        assert_eq!(
+            w.clone().hydrogens(Hydrogens::Bracket).write(&m).unwrap(),
+            "[13CH3:1][c]1[cH][cH][cH][nH]1"
+        );
+
+        let m = Parser::new("N[C@@H](C)C(=O)O").parse().unwrap();
+        let w = SmilesWriter::new().canonical(false).stereo(false);
+        assert_eq!(w.write(&m).unwrap(), "NC(C)C(=O)O");
+        let m = Parser::new("F/C=C/F").parse().unwrap();
+        assert_eq!(w.write(&m).unwrap(), "FC=CF");
+        let m = Parser::new("[2H]C").parse().unwrap();
+        assert_eq!(w.isotopes(false).write(&m).unwrap(), "[H]C");
+    }
+
+    #[test]
+    fn test_writer_hydrogen_atoms() {
+        let w = SmilesWriter::new().hydrogens(Hydrogens::Atoms);
+        for smi in ["CCO", "c1cc[nH]c1", "N[C@@H](C)C(=O)O", "[NH4+]"] {
+            let m = Parser::new(smi).parse().unwrap();
+            let written = w.write(&m).unwrap();
+            let back = Parser::new(&written).parse().unwrap();
+            let hydrogens: usize = m
+                .atoms()
+                .iter()
+                .map(|i| m.hydrogen_count(i).unwrap() as usize)
+                .sum();
+            assert_eq!(back.order(), m.order() + hydrogens, "{}", written);
+            assert!(back
+                .atoms()
+                .iter()
+                .all(|i| back.hydrogen_count(i).unwrap() == 0));
+        }
+        let m = Parser::new("N[C@@H](C)C(=O)O").parse().unwrap();
+        let back = Parser::new(&w.write(&m).unwrap()).parse().unwrap();
+        assert_eq!(back.chiralatoms_count(), 1);
+    }
+
+    #[test]
+    fn test_writer_kekule() {
+        let w = SmilesWriter::new().canonical(false).aromatic(false);
+        for (smi, kekule) in [
+            ("c1ccccc1", "C1=CC=CC=C1"),
+            ("c1ccc2ccccc2c1", "C1=CC=C2C=CC=CC2=C1"),
+            ("c1ccc2[nH]ccc2c1", "C1=CC=C2NC=CC2=C1"),
+            ("c1ccc[n+]([O-])c1", "C1=CC=C[N+]([O-])=C1"),
+            ("Cn1cnc2c1c(=O)n(C)c(=O)n2C", "CN1C=NC2=C1C(=O)N(C)C(=O)N2C"),
+        ] {
+            let m = Parser::new(smi).parse().unwrap();
+            assert_eq!(w.write(&m).unwrap(), kekule);
+        }
+    }
+
+    #[test]
+    fn test_writer_kekule_canonical() {
+        let w = SmilesWriter::new().aromatic(false);
+        for (aromatic, kekule) in [
+            ("c1ccccc1", "C1=CC=CC=C1"),
+            ("c1ccccc1C", "CC1=CC=CC=C1"),
+            ("Oc1ccccc1C", "CC1=CC=CC=C1O"),
+            ("c1ccncc1", "C1=CN=CC=C1"),
+            ("c1cc[nH]c1", "C1=CNC=C1"),
+            ("c1ccc2ccccc2c1", "C1=CC=CC2C=CC=CC1=2"),
+        ] {
+            let a = Parser::new(aromatic).parse().unwrap();
+            let k = Parser::new(kekule).parse().unwrap();
+            assert_eq!(w.write(&a).unwrap(), w.write(&k).unwrap(), "{}", aromatic);
+        }
+    }
+
+    #[test]
+    fn test_writer_shared() {
+        let m = Parser::new("CC(=O)Oc1ccccc1C(=O)O").parse().unwrap();
+        let expected = m.to_smiles().unwrap();
+        let w = SmilesWriter::new();
+        std::thread::scope(|s| {
+            let handles: Vec<_> = (0..4).map(|_| s.spawn(|| w.write(&m).unwrap())).collect();
+            for h in handles {
+                assert_eq!(h.join().unwrap(), expected);
+            }
+        });
+    }
+
+    #[test]
+    fn test_writer_stereo_off() {
+        let w = SmilesWriter::new().stereo(false);
+        for smi in [
+            "c1ccc2cc3ccccc3cc2c1",
+            "NC(C)C(=O)O",
+            "FC=Cc1ccccc1",
+            "C1C2CC3CC1CC(C2)C3",
+            "NCc1ccccc1",
+        ] {
+            let m = Parser::new(smi).parse().unwrap();
+            assert_eq!(w.write(&m).unwrap(), m.to_smiles().unwrap(), "{}", smi);
+        }
+        for (smi, flat) in [
+            ("N[C@@H](C)C(=O)O", "NC(C)C(=O)O"),
+            ("N[C@H](C)C(=O)O", "NC(C)C(=O)O"),
+            ("F/C=C/c1ccccc1", "FC=Cc1ccccc1"),
+            ("F/C=C\\c1ccccc1", "FC=Cc1ccccc1"),
+        ] {
+            let m = Parser::new(smi).parse().unwrap();
+            let flat = Parser::new(flat).parse().unwrap();
+            assert_eq!(w.write(&m).unwrap(), flat.to_smiles().unwrap(), "{}", smi);
+        }
+    }
 }
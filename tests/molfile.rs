@@ -0,0 +1,370 @@
+#[cfg(test)]
+mod test {
+    use ruatom::error::RuatomError;
+    use ruatom::molecule::StereoGroup;
+    use ruatom::molfile::{read_molfile, write_molfile, write_molfile_v3000};
+    use ruatom::parser::Parser;
+
+    const L_ALANINE: &str = "L-alanine
+  ruatom            2D
+
+  6  5  0  0  1  0  0  0  0  0999 V2000
+   -0.8700    0.5000    0.0000 N   0  0  0  0  0  0  0  0  0  0  0  0
+    0.0000    0.0000    0.0000 C   0  0  0  0  0  0  0  0  0  0  0  0
+    0.0000   -1.0000    0.0000 C   0  0  0  0  0  0  0  0  0  0  0  0
+    0.8700    0.5000    0.0000 C   0  0  0  0  0  0  0  0  0  0  0  0
+    1.7400    0.0000    0.0000 O   0  0  0  0  0  0  0  0  0  0  0  0
+    0.8700    1.5000    0.0000 O   0  0  0  0  0  0  0  0  0  0  0  0
+  1  2  1  0
+  2  3  1  6
+  2  4  1  0
+  4  5  2  0
+  4  6  1  0
+M  END
+";
+
+    fn smiles(s: &str) -> String {
+        Parser::new(s).parse().unwrap().to_smiles().unwrap()
+    }
+
+    #[test]
+    fn test_read_wedge() {
+        let m = read_molfile(L_ALANINE).unwrap();
+        assert_eq!(m.order(), 6);
+        assert_eq!(m.coordinates(&3), Some([0.0, -1.0, 0.0]));
+        assert_eq!(m.to_smiles().unwrap(), smiles("N[C@@H](C)C(=O)O"));
+
+        let hashed = L_ALANINE.replace("  2  3  1  6", "  2  3  1  1");
+        let m = read_molfile(&hashed).unwrap();
+        assert_eq!(m.to_smiles().unwrap(), smiles("N[C@H](C)C(=O)O"));
+    }
+
+    #[test]
+    fn test_read_3d() {
+        let block = L_ALANINE.replace("  2D", "  3D").replace(
+            "    0.0000   -1.0000    0.0000 C",
+            "    0.0000   -0.7000   -0.7000 C",
+        );
+        let m = read_molfile(&block).unwrap();
+        assert_eq!(m.to_smiles().unwrap(), smiles("N[C@@H](C)C(=O)O"));
+    }
+
+    #[test]
+    fn test_read_double_bond() {
+        let block = "
+  ruatom            2D
+
+  4  3  0  0  0  0  0  0  0  0999 V2000
+   -1.3000   -0.5000    0.0000 C   0  0  0  0  0  0  0  0  0  0  0  0
+   -0.5000    0.0000    0.0000 C   0  0  0  0  0  0  0  0  0  0  0  0
+    0.5000    0.0000    0.0000 C   0  0  0  0  0  0  0  0  0  0  0  0
+    1.3000    0.5000    0.0000 C   0  0  0  0  0  0  0  0  0  0  0  0
+  1  2  1  0
+  2  3  2  0
+  3  4  1  0
+M  END
+";
+        let m = read_molfile(block).unwrap();
+        assert_eq!(m.to_smiles().unwrap(), smiles("C/C=C/C"));
+        let cis = block.replace("    1.3000    0.5000", "    1.3000   -0.5000");
+        let m = read_molfile(&cis).unwrap();
+        assert_eq!(m.to_smiles().unwrap(), smiles("C/C=C\\C"));
+        let either = block.replace("  2  3  2  0", "  2  3  2  3");
+        let m = read_molfile(&either).unwrap();
+        assert_eq!(m.to_smiles().unwrap(), smiles("CC=CC"));
+    }
+
+    #[test]
+    fn test_read_properties() {
+        let block = "
+  ruatom            2D
+
+  5  4  0  0  0  0  0  0  0  0999 V2000
+    0.0000    0.0000    0.0000 C   0  0  0  0  0  0  0  0  0  0  0  0
+    0.0000    0.0000    0.0000 C   0  0  0  0  0  0  0  0  0  0  0  0
+    0.0000    0.0000    0.0000 O   0  0  0  0  0  0  0  0  0  0  0  0
+    0.0000    0.0000    0.0000 O   0  3  0  0  0  0  0  0  0  0  0  0
+    0.0000    0.0000    0.0000 C   0  0  0  0  0  0  0  0  0  0  0  0
+  1  2  1  0
+  2  3  2  0
+  2  4  1  0
+  1  5  1  0
+M  CHG  1   4  -1
+M  ISO  1   1  13
+M  RAD  1   5   2
+M  END
+";
+        let m = read_molfile(block).unwrap();
+        assert_eq!(m.atom_at(&4).unwrap().charge(), -1);
+        assert_eq!(m.atom_at(&1).unwrap().isotope(), 13);
+        assert_eq!(m.hydrogen_count(&1).unwrap(), 2);
+        assert_eq!(m.atom_at(&5).unwrap().radical(), 2);
+        assert_eq!(m.hydrogen_count(&5).unwrap(), 2);
+        assert_eq!(m.hydrogen_count(&4).unwrap(), 0);
+
+        let m = read_molfile(&write_molfile(&m).unwrap()).unwrap();
+        assert_eq!(m.atom_at(&4).unwrap().charge(), -1);
+        assert_eq!(m.atom_at(&1).unwrap().isotope(), 13);
+        assert_eq!(m.atom_at(&5).unwrap().radical(), 2);
+        assert_eq!(m.hydrogen_count(&5).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_read_errors() {
+        assert!(matches!(
+            read_molfile("\n\n\n  1  0"),
+            Err(RuatomError::IllegalMolfile(5, "missing atom line"))
+        ));
+        let block = L_ALANINE.replace("  4  6  1  0", "  4  7  1  0");
+        assert!(matches!(
+            read_molfile(&block),
+            Err(RuatomError::IllegalMolfile(15, "bond to a missing atom"))
+        ));
+        let block = L_ALANINE.replace("M  END\n", "");
+        assert!(matches!(
+            read_molfile(&block),
+            Err(RuatomError::IllegalMolfile(_, "missing `M  END` line"))
+        ));
+        let mut block = String::from("\n\n\n  2100  0  0  0  0  0  0  0  0999 V2000\n");
+        block += "    0.0000    0.0000    0.0000 C   0  0\n"
+            .repeat(2)
+            .as_str();
+        block += "  1  2  3  0\n".repeat(100).as_str();
+        block += "M  END\n";
+        assert!(matches!(
+            read_molfile(&block),
+            Err(RuatomError::IllegalMolecule("too many bonds to one atom"))
+        ));
+    }
+
+    const DIFLUOROETHENE: &str = "
+  ruatom            2D
+
+  4  3  0  0  0  0  0  0  0  0999 V2000
+   -1.3000   -0.5000    0.0000 F   0  0  0  0  0  0  0  0  0  0  0  0
+   -0.5000    0.0000    0.0000 C   0  0  0  0  0  0  0  0  0  0  0  0
+    0.5000    0.0000    0.0000 C   0  0  0  0  0  0  0  0  0  0  0  0
+    1.3000    0.5000    0.0000 F   0  0  0  0  0  0  0  0  0  0  0  0
+  1  2  1  0
+  2  3  2  0
+  3  4  1  0
+M  END
+";
+
+    /// The V2000 `block` with its atoms listed in `order`, old indices.
+    /// Parities follow neighbour indices, so they flip where the new
+    /// numbering puts the neighbours in an odd permutation.
+    fn shuffled(block: &str, order: &[usize]) -> String {
+        let lines: Vec<&str> = block.lines().collect();
+        let n: usize = lines[3][0..3].trim().parse().unwrap();
+        let m: usize = lines[3][3..6].trim().parse().unwrap();
+        let mut new_of = vec![0; n + 1];
+        for (ix, at) in order.iter().enumerate() {
+            new_of[*at] = ix + 1;
+        }
+        let bonds: Vec<(usize, usize)> = lines[4 + n..4 + n + m]
+            .iter()
+            .map(|l| {
+                (
+                    l[0..3].trim().parse().unwrap(),
+                    l[3..6].trim().parse().unwrap(),
+                )
+            })
+            .collect();
+        let mut out: Vec<String> = lines[..4].iter().map(|l| l.to_string()).collect();
+        for at in order.iter() {
+            let line = lines[3 + at];
+            let mut nbors: Vec<usize> = bonds
+                .iter()
+                .filter_map(|(u, v)| match (u == at, v == at) {
+                    (true, _) => Some(*v),
+                    (_, true) => Some(*u),
+                    _ => None,
+                })
+                .collect();
+            nbors.sort();
+            let nbors: Vec<usize> = nbors.iter().map(|b| new_of[*b]).collect();
+            let mut odd = false;
+            for i in 0..nbors.len() {
+                for j in i + 1..nbors.len() {
+                    odd ^= nbors[i] > nbors[j];
+                }
+            }
+            let parity = match (&line[39..42], odd) {
+                ("  1", true) => "  2",
+                ("  2", true) => "  1",
+                (p, _) => p,
+            };
+            out.push(format!("{}{}{}", &line[..39], parity, &line[42..]));
+        }
+        for (l, (u, v)) in lines[4 + n..4 + n + m].iter().zip(bonds) {
+            out.push(format!("{:>3}{:>3}{}", new_of[u], new_of[v], &l[6..]));
+        }
+        out.extend(lines[4 + n + m..].iter().map(|l| l.to_string()));
+        out.join("\n") + "\n"
+    }
+
+    #[test]
+    fn test_to_smiles_ignores_atom_order() {
+        let mut seed: u64 = 0x2545f4914f6cdd1d;
+        let mut next = move |n: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % n as u64) as usize
+        };
+        let mut corpus = vec![
+            (L_ALANINE.to_string(), "N[C@@H](C)C(=O)O"),
+            (DIFLUOROETHENE.to_string(), "F/C=C/F"),
+        ];
+        for s in [
+            "N[C@@H](C)C(=O)O",
+            "C1C2CC3CC1CC(C2)C3", // example from nauty
+            "NC[C@TH2H]1O[C@TH1H](O[C@TH1H]2[C@TH1H](O)[C@TH2H](O[C@TH2H]3[C@TH2H](O)[C@TH1H](N)C[C@TH1H](N)[C@TH1H]3O[C@TH1H]3O[C@TH1H](CN)[C@TH2H](O)[C@TH1H](O)[C@TH1H]3N)O[C@TH1H]2CSCCNC(NCCCCN2C(=O)c3ccc4c5ccc6c7c(ccc(c8ccc(c3c48)C2=O)c57)C(=O)N(CCCCNC(NCCSC[C@TH1H]2O[C@TH2H](O[C@TH2H]3[C@TH2H](O)[C@TH1H](N)C[C@TH1H](N)[C@TH1H]3O[C@TH1H]3O[C@TH1H](CN)[C@TH2H](O)[C@TH1H](O)[C@TH1H]3N)[C@TH2H](O)[C@TH1H]2O[C@TH1H]2O[C@TH2H](CN)[C@TH2H](O)[C@TH1H](O)[C@TH1H]2N)=S)C6=O)=S)[C@TH1H](N)[C@TH1H](O)[C@TH2H]1O", // 52881
+        ] {
+            let m = Parser::new(s).parse().unwrap();
+            corpus.push((write_molfile(&m).unwrap(), s));
+        }
+        for (block, s) in corpus {
+            let expected = smiles(s);
+            assert_eq!(read_molfile(&block).unwrap().to_smiles().unwrap(), expected);
+            let n = read_molfile(&block).unwrap().order();
+            for _ in 0..10 {
+                let mut order: Vec<usize> = (1..=n).collect();
+                for i in (1..n).rev() {
+                    order.swap(i, next(i + 1));
+                }
+                let m = read_molfile(&shuffled(&block, &order)).unwrap();
+                assert_eq!(m.to_smiles().unwrap(), expected, "{} {:?}", s, order);
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let corpus = [
+            "CCO",
+            "c1ccccc1O",
+            "c1cc[nH]c1",
+            "CC(=O)[O-]",
+            "[NH4+]",
+            "C[N+](C)(C)C",
+            "OC(=O)C1=CC=CC=C1",
+            "N[C@@H](C)C(=O)O",
+            "N[C@H](C)C(=O)O",
+            "F[C@](Cl)(Br)I",
+            "C[C@@H]1CCCC[C@H]1C",
+            "C[S@@](=O)CC",
+            "CC#N",
+            "[Na+].[Cl-]",
+            "OS(=O)(=O)O",
+            "C1CC[Fe]CC1",
+        ];
+        for s in corpus {
+            let m = Parser::new(s).parse().unwrap();
+            let block = write_molfile(&m).unwrap();
+            let back = read_molfile(&block).unwrap();
+            assert_eq!(back.to_smiles().unwrap(), m.to_smiles().unwrap(), "{}", s);
+        }
+
+        let m = read_molfile(L_ALANINE).unwrap();
+        let block = write_molfile(&m).unwrap();
+        assert!(block.contains("  2  3  1  6") || block.contains("  2  1  1  6"));
+        let back = read_molfile(&block).unwrap();
+        assert_eq!(back.to_smiles().unwrap(), m.to_smiles().unwrap());
+        assert_eq!(back.coordinates(&5), m.coordinates(&5));
+    }
+
+    const BUTANOL_V3000: &str = "butan-2-ol
+  ruatom            2D
+
+  0  0  0     0  0            999 V3000
+M  V30 BEGIN CTAB
+M  V30 COUNTS 5 4 1 0 0
+M  V30 BEGIN ATOM
+M  V30 1 C -1.3 -0.5 0 0
+M  V30 2 C -0.5 0 0 0
+M  V30 3 O -0.5 1 0 0
+M  V30 4 C 0.3 -0.5 0 0
+M  V30 5 C 1.1 0 0 0 MASS=13
+M  V30 END ATOM
+M  V30 BEGIN BOND
+M  V30 1 1 1 2
+M  V30 2 1 2 3 CFG=1
+M  V30 3 1 2 4
+M  V30 4 1 4 5
+M  V30 END BOND
+M  V30 BEGIN SGROUP
+M  V30 1 SUP 0 ATOMS=(2 4 5) XBONDS=(1 3) LABEL=Et
+M  V30 END SGROUP
+M  V30 BEGIN COLLECTION
+M  V30 MDLV30/STERAC1 ATOMS=(1 2)
+M  V30 MDLV30/HILITE ATOMS=(2 1 2) -
+M  V30 BONDS=(1 1)
+M  V30 END COLLECTION
+M  V30 END CTAB
+M  END
+";
+
+    #[test]
+    fn test_read_v3000() {
+        let m = read_molfile(BUTANOL_V3000).unwrap();
+        assert_eq!(m.order(), 5);
+        assert_eq!(m.atom_at(&5).unwrap().isotope(), 13);
+        assert_eq!(m.to_smiles().unwrap(), smiles("C[C@@H](O)C[13CH3]"));
+        assert_eq!(m.topology_at(&2).unwrap().group(), StereoGroup::And(1));
+        assert_eq!(
+            m.opaque_blocks(),
+            &[
+                (
+                    "SGROUP".to_string(),
+                    vec!["1 SUP 0 ATOMS=(2 4 5) XBONDS=(1 3) LABEL=Et".to_string()]
+                ),
+                (
+                    "COLLECTION".to_string(),
+                    vec!["MDLV30/HILITE ATOMS=(2 1 2) BONDS=(1 1)".to_string()]
+                ),
+            ]
+        );
+
+        let rel = BUTANOL_V3000.replace("STERAC1", "STEREL2");
+        let m = read_molfile(&rel).unwrap();
+        assert_eq!(m.topology_at(&2).unwrap().group(), StereoGroup::Or(2));
+
+        let open = BUTANOL_V3000.replace("M  V30 END COLLECTION\n", "");
+        assert!(matches!(
+            read_molfile(&open),
+            Err(RuatomError::IllegalMolfile(_, "unterminated block"))
+        ));
+    }
+
+    #[test]
+    fn test_round_trip_v3000() {
+        let m = read_molfile(BUTANOL_V3000).unwrap();
+        let block = write_molfile(&m).unwrap();
+        assert!(block.contains("V3000"));
+        assert!(block.contains("M  V30 MDLV30/STERAC1 ATOMS=(1 2)"));
+        let mut back = read_molfile(&block).unwrap();
+        assert_eq!(back.to_smiles().unwrap(), m.to_smiles().unwrap());
+        assert_eq!(back.topology_at(&2).unwrap().group(), StereoGroup::And(1));
+        assert_eq!(back.opaque_blocks(), m.opaque_blocks());
+
+        back.set_stereo_group(&2, StereoGroup::Absolute).unwrap();
+        back.remove_atom(&5).unwrap();
+        assert!(back.opaque_blocks().is_empty());
+        assert!(write_molfile(&back).unwrap().contains("V2000"));
+
+        for s in ["CCO", "c1ccccc1O", "CC(=O)[O-]", "N[C@@H](C)C(=O)O"] {
+            let m = Parser::new(s).parse().unwrap();
+            let back = read_molfile(&write_molfile_v3000(&m).unwrap()).unwrap();
+            assert_eq!(back.to_smiles().unwrap(), m.to_smiles().unwrap(), "{}", s);
+        }
+
+        let m = Parser::new(&"C".repeat(1000)).parse().unwrap();
+        let block = write_molfile(&m).unwrap();
+        assert!(block.contains("COUNTS 1000 999 0 0 0"));
+        let back = read_molfile(&block).unwrap();
+        assert_eq!(back.order(), 1000);
+        assert_eq!(back.to_smiles().unwrap(), m.to_smiles().unwrap());
+    }
+}
@@ -1,54 +1,86 @@
 mod test {
     use ruatom::molecule::{TopologySeq, AL1, AL2, DB1, DB2};
-    use ruatom::{error::RuatomError, parser::Parser};
+    use ruatom::{
+        error::{RuatomError, SmilesError},
+        parser::Parser,
+    };
+
+    fn syntax_error(smiles: &str) -> SmilesError {
+        match Parser::new(smiles).parse() {
+            Err(RuatomError::SmilesSyntax(e)) => e,
+            _ => panic!("`{}` should fail to parse", smiles),
+        }
+    }
 
     #[test]
     fn test_parser_unclosed_ring() {
-        let p = Parser::new("C1CCCCC");
-        let err = p.parse();
-        assert_eq!(
-            err.err().unwrap(),
-            RuatomError::IllegalSMILES("unclosed ring")
-        );
+        let e = syntax_error("C1CCCCC");
+        assert_eq!(*e.reason, RuatomError::IllegalSMILES("unclosed ring"));
+        assert_eq!((e.position, e.token.as_str(), e.opened), (7, "1", Some(1)));
+        assert_eq!(e.render(), "C1CCCCC\n ^ unclosed ring");
     }
 
     #[test]
     fn test_parser_unclosed_ring_with_closed() {
-        let p = Parser::new("C1CCCCC1CCCC1CCCC");
-        let err = p.parse();
-        assert_eq!(
-            err.err().unwrap(),
-            RuatomError::IllegalSMILES("unclosed ring")
-        );
+        let e = syntax_error("C1CCCCC1CCCC1CCCC");
+        assert_eq!(*e.reason, RuatomError::IllegalSMILES("unclosed ring"));
+        assert_eq!(e.opened, Some(12));
+
+        let e = syntax_error("C%12CC%13C");
+        assert_eq!((e.token.as_str(), e.opened), ("%12", Some(1)));
+        assert_eq!(e.render(), "C%12CC%13C\n ^^^ unclosed ring");
     }
 
     #[test]
     fn test_parser_unclosed_branch_left() {
-        let p = Parser::new("CCCC(CCCC");
-        let err = p.parse();
+        let e = syntax_error("CCCC(CCCC");
+        assert_eq!(*e.reason, RuatomError::IllegalSMILES("unclosed branch"));
+        assert_eq!((e.position, e.token.as_str(), e.opened), (9, "(", Some(4)));
         assert_eq!(
-            err.err().unwrap(),
-            RuatomError::IllegalSMILES("unclosed branch")
+            e.to_string(),
+            "unclosed branch at 9 `(`, opened at 4".to_string()
         );
     }
 
     #[test]
     fn test_parser_unclosed_branch_right() {
-        let p = Parser::new("CCCC)CCCC");
-        let err = p.parse();
+        let e = syntax_error("CCCC)CCCC");
         assert_eq!(
-            err.err().unwrap(),
+            *e.reason,
             RuatomError::IllegalSMILES("failed to close branch after ')'")
         );
+        assert_eq!((e.position, e.token.as_str(), e.opened), (4, ")", None));
+        assert_eq!(
+            e.render(),
+            "CCCC)CCCC\n    ^ failed to close branch after ')'"
+        );
     }
 
     #[test]
     fn test_parser_unclosed_branch_with_closed() {
-        let p = Parser::new("CCC(C)C(CCC");
-        let err = p.parse();
+        let e = syntax_error("CCC(C)C(CCC");
+        assert_eq!(*e.reason, RuatomError::IllegalSMILES("unclosed branch"));
+        assert_eq!(e.render(), "CCC(C)C(CCC\n       ^ unclosed branch");
+    }
+
+    #[test]
+    fn test_parser_error_tokens() {
+        let e = syntax_error("CC[C@XH]C");
+        assert_eq!((e.position, e.token.as_str()), (2, "[C@"));
+        let e = syntax_error("CC[Xx]");
         assert_eq!(
-            err.err().unwrap(),
-            RuatomError::IllegalSMILES("unclosed branch")
+            *e.reason,
+            RuatomError::IllegalSMILES("need an element in bracket")
+        );
+        let e = syntax_error("CC.1CC1");
+        assert_eq!((e.position, e.token.as_str()), (3, "1"));
+        assert_eq!(*e.reason, RuatomError::IllegalSMILES("ring bond after '.'"));
+        let e = syntax_error("C=1CC-1");
+        assert_eq!((e.position, e.opened), (6, Some(2)));
+        let e = syntax_error("CC?C");
+        assert_eq!(
+            RuatomError::SmilesSyntax(e).to_string(),
+            "invalid SMILES: unexpected character at 2 `?`"
         );
     }
 
@@ -122,7 +154,7 @@ mod test {
         assert_eq!(m.topology_at(&4).unwrap().configuration().unwrap(), AL2);
 
         let p = Parser::new("Cl[C@@H](Br)CC[C@H](CO)C#N");
-        let mut m = p.parse().unwrap();
+        let m = p.parse().unwrap();
         assert_eq!("OC[C@TH1H](C#N)CC[C@TH2H](Cl)Br", m.to_smiles().unwrap());
     }
 
@@ -134,20 +166,40 @@ mod test {
 
     #[test]
     fn test_up_down_with_multiple() {
-        let p = Parser::new("C/C=C(/C)/C");
+        let e = syntax_error("C/C=C(/C)/C");
         assert_eq!(
-            RuatomError::IllegalMolecule("invalid Cis/Trans specification"),
-            p.parse().err().unwrap()
-        )
+            *e.reason,
+            RuatomError::IllegalMolecule("invalid Cis/Trans specification")
+        );
     }
 
     #[test]
     fn test_up_down_with_invalid() {
-        let p = Parser::new("C\\=C");
+        let e = syntax_error("C\\=C");
+        assert_eq!(*e.reason, RuatomError::IllegalSMILES("bond conflict"));
+        assert_eq!((e.position, e.token.as_str()), (2, "="));
+    }
+
+    #[test]
+    fn test_atom_class() {
+        let m = Parser::new("[CH3:12][C@@H:1](O)[NH3+:007]")
+            .parse()
+            .unwrap();
+        let classes: Vec<u32> = (1..=4)
+            .map(|i| m.atom_at(&i).unwrap().atom_class())
+            .collect();
+        assert_eq!(classes, [12, 1, 0, 7]);
+        assert_eq!(m.to_smiles().unwrap(), "[CH3:12][C@TH1H:1]([NH3+:7])O");
+
+        let mut m = Parser::new("CCO").parse().unwrap();
+        m.atom_mut(&1).unwrap().set_atom_class(5);
+        assert_eq!(m.to_smiles().unwrap(), "[CH3:5]CO");
+
+        let e = syntax_error("[CH3:]C");
         assert_eq!(
-            RuatomError::IllegalSMILES("bond conflict"),
-            p.parse().err().unwrap()
-        )
+            *e.reason,
+            RuatomError::IllegalSMILES("need a digit after ':'")
+        );
     }
 
     #[test]
@@ -416,7 +468,7 @@ mod test {
     fn test_ring_size() {
         let p = Parser::new("C2CCC1CCCC1C2");
         let mut m = p.parse().unwrap();
-        assert!(!m.rings_detection().is_err());
+        assert!(m.rings_detection().is_ok());
     }
 
     #[test]
@@ -454,7 +506,7 @@ mod test {
     fn test_is_stereocenter() {
         let p = Parser::new("CC(Cl)CO");
         let m = p.parse().unwrap();
-        let mark = vec![false, true, false, false, false];
+        let mark = [false, true, false, false, false];
         for i in 1..6 {
             assert_eq!(
                 m.atom_at(&i).unwrap().is_stereocenter(),
@@ -495,22 +547,22 @@ mod test {
     #[test]
     fn test_to_smiles() {
         let p = Parser::new("c1c(CN)cccc1");
-        let mut m = p.parse().unwrap();
+        let m = p.parse().unwrap();
         let smiles = m.to_smiles().unwrap();
         assert_eq!("NCc1ccccc1", smiles);
 
         let p = Parser::new("c1ccc(CN)cc1");
-        let mut m = p.parse().unwrap();
+        let m = p.parse().unwrap();
         let smiles = m.to_smiles().unwrap();
         assert_eq!("NCc1ccccc1", smiles);
 
         let p = Parser::new("c1cc(CN)ccc1");
-        let mut m = p.parse().unwrap();
+        let m = p.parse().unwrap();
         let smiles = m.to_smiles().unwrap();
         assert_eq!("NCc1ccccc1", smiles);
 
         let p = Parser::new("c1cccc(CN)c1");
-        let mut m = p.parse().unwrap();
+        let m = p.parse().unwrap();
         let smiles = m.to_smiles().unwrap();
         assert_eq!("NCc1ccccc1", smiles);
 
@@ -528,13 +580,13 @@ mod test {
             "C1CC1N1CN2c3nonc3N3CN(CN4c5nonc5N(C1)C2C34)C1CC1", // 199821
             "O=P1(O)OC2C3OP(=O)(O)OP(=O)(O)OC3C3OP(=O)(O)OP(=O)(O)OC3C2OP(=O)(O)O1", // 208361
             "BrC1CCC(Br)C(Br)CCC(Br)C(Br)CCC1Br", // 377203
-            "C1C2CC3CC(CC1C3)C2", // example from nauty, https://pallini.di.uniroma1.it/Introduction.html
+            "C1C2CC3CC1CC(C2)C3", // example from nauty, https://pallini.di.uniroma1.it/Introduction.html
             "OCC(CO)(CO)NC(=O)CCCc1ccc(cc1)C1(c2ccccc2)C23c4c5c6c7c8c9c(c%10c%11c2c2c4c4c%12c5c5c6c6c8c8c%13c9c9c%10c%10c%11c%11c2c2c4c4c%12c%12c5c5c6c8c6c8c%13c9c9c%10c%10c%11c2c2c4c4c%12c5c6c5c8c9c%10c2c45)C137", // 267348
             "O=C(O)c1cc2cc(c1)Cc1cc(cc(c1)C(=O)O)Cc1cc(cc(c1)C(=O)O)Cc1cc(cc(c1)C(=O)O)C2", // graph reduction demo
-            // r#"C[C@H](CC[C@@H]([C@@H]([C@H](C)C[C@H](C(=C)/C(=C/CO)/C)O)O)OS(=O)(=O)[O-])[C@H]([C@@H](C)[C@H]1[C@@H]([C@@H]([C@H]2[C@H](O1)[C@@H](C[C@]3([C@H](O2)C[C@H]4[C@H](O3)C[C@]5([C@H](O4)[C@H]([C@H]6[C@H](O5)C[C@H]([C@H](O6)[C@@H]([C@H](C[C@H]7[C@@H]([C@@H]([C@H]8[C@H](O7)C[C@H]9[C@H](O8)C[C@H]1[C@H](O9)[C@H]([C@@H]2[C@@H](O1)[C@@H]([C@H]([C@@H](O2)[C@H]1[C@@H]([C@H]([C@H]2[C@@H](O1)C[C@H]([C@@H](O2)[C@@H](C[C@H](C[C@H]1[C@@H]([C@H]([C@H]2[C@@H](O1)C[C@H]([C@@H](O2)[C@H]1[C@@H](C[C@]2([C@H](O1)[C@@H]([C@]1([C@H](O2)C[C@]2([C@H](O1)CC[C@]1([C@H](O2)C[C@]2([C@H](O1)C[C@H]1[C@H](O2)CC[C@H](O1)[C@]1([C@@H](C[C@H]2[C@](O1)(C[C@H]1[C@](O2)(CC[C@]2([C@H](O1)C[C@H]1[C@](O2)(C[C@H]2[C@H](O1)C/C=C\[C@H]1[C@H](O2)C[C@H]2[C@](O1)(C[C@]1([C@H](O2)C[C@H]2[C@](O1)(CC[C@H](O2)[C@H]([C@@H](C[C@@H](C)[C@@H](C)CC=C)O)O)C)C)C)C)C)C)C)O)C)C)C)C)C)O)C)O)O)O)O)O)O)O)O)O)O)O)O)O)OS(=O)(=O)[O-])O)O)O)O)C)C)O)O)O)O"#, // Maitotoxin
+            // r#"C=CC[C@TH1H](C)[C@TH1H](C)C[C@TH2H](O)[C@TH1H](O)[C@TH2H]1CC[C@TH2]2(C)O[C@TH2]3(C)C[C@TH2]4(C)O[C@TH1H]5/C=C\C[C@TH1H]6O[C@TH1H]7C[C@TH1H]8O[C@TH1H]9C[C@TH2]%10(C)O[C@TH1](C)([C@TH1H](O)C[C@TH2H]%10O[C@TH1]9(C)CC[C@TH1]8(C)O[C@TH1]7(C)C[C@TH2H]6O[C@TH2H]5C[C@TH2H]4O[C@TH2H]3C[C@TH2H]2O1)[C@TH2H]1CC[C@TH1H]2O[C@TH2]3(C)C[C@TH1H]4O[C@TH2]5(C)C[C@TH1H]6O[C@TH2]7(C)C[C@TH2H](O)[C@TH2H](O[C@TH2H]7[C@TH1H](O)[C@TH1]6(C)O[C@TH2H]5CC[C@TH1]4(C)O[C@TH2H]3C[C@TH2H]2O1)[C@TH2H]1O[C@TH1H]2[C@TH1H](O)[C@TH2H](O)[C@TH1H](C[C@TH1H](O)C[C@TH2H](O)[C@TH2H]3O[C@TH1H]4[C@TH1H](O)[C@TH2H](O)[C@TH2H](O[C@TH1H]4C[C@TH1H]3O)[C@TH2H]3O[C@TH2H]4[C@TH1H](O)[C@TH1H]5O[C@TH1H]6C[C@TH1H]7O[C@TH2H](C[C@TH1H](O)[C@TH2H](O)[C@TH1H]8O[C@TH2H]9[C@TH2H](C[C@TH1H]8O)O[C@TH2]8(C)C[C@TH1H]%10O[C@TH2]%11(C)C[C@TH2H](O)[C@TH1H]%12O[C@TH2H]([C@TH1H](C)[C@TH1H](O)[C@TH1H](C)CC[C@TH1H](OS([O-])(=O)=O)[C@TH1H](O)[C@TH1H](C)C[C@TH2H](O)C(=C)C(\C)=C\CO)[C@TH1H](O)[C@TH1H](O)[C@TH2H]%12O[C@TH2H]%11C[C@TH2H]%10O[C@TH2H]8[C@TH1H]9O)[C@TH1H](OS([O-])(=O)=O)[C@TH1H](O)[C@TH2H]7O[C@TH2H]6C[C@TH2H]5O[C@TH1H]4[C@TH1H](O)[C@TH1H]3O)O[C@TH1H]2C[C@TH1H]1O"#, // Maitotoxin
             "NC[CH]1O[CH](O[CH]2[CH](O)[CH](O[CH]3[CH](O)[CH](N)C[CH](N)[CH]3O[CH]3O[CH](CN)[CH](O)[CH](O)[CH]3N)O[CH]2CSCCNC(NCCCCN2C(=O)c3ccc4c5ccc6c7c(ccc(c8ccc(c3c48)C2=O)c57)C(=O)N(CCCCNC(NCCSC[CH]2O[CH](O[CH]3[CH](O)[CH](N)C[CH](N)[CH]3O[CH]3O[CH](CN)[CH](O)[CH](O)[CH]3N)[CH](O)[CH]2O[CH]2O[CH](CN)[CH](O)[CH](O)[CH]2N)=S)C6=O)=S)[CH](N)[CH](O)[CH]1O",
             "N[CH](Cc1cnc([nH]1)C12CC3CC(CC(C3)C1)C2)C(=O)N[CH](Cc1c[nH]c2ccccc12)C(=O)N[CH](Cc1cnc([nH]1)C12CC3CC(CC(C3)C1)C2)C(=O)NCc1ccccc1", // 7844
-            "O1[C@TH1H](CSCCNC(NCCCCN2C(=O)c3ccc4c5ccc6c7c(ccc(c8ccc(c3c48)C2=O)c57)C(=O)N(CCCCNC(NCCSC[C@TH2H]2O[C@TH2H]([C@TH2H](O)[C@TH2H]2O[C@TH1H]2O[C@TH2H](CN)[C@TH2H](O)[C@TH2H](O)[C@TH1H]2N)O[C@TH2H]2[C@TH2H](O)[C@TH1H](N)C[C@TH1H](N)[C@TH1H]2O[C@TH1H]2O[C@TH1H](CN)[C@TH2H](O)[C@TH1H](O)[C@TH1H]2N)=S)C6=O)=S)[C@TH2H]([C@TH1H](O)[C@TH2H]1O[C@TH2H]1[C@TH2H](O)[C@TH1H](N)C[C@TH1H](N)[C@TH1H]1O[C@TH1H]1O[C@TH1H](CN)[C@TH2H](O)[C@TH1H](O)[C@TH1H]1N)O[C@TH1H]1O[C@TH2H](CN)[C@TH2H](O)[C@TH1H](O)[C@TH1H]1N", // 52881
+            "NC[C@TH2H]1O[C@TH1H](O[C@TH1H]2[C@TH1H](O)[C@TH2H](O[C@TH2H]3[C@TH2H](O)[C@TH1H](N)C[C@TH1H](N)[C@TH1H]3O[C@TH1H]3O[C@TH1H](CN)[C@TH2H](O)[C@TH1H](O)[C@TH1H]3N)O[C@TH1H]2CSCCNC(NCCCCN2C(=O)c3ccc4c5ccc6c7c(ccc(c8ccc(c3c48)C2=O)c57)C(=O)N(CCCCNC(NCCSC[C@TH1H]2O[C@TH2H](O[C@TH2H]3[C@TH2H](O)[C@TH1H](N)C[C@TH1H](N)[C@TH1H]3O[C@TH1H]3O[C@TH1H](CN)[C@TH2H](O)[C@TH1H](O)[C@TH1H]3N)[C@TH2H](O)[C@TH1H]2O[C@TH1H]2O[C@TH2H](CN)[C@TH2H](O)[C@TH1H](O)[C@TH1H]2N)=S)C6=O)=S)[C@TH1H](N)[C@TH1H](O)[C@TH2H]1O", // 52881
             "CC[n+]1ccc(cc1)-c1cc[n+](cc1)Cc1cc(cc(c1)C[n+]1ccc(cc1)-c1cc[n+](cc1)Cc1cc(cc(c1)-[n+]1ccc(cc1)-c1cc[n+](cc1)-c1cc(cc(c1)C[n+]1ccc(cc1)-c1cc[n+](cc1)Cc1cc(cc(c1)C[n+]1ccc(cc1)-c1cc[n+](cc1)CC)C[n+]1ccc(cc1)-c1cc[n+](cc1)CC)C[n+]1ccc(cc1)-c1cc[n+](cc1)Cc1cc(cc(c1)C[n+]1ccc(cc1)-c1cc[n+](cc1)CC)C[n+]1ccc(cc1)-c1cc[n+](cc1)CC)C[n+]1ccc(cc1)-c1cc[n+](cc1)Cc1cc(cc(c1)C[n+]1ccc(cc1)-c1cc[n+](cc1)CC)C[n+]1ccc(cc1)-c1cc[n+](cc1)CC)C[n+]1ccc(cc1)-c1cc[n+](cc1)CC", // 826428
             "CC[n+]1ccc(cc1)-c1cc[n+](cc1)Cc1cc(cc(c1)C[n+]1ccc(cc1)-c1cc[n+](cc1)Cc1cc(cc(c1)-[n+]1ccc(cc1)-c1cc[n+](cc1)-c1cc(cc(c1)C[n+]1ccc(cc1)-c1cc[n+](cc1)Cc1cc(cc(c1)C[n+]1ccc(cc1)-c1cc[n+](cc1)CC)C[n+]1ccc(cc1)-c1cc[n+](cc1)CC)C[n+]1ccc(cc1)-c1cc[n+](cc1)Cc1cc(cc(c1)C[n+]1ccc(cc1)-c1cc[n+](cc1)CC)C[n+]1ccc(cc1)-c1cc[n+](cc1)CC)C[n+]1ccc(cc1)-c1cc[n+](cc1)Cc1cc(cc(c1)C[n+]1ccc(cc1)-c1cc[n+](cc1)CC)C[n+]1ccc(cc1)-c1cc[n+](cc1)CC)C[n+]1ccc(cc1)-c1cc[n+](cc1)CC", // 1246825
             "CCC[CH]1CC[CH](CC1)[CH]1CC[CH](CC1)OC(=O)[CH]1[CH](c2ccc(O)cc2)[CH]([CH]1c1ccc(O)cc1)C(=O)O[CH]1CC[CH](CC1)[CH]1CC[CH](CCC)CC1", // CHEMBL415840
@@ -545,7 +597,7 @@ mod test {
         .collect();
         for td in test_data.iter() {
             let p = Parser::new(td);
-            let mut m = p.parse().unwrap();
+            let m = p.parse().unwrap();
             assert_eq!(td, &m.to_smiles().unwrap());
         }
     }
@@ -555,14 +607,28 @@ mod test {
         let p = Parser::new(
             r#"C[C@H](CC[C@@H]([C@@H]([C@H](C)C[C@H](C(=C)/C(=C/CO)/C)O)O)OS(=O)(=O)[O-])[C@H]CO"#,
         );
-        let mut m = p.parse().unwrap();
+        let m = p.parse().unwrap();
         println!("{}", m.to_smiles().unwrap());
     }
 
     #[test]
     fn test_up_and_down() {
         let p = Parser::new("C=C([C@TH1H](O)C[C@TH1H](C)[C@TH2H](O)[C@TH2H](CC[C@TH1H](C)[C@AL1H]CO)OS([O-])(=O)=O)/C(/C)=C/CO");
-        let mut m = p.parse().unwrap();
+        let m = p.parse().unwrap();
         println!("{}", m.to_smiles().unwrap());
     }
+    #[test]
+    fn test_large_molecule() {
+        let smiles = "CC(=O)N".repeat(300);
+        let p = Parser::new(&smiles);
+        let m = p.parse().unwrap();
+        assert_eq!(1200, m.order());
+        assert!(!m.to_smiles().unwrap().is_empty());
+
+        let smiles = format!("C1{}1", "C".repeat(1100));
+        let p = Parser::new(&smiles);
+        let m = p.parse().unwrap();
+        assert_eq!(1101, m.order());
+        assert_eq!(1101, m.size());
+    }
 }
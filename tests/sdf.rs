@@ -0,0 +1,100 @@
+#[cfg(test)]
+mod test {
+    use rayon::prelude::*;
+    use ruatom::error::RuatomError;
+    use ruatom::molfile::write_molfile;
+    use ruatom::parser::Parser;
+    use ruatom::sdf::{SdfReader, SdfWriter};
+
+    fn record(smiles: &str, props: &str) -> String {
+        let m = Parser::new(smiles).parse().unwrap();
+        format!("{}{}$$$$\n", write_molfile(&m).unwrap(), props)
+    }
+
+    fn sdf() -> String {
+        let mut s = record(
+            "CCO",
+            "> <ID>\nE-1\n\n> <NOTE>\nfirst line\nsecond line\n\n",
+        );
+        s += "broken\n\n\n  1  0  0  0  0  0  0  0  0  0999 V2000\nM  END\n> <ID>\nE-2\n\n$$$$\n";
+        s += &record("c1ccccc1", "> 25 <ID> (MFCD-3)\nE-3\n\n");
+        s += &record("CC(=O)[O-]", "");
+        s
+    }
+
+    #[test]
+    fn test_read() {
+        let text = sdf();
+        let mut records: Vec<_> = SdfReader::new(text.as_bytes()).collect();
+        assert_eq!(records.len(), 4);
+
+        let (m, props) = records.remove(0).unwrap();
+        assert_eq!(m.to_smiles().unwrap(), "CCO");
+        assert_eq!(
+            props,
+            vec![
+                ("ID".to_string(), "E-1".to_string()),
+                ("NOTE".to_string(), "first line\nsecond line".to_string()),
+            ]
+        );
+
+        assert!(matches!(
+            &records[0],
+            Err(RuatomError::IllegalSdfRecord(2, e))
+                if **e == RuatomError::IllegalMolfile(5, "invalid coordinate")
+        ));
+
+        let (_, props) = records.remove(1).unwrap();
+        assert_eq!(props, vec![("ID".to_string(), "E-3".to_string())]);
+        let (m, props) = records.remove(1).unwrap();
+        assert_eq!(m.order(), 4);
+        assert!(props.is_empty());
+    }
+
+    #[test]
+    fn test_read_unterminated() {
+        let mut text = record("CCO", "");
+        text += &record("CCN", "> <ID>\nE-2\n\n");
+        text.truncate(text.len() - "$$$$\n".len());
+        let records: Vec<_> = SdfReader::new(text.as_bytes())
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].1, vec![("ID".to_string(), "E-2".to_string())]);
+        assert_eq!(SdfReader::new("\n\n".as_bytes()).count(), 0);
+    }
+
+    #[test]
+    fn test_write() {
+        let text = sdf();
+        let mut writer = SdfWriter::new(Vec::new());
+        for r in SdfReader::new(text.as_bytes()).flatten() {
+            writer.write(&r.0, &r.1).unwrap();
+        }
+        let out = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(out.matches("$$$$").count(), 3);
+
+        let back: Vec<_> = SdfReader::new(out.as_bytes()).map(|r| r.unwrap()).collect();
+        let orig: Vec<_> = SdfReader::new(text.as_bytes()).flatten().collect();
+        for ((a, pa), (b, pb)) in back.into_iter().zip(orig) {
+            assert_eq!(a.to_smiles().unwrap(), b.to_smiles().unwrap());
+            assert_eq!(pa, pb);
+        }
+    }
+
+    #[test]
+    fn test_parallel() {
+        let text = sdf().repeat(8);
+        let mut results: Vec<(usize, bool)> = SdfReader::new(text.as_bytes())
+            .blocks()
+            .par_bridge()
+            .map(|b| {
+                let b = b.unwrap();
+                (b.record, b.parse().is_ok())
+            })
+            .collect();
+        results.sort();
+        assert_eq!(results.len(), 32);
+        assert!(results.iter().all(|(r, ok)| *ok == (r % 4 != 2)));
+    }
+}
@@ -0,0 +1,185 @@
+#[cfg(test)]
+mod test {
+    use ruatom::error::RuatomError;
+    use ruatom::molecule::Molecule;
+    use ruatom::parser::Parser;
+    use ruatom::smarts::{AtomPrimitive, BondPrimitive, Expr, QueryMol, SmartsParser};
+
+    fn mol(smiles: &str) -> Molecule {
+        Parser::new(smiles).parse().unwrap()
+    }
+
+    fn query(smarts: &str) -> QueryMol {
+        SmartsParser::new(smarts).parse().unwrap()
+    }
+
+    fn is_match(smarts: &str, smiles: &str) -> bool {
+        query(smarts).is_match(&mol(smiles)).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let q = query("C(=O)[OH]");
+        assert_eq!(q.atoms().len(), 3);
+        assert_eq!(
+            q.atoms()[0],
+            Expr::Primitive(AtomPrimitive::Symbol(6, false))
+        );
+        assert_eq!(q.bond(1, 2), Some(&Expr::Primitive(BondPrimitive::Double)));
+        assert_eq!(
+            q.bond(3, 1),
+            Some(&Expr::Primitive(BondPrimitive::Implicit))
+        );
+        assert_eq!(
+            q.atoms()[2],
+            Expr::And(
+                Box::new(Expr::Primitive(AtomPrimitive::Symbol(8, false))),
+                Box::new(Expr::Primitive(AtomPrimitive::TotalH(1)))
+            )
+        );
+
+        let q = query("c1ccccc1");
+        assert_eq!(q.bonds().len(), 6);
+        assert_eq!(query("[2H]").atoms()[0].to_string(), "2&#1");
+        assert_eq!(query("[Cl-]").atoms()[0].to_string(), "Cl&-");
+        assert_eq!(query("[se]").atoms()[0].to_string(), "se");
+        assert_eq!(query("[Fe++]").atoms()[0].to_string(), "Fe&+2");
+    }
+
+    #[test]
+    fn test_atom_primitives() {
+        assert!(is_match("[#6]", "c1ccccc1"));
+        assert!(!is_match("[#7]", "CCO"));
+        assert!(is_match("a", "c1ccccc1"));
+        assert!(!is_match("A", "c1ccccc1"));
+        assert!(is_match("A", "c1ccccc1C"));
+        assert!(is_match("[CD3]", "CC(C)C"));
+        assert!(!is_match("[CD4]", "CC(C)C"));
+        assert!(is_match("[CX4]", "C"));
+        assert!(is_match("[CH3]", "CC"));
+        assert!(!is_match("[CH4]", "CC"));
+        assert!(is_match("[OH]", "[H]OC"));
+        assert!(is_match("[CR2]", "C1CCC2CCCCC2C1"));
+        assert!(!is_match("[CR]", "CCC"));
+        assert!(is_match("[R]", "C1CC1"));
+        assert!(is_match("[r5]", "C1CCCC1C"));
+        assert!(!is_match("[r6]", "C1CCCC1C"));
+        assert!(is_match("[r3]", "C1CC12CCCC2"));
+        assert!(is_match("[Nv4]", "C[N+](=O)[O-]"));
+        assert!(is_match("[Cv4]", "CC"));
+        assert!(is_match("[N+]", "C[NH3+]"));
+        assert!(!is_match("[N+]", "CN"));
+        assert!(is_match("[O-]", "CC(=O)[O-]"));
+        assert!(is_match("[13C]", "[13CH4]"));
+        assert!(!is_match("[13C]", "C"));
+        assert!(is_match("[#1]", "[H][H]"));
+    }
+
+    #[test]
+    fn test_logic() {
+        assert!(is_match("[!C]", "CO"));
+        assert!(!is_match("[!C]", "CC"));
+        assert!(is_match("[N,O]", "CO"));
+        assert!(!is_match("[N,O]", "CC"));
+        assert!(is_match("[C&H3]", "CC"));
+        assert!(!is_match("[C&H2]", "CC"));
+        // `&` binds tighter than `,`, which binds tighter than `;`
+        assert!(is_match("[C,NH2]", "CC"));
+        assert!(is_match("[O,N;H2]", "CN"));
+        assert!(!is_match("[O,N;H2]", "CO"));
+        assert!(is_match("[!#6;!#1]", "CO"));
+        assert!(is_match("[!!#6]", "C"));
+    }
+
+    #[test]
+    fn test_bond_primitives() {
+        assert!(is_match("C~O", "C=O"));
+        assert!(is_match("C~O", "CO"));
+        assert!(is_match("C-O", "CO"));
+        assert!(!is_match("C-O", "C=O"));
+        assert!(is_match("c:c", "c1ccccc1"));
+        assert!(is_match("cc", "c1ccccc1"));
+        assert!(!is_match("c-c", "c1ccccc1"));
+        assert!(is_match("C@C", "C1CC1"));
+        assert!(!is_match("C@C", "CC"));
+        assert!(is_match("C!@C", "CC1CC1"));
+        assert!(!is_match("C!@C", "C1CC1"));
+        assert!(is_match("C-&@C", "C1CC1"));
+        assert!(is_match("C=,#C", "C#C"));
+        assert!(!is_match("C=,#C", "CC"));
+    }
+
+    #[test]
+    fn test_recursive() {
+        // carbon next to an oxygen
+        let q = query("[C;$(CO)]");
+        assert_eq!(q.matches(&mol("CCO")).unwrap(), vec![vec![2]]);
+        assert!(is_match("[$(C=O);!$(C(=O)O)]", "CC=O"));
+        assert!(!is_match("[$(C=O);!$(C(=O)O)]", "CC(=O)O"));
+        assert!(is_match("[$([NH2]c1ccccc1)]", "Nc1ccccc1"));
+        assert!(is_match("[$(*[N+](=O)[O-]),$(*N(=O)=O)]", "C[N+](=O)[O-]"));
+    }
+
+    #[test]
+    fn test_matches() {
+        let q = query("[#6]~[#8]");
+        let target = mol("OCCO");
+        assert_eq!(q.matches(&target).unwrap().len(), 2);
+        assert_eq!(q.first(&target).unwrap(), Some(vec![2, 1]));
+        assert_eq!(query("*").unique_matches(&target).unwrap().len(), 4);
+        assert!(query("C.C").is_match(&mol("CC")).unwrap());
+        assert!(!query("C.C").is_match(&mol("C")).unwrap());
+    }
+
+    #[test]
+    fn test_to_smarts() {
+        for smarts in [
+            "CC(=O)O",
+            "c1ccccc1",
+            "[#6]~[#8]",
+            "[N&X3][C&X3](=[O&X1])[#6]",
+            "[C;$(C=O)]",
+            "[!#6;!#1]",
+            "C-&@C!@C",
+            "C1CC2CC1CC2",
+            "C.O",
+            "[13C&H3][Cl]",
+            "[Fe&+2]",
+            "[n&H]1cccc1",
+            "C%10CC%10",
+        ] {
+            let q = query(smarts);
+            let written = q.to_smarts();
+            assert_eq!(query(&written), q, "{} -> {}", smarts, written);
+        }
+        assert_eq!(query("CC(=O)O").to_smarts(), "CC(=O)O");
+        assert_eq!(query("c1ccccc1").to_smarts(), "c1ccccc1");
+        assert_eq!(query("[C,N;!R]=O").to_smarts(), "[C,N;!R]=O");
+        assert_eq!(query("[Cl]C").to_smarts(), "ClC");
+        assert_eq!(query("C%12CC%12").to_smarts(), "C1CC1");
+    }
+
+    #[test]
+    fn test_errors() {
+        for (smarts, at) in [
+            ("", 0),
+            ("C(", 2),
+            ("C)", 1),
+            ("C1CC", 4),
+            ("[C", 2),
+            ("C=", 2),
+            ("[Q]", 1),
+            ("[$C]", 2),
+            ("[$(CC]", 5),
+            ("C%1C", 3),
+            ("Xx", 0),
+        ] {
+            match SmartsParser::new(smarts).parse() {
+                Err(RuatomError::IllegalSMARTS(_, position)) => {
+                    assert_eq!(position, at, "{}", smarts)
+                }
+                r => panic!("{}: {:?}", smarts, r),
+            }
+        }
+    }
+}
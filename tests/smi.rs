@@ -0,0 +1,98 @@
+#[cfg(test)]
+mod test {
+    use rayon::prelude::*;
+    use ruatom::error::RuatomError;
+    use ruatom::smi::SmiReader;
+
+    const SMI: &str = "# test set\n\
+                       CCO ethanol\n\
+                       \n\
+                       c1ccccc1\tbenzene ring\n\
+                       C1CC broken\n\
+                       CC(=O)O acetic acid\r\n";
+
+    #[test]
+    fn test_read_smi() {
+        let records: Vec<_> = SmiReader::new(SMI.as_bytes()).collect();
+        assert_eq!(records.len(), 4);
+
+        let r = records[0].as_ref().ok().unwrap();
+        assert_eq!((r.line, r.title()), (2, Some("ethanol")));
+        assert_eq!(r.molecule.order(), 3);
+        let r = records[1].as_ref().ok().unwrap();
+        assert_eq!((r.line, r.title()), (4, Some("benzene ring")));
+        assert!(matches!(
+            &records[2],
+            Err(RuatomError::IllegalSmilesRecord(5, text, e))
+                if text == "C1CC broken" && matches!(
+                    &**e,
+                    RuatomError::SmilesSyntax(e)
+                        if *e.reason == RuatomError::IllegalSMILES("unclosed ring")
+                )
+        ));
+        let r = records[3].as_ref().ok().unwrap();
+        assert_eq!((r.line, r.title()), (6, Some("acetic acid")));
+
+        let r = SmiReader::new("CC\n".as_bytes())
+            .next()
+            .unwrap()
+            .ok()
+            .unwrap();
+        assert_eq!(r.title(), None);
+    }
+
+    #[test]
+    fn test_read_csv() {
+        let csv = "id,name,SMILES,mw\n\
+                   1,\"ethanol, absolute\",CCO,46.07\n\
+                   2,empty,,0\n\
+                   3,\"the \"\"ring\"\"\",c1ccccc1,78.11\n";
+        let reader = SmiReader::csv(csv.as_bytes(), ',').unwrap();
+        assert_eq!(reader.columns(), ["id", "name", "SMILES", "mw"]);
+        let records: Vec<_> = reader.collect();
+        assert_eq!(records.len(), 3);
+
+        let r = records[0].as_ref().ok().unwrap();
+        assert_eq!(r.line, 2);
+        assert_eq!(r.fields, ["1", "ethanol, absolute", "46.07"]);
+        assert!(matches!(
+            &records[1],
+            Err(RuatomError::IllegalSmilesRecord(3, _, e))
+                if **e == RuatomError::IllegalSMILES("empty SMILES")
+        ));
+        let r = records[2].as_ref().ok().unwrap();
+        assert_eq!(r.fields, ["3", "the \"ring\"", "78.11"]);
+        assert_eq!(r.molecule.order(), 6);
+
+        let tsv = "smiles\tname\nCCN\tethylamine\n";
+        let mut reader = SmiReader::csv(tsv.as_bytes(), '\t').unwrap();
+        assert_eq!(
+            reader.next().unwrap().ok().unwrap().title(),
+            Some("ethylamine")
+        );
+    }
+
+    #[test]
+    fn test_parallel() {
+        let text = SMI.repeat(50);
+        let records = SmiReader::new(text.as_bytes()).par_read();
+        assert_eq!(records.len(), 200);
+        let lines: Vec<usize> = records
+            .iter()
+            .map(|r| match r {
+                Ok(r) => r.line,
+                Err(RuatomError::IllegalSmilesRecord(line, _, _)) => *line,
+                Err(_) => unreachable!(),
+            })
+            .collect();
+        assert!(lines.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(records.iter().filter(|r| r.is_err()).count(), 50);
+
+        let count = SmiReader::new(text.as_bytes())
+            .lines()
+            .par_bridge()
+            .filter_map(|l| l.unwrap().parse().ok())
+            .count();
+        assert_eq!(count, 150);
+    }
+}
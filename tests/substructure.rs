@@ -0,0 +1,89 @@
+#[cfg(test)]
+mod test {
+    use ruatom::molecule::{Molecule, SubstructureMatcher};
+    use ruatom::parser::Parser;
+
+    fn mol(smiles: &str) -> Molecule {
+        Parser::new(smiles).parse().unwrap()
+    }
+
+    #[test]
+    fn test_substructure() {
+        let toluene = mol("Cc1ccccc1");
+        assert!(toluene.has_substructure(&mol("c1ccccc1")).unwrap());
+        assert!(toluene.has_substructure(&mol("*c1ccccc1")).unwrap());
+        assert!(!toluene.has_substructure(&mol("C1CCCCC1")).unwrap());
+        assert!(!toluene.has_substructure(&mol("CC")).unwrap());
+        assert!(mol("CCO").has_substructure(&mol("C.O")).unwrap());
+        assert!(!mol("CO").has_substructure(&mol("CCO")).unwrap());
+    }
+
+    #[test]
+    fn test_matches() {
+        let benzene = mol("c1ccccc1");
+        let m = SubstructureMatcher::new(&benzene);
+        assert_eq!(m.matches(&mol("Cc1ccccc1")).unwrap().len(), 12);
+        assert_eq!(m.unique_matches(&mol("Cc1ccccc1")).unwrap().len(), 1);
+
+        let cc = mol("CC");
+        let m = SubstructureMatcher::new(&cc);
+        assert_eq!(m.matches(&mol("CCC")).unwrap().len(), 4);
+        assert_eq!(m.unique_matches(&mol("CCC")).unwrap().len(), 2);
+
+        let co = mol("CO");
+        let target = mol("CCCO");
+        let first = SubstructureMatcher::new(&co).first(&target).unwrap();
+        assert_eq!(first, Some(vec![3, 4]));
+        assert_eq!(
+            SubstructureMatcher::new(&co).first(&mol("CC")).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_bond_order() {
+        let acid = mol("CC(=O)O");
+        assert!(acid.has_substructure(&mol("C=O")).unwrap());
+        assert!(acid.has_substructure(&mol("CO")).unwrap());
+        assert!(!mol("CCO").has_substructure(&mol("C=O")).unwrap());
+        assert!(!mol("CC#N").has_substructure(&mol("C=N")).unwrap());
+        // aromatic bonds only match aromatic bonds
+        assert!(!mol("c1ccccc1").has_substructure(&mol("C=C")).unwrap());
+        let biphenyl = mol("c1ccccc1-c1ccccc1");
+        assert!(mol("Cc1ccc(cc1)-c1ccccc1")
+            .has_substructure(&biphenyl)
+            .unwrap());
+    }
+
+    #[test]
+    fn test_charge_and_isotope() {
+        let anion = mol("[O-]");
+        assert!(!mol("CC(=O)O").has_substructure(&anion).unwrap());
+        assert!(mol("CC(=O)[O-]").has_substructure(&anion).unwrap());
+        assert!(mol("CC(=O)[O-]").has_substructure(&mol("C(=O)O")).unwrap());
+        let m = SubstructureMatcher::new(&anion).charge(false);
+        assert!(m.is_match(&mol("CCO")).unwrap());
+
+        let labelled = mol("[13CH3]C");
+        assert!(!mol("CC").has_substructure(&labelled).unwrap());
+        assert!(mol("[13CH3]CC").has_substructure(&labelled).unwrap());
+        let m = SubstructureMatcher::new(&labelled).isotope(false);
+        assert!(m.is_match(&mol("CC")).unwrap());
+    }
+
+    #[test]
+    fn test_chirality() {
+        let query = mol("C[C@H](O)N");
+        let m = SubstructureMatcher::new(&query).chirality(true);
+        assert!(m.is_match(&mol("C[C@H](O)N")).unwrap());
+        assert!(m.is_match(&mol("C[C@@H](N)O")).unwrap());
+        assert!(m.is_match(&mol("CN[C@H](C)O")).unwrap());
+        assert!(!m.is_match(&mol("CN[C@@H](C)O")).unwrap());
+        assert!(!m.is_match(&mol("C[C@H](N)O")).unwrap());
+        assert!(!m.is_match(&mol("CC(N)O")).unwrap());
+
+        let m = SubstructureMatcher::new(&query);
+        assert!(m.is_match(&mol("C[C@H](N)O")).unwrap());
+        assert!(m.is_match(&mol("CC(N)O")).unwrap());
+    }
+}
@@ -1,9 +1,13 @@
-use ruatom::{molecule::expand, Parser};
+use ruatom::error::RuatomError;
+use ruatom::{
+    molecule::{expand, kekulize},
+    Parser,
+};
 
 #[test]
 fn test_expand_ethanol() {
     let mol = Parser::new("CCO").parse().unwrap();
-    let mut expanded = expand(&mol).unwrap();
+    let expanded = expand(&mol).unwrap();
     let smiles = expanded.to_smiles().unwrap();
     assert_eq!(smiles, "[CH3][CH2][OH]");
 }
@@ -11,7 +15,7 @@ fn test_expand_ethanol() {
 #[test]
 fn test_expand_benzene() {
     let mol = Parser::new("c1ccccc1").parse().unwrap();
-    let mut expanded = expand(&mol).unwrap();
+    let expanded = expand(&mol).unwrap();
     let smiles = expanded.to_smiles().unwrap();
     assert!(smiles.contains("c"));
 }
@@ -19,7 +23,7 @@ fn test_expand_benzene() {
 #[test]
 fn test_expand_methane() {
     let mol = Parser::new("C").parse().unwrap();
-    let mut expanded = expand(&mol).unwrap();
+    let expanded = expand(&mol).unwrap();
     let smiles = expanded.to_smiles().unwrap();
     assert_eq!(smiles, "[CH4]");
 }
@@ -27,7 +31,7 @@ fn test_expand_methane() {
 #[test]
 fn test_expand_ammonia() {
     let mol = Parser::new("N").parse().unwrap();
-    let mut expanded = expand(&mol).unwrap();
+    let expanded = expand(&mol).unwrap();
     let smiles = expanded.to_smiles().unwrap();
     assert_eq!(smiles, "[NH3]");
 }
@@ -35,7 +39,7 @@ fn test_expand_ammonia() {
 #[test]
 fn test_expand_water() {
     let mol = Parser::new("O").parse().unwrap();
-    let mut expanded = expand(&mol).unwrap();
+    let expanded = expand(&mol).unwrap();
     let smiles = expanded.to_smiles().unwrap();
     assert_eq!(smiles, "[OH2]");
 }
@@ -43,7 +47,7 @@ fn test_expand_water() {
 #[test]
 fn test_expand_methanol() {
     let mol = Parser::new("CO").parse().unwrap();
-    let mut expanded = expand(&mol).unwrap();
+    let expanded = expand(&mol).unwrap();
     let smiles = expanded.to_smiles().unwrap();
     assert!(
         smiles.contains("[CH") && smiles.contains("[O"),
@@ -60,7 +64,83 @@ fn test_atom_based_db_stereo_to_trigonal() {
     let mol = Parser::new("F/C=C/F").parse().unwrap();
     let m1 = implicit_to_explicit(&mol).unwrap();
     let m2 = atom_based_db_stereo(&m1).unwrap();
-    let mut m3 = explicit_to_implicit(&m2).unwrap();
+    let m3 = explicit_to_implicit(&m2).unwrap();
     let smiles = m3.to_smiles().unwrap();
     assert_eq!(smiles, "F[C@H]=[C@@H]F");
 }
+
+#[test]
+fn test_atom_class_through_expand_collapse() {
+    use ruatom::molecule::collapse;
+    let mol = Parser::new("[CH3:12]C[OH:3]").parse().unwrap();
+    let expanded = expand(&mol).unwrap();
+    assert_eq!(expanded.to_smiles().unwrap(), "[CH3:12][CH2][OH:3]");
+    let collapsed = collapse(&expanded).unwrap();
+    let classes: Vec<u32> = (1..=3)
+        .map(|i| collapsed.atom_at(&i).unwrap().atom_class())
+        .collect();
+    assert_eq!(classes, [12, 0, 3]);
+}
+
+fn hydrogens(mol: &ruatom::molecule::Molecule) -> Vec<u8> {
+    mol.atoms()
+        .iter()
+        .map(|i| mol.hydrogen_count(i).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_kekulize() {
+    for (smiles, kekule) in [
+        ("c1ccccc1", "C1=CC=CC=C1"),
+        ("c1cc[nH]c1", "C1C=CNC=1"),
+        ("c1ccc[n+]([O-])c1", "[O-][N+]1C=CC=CC=1"),
+        ("[cH-]1cccc1", "C1C=C[CH-]C=1"),
+        ("Cn1cnc2c1c(=O)n(C)c(=O)n2C", "CN1C=NC2=C1C(=O)N(C)C(=O)N2C"),
+    ] {
+        let mol = Parser::new(smiles).parse().unwrap();
+        let k = kekulize(&mol).unwrap();
+        assert_eq!(k.to_smiles().unwrap(), kekule);
+        assert_eq!(hydrogens(&k), hydrogens(&mol), "{}", smiles);
+        assert!(k
+            .atoms()
+            .iter()
+            .all(|i| !k.atom_at(i).unwrap().is_aromatic()));
+    }
+}
+
+#[test]
+fn test_kekulize_fused() {
+    for smiles in [
+        "c1ccc2ccccc2c1",
+        "c1ccc2[nH]ccc2c1",
+        "c1cc2cccccc2c1",
+        "c1ccc2c(c1)ccc1ccccc12",
+    ] {
+        let mol = Parser::new(smiles).parse().unwrap();
+        let k = kekulize(&mol).unwrap();
+        let doubles = k
+            .bonds()
+            .iter()
+            .filter(|[u, v]| k.edge_at(*u, *v).unwrap().is("="))
+            .count();
+        assert_eq!(doubles * 2, mol.order() - smiles.matches("[nH]").count());
+        assert_eq!(hydrogens(&k), hydrogens(&mol), "{}", smiles);
+    }
+}
+
+#[test]
+fn test_kekulize_keeps_stereo() {
+    let mol = Parser::new("C[C@H](N)c1ccccc1").parse().unwrap();
+    let k = kekulize(&mol).unwrap();
+    assert_eq!(k.to_smiles().unwrap(), "C[C@TH1H](N)C1C=CC=CC=1");
+}
+
+#[test]
+fn test_kekulize_impossible() {
+    let mol = Parser::new("c1cccc1").parse().unwrap();
+    assert_eq!(
+        kekulize(&mol).err(),
+        Some(RuatomError::NoKekuleStructure(vec![5]))
+    );
+}